@@ -44,3 +44,154 @@ INSERT INTO users (fullname, email, password_hash, ws_id) VALUES ('Alice', 'alic
 INSERT INTO users (fullname, email, password_hash, ws_id) VALUES ('Alice', 'alice1@none.co', '', 0);
 INSERT INTO users (fullname, email, password_hash, ws_id) VALUES ('Alice2', 'alice2@none.co', '', 0);
 INSERT INTO users (fullname, email, password_hash, ws_id) VALUES ('Alice3', 'alice3@none.co', '', 0);
+
+-- refresh tokens, grouped by family for rotation and reuse detection
+CREATE TABLE IF NOT EXISTS refresh_token (
+    id BIGSERIAL PRIMARY KEY,
+    jti VARCHAR(32) NOT NULL UNIQUE,
+    family_id VARCHAR(32) NOT NULL,
+    user_id BIGINT NOT NULL REFERENCES users(id),
+    expires_at TIMESTAMPTZ NOT NULL,
+    revoked BOOLEAN NOT NULL DEFAULT FALSE,
+    replaced_by VARCHAR(32),
+    create_at TIMESTAMPTZ DEFAULT CURRENT_TIMESTAMP
+    );
+
+CREATE INDEX IF NOT EXISTS idx_refresh_token_family_id ON refresh_token(family_id);
+
+-- denylist of revoked access tokens, keyed by jti
+CREATE TABLE IF NOT EXISTS revoked_token (
+    jti VARCHAR(32) PRIMARY KEY,
+    expires_at TIMESTAMPTZ NOT NULL,
+    create_at TIMESTAMPTZ DEFAULT CURRENT_TIMESTAMP
+    );
+
+CREATE INDEX IF NOT EXISTS idx_revoked_token_expires_at ON revoked_token(expires_at);
+
+-- roles and permissions for access control
+CREATE TABLE IF NOT EXISTS role (
+    id BIGSERIAL PRIMARY KEY,
+    name VARCHAR(32) NOT NULL UNIQUE,
+    create_at TIMESTAMPTZ DEFAULT CURRENT_TIMESTAMP
+    );
+
+CREATE TABLE IF NOT EXISTS permission (
+    id BIGSERIAL PRIMARY KEY,
+    name VARCHAR(64) NOT NULL UNIQUE,
+    create_at TIMESTAMPTZ DEFAULT CURRENT_TIMESTAMP
+    );
+
+CREATE TABLE IF NOT EXISTS role_permission (
+    role_id BIGINT NOT NULL REFERENCES role(id),
+    permission_id BIGINT NOT NULL REFERENCES permission(id),
+    PRIMARY KEY (role_id, permission_id)
+    );
+
+CREATE TABLE IF NOT EXISTS user_role (
+    user_id BIGINT NOT NULL REFERENCES users(id),
+    role_id BIGINT NOT NULL REFERENCES role(id),
+    PRIMARY KEY (user_id, role_id)
+    );
+
+-- initial values for roles and permissions;
+INSERT INTO role (name) VALUES ('admin'), ('member');
+INSERT INTO permission (name) VALUES
+    ('user:create'), ('user:read'), ('user:update'), ('user:delete'), ('workspace:create');
+
+-- admin is granted every permission, member can only read users
+INSERT INTO role_permission (role_id, permission_id)
+    SELECT r.id, p.id FROM role r, permission p WHERE r.name = 'admin';
+INSERT INTO role_permission (role_id, permission_id)
+    SELECT r.id, p.id FROM role r, permission p WHERE r.name = 'member' AND p.name = 'user:read';
+
+INSERT INTO user_role (user_id, role_id) SELECT 0, id FROM role WHERE name = 'admin';
+INSERT INTO user_role (user_id, role_id)
+    SELECT u.id, r.id FROM users u, role r WHERE u.id <> 0 AND r.name = 'member';
+
+-- moving users out of the caller's workspace is reserved to admins
+INSERT INTO permission (name) VALUES ('user:transfer');
+INSERT INTO role_permission (role_id, permission_id)
+    SELECT r.id, p.id FROM role r, permission p WHERE r.name = 'admin' AND p.name = 'user:transfer';
+
+-- self-service signup: users confirm their email address
+ALTER TABLE users ADD COLUMN email_verified BOOL NOT NULL DEFAULT FALSE;
+-- accounts created before were set up by an admin
+UPDATE users SET email_verified = TRUE;
+
+CREATE TYPE USER_TOKEN_PURPOSE AS ENUM('verify_email', 'reset_password');
+
+-- single-use tokens mailed to users, only the sha256 hash of the token is stored
+CREATE TABLE IF NOT EXISTS user_token (
+    id BIGSERIAL PRIMARY KEY,
+    user_id BIGINT NOT NULL REFERENCES users(id),
+    purpose USER_TOKEN_PURPOSE NOT NULL,
+    token_hash VARCHAR(64) NOT NULL UNIQUE,
+    expires_at TIMESTAMPTZ NOT NULL,
+    used_at TIMESTAMPTZ,
+    create_at TIMESTAMPTZ DEFAULT CURRENT_TIMESTAMP
+    );
+
+CREATE INDEX IF NOT EXISTS idx_user_token_user_id ON user_token (user_id);
+
+-- argon2id hashes with larger parameters do not fit into 97 characters
+ALTER TABLE users ALTER COLUMN password_hash TYPE VARCHAR(128);
+
+-- TOTP second factor, enabled once the first code has been confirmed
+CREATE TABLE IF NOT EXISTS user_mfa (
+    user_id BIGINT PRIMARY KEY REFERENCES users(id),
+    secret VARCHAR(64) NOT NULL,
+    enabled BOOL NOT NULL DEFAULT FALSE,
+    last_used_step BIGINT NOT NULL DEFAULT 0,
+    create_at TIMESTAMPTZ DEFAULT CURRENT_TIMESTAMP
+    );
+
+-- single-use recovery codes, only the sha256 hash of a code is stored
+CREATE TABLE IF NOT EXISTS mfa_recovery_code (
+    id BIGSERIAL PRIMARY KEY,
+    user_id BIGINT NOT NULL REFERENCES users(id),
+    code_hash VARCHAR(64) NOT NULL UNIQUE,
+    used_at TIMESTAMPTZ,
+    create_at TIMESTAMPTZ DEFAULT CURRENT_TIMESTAMP
+    );
+
+CREATE INDEX IF NOT EXISTS idx_mfa_recovery_code_user_id ON mfa_recovery_code (user_id);
+
+-- API keys for services and scripts, scoped to a workspace; only the sha256 hash of a key is stored
+CREATE TABLE IF NOT EXISTS api_key (
+    id BIGSERIAL PRIMARY KEY,
+    ws_id BIGINT NOT NULL REFERENCES workspace(id),
+    user_id BIGINT NOT NULL REFERENCES users(id),
+    name VARCHAR(64) NOT NULL,
+    prefix VARCHAR(16) NOT NULL,
+    key_hash VARCHAR(64) NOT NULL UNIQUE,
+    -- space separated permissions, a subset of the creator's permissions
+    scopes TEXT NOT NULL,
+    expires_at TIMESTAMPTZ,
+    last_used_at TIMESTAMPTZ,
+    revoked_at TIMESTAMPTZ,
+    create_at TIMESTAMPTZ DEFAULT CURRENT_TIMESTAMP
+    );
+
+CREATE INDEX IF NOT EXISTS idx_api_key_ws_id ON api_key (ws_id);
+
+INSERT INTO permission (name) VALUES ('api_key:manage');
+INSERT INTO role_permission (role_id, permission_id)
+    SELECT r.id, p.id FROM role r, permission p WHERE r.name = 'admin' AND p.name = 'api_key:manage';
+
+-- accounts at external OpenID Connect providers linked to users
+CREATE TABLE IF NOT EXISTS user_identity (
+    id BIGSERIAL PRIMARY KEY,
+    user_id BIGINT NOT NULL REFERENCES users(id),
+    provider VARCHAR(32) NOT NULL,
+    subject VARCHAR(255) NOT NULL,
+    email VARCHAR(64),
+    create_at TIMESTAMPTZ DEFAULT CURRENT_TIMESTAMP,
+    UNIQUE (provider, subject)
+    );
+
+CREATE INDEX IF NOT EXISTS idx_user_identity_user_id ON user_identity (user_id);
+
+-- changing the log levels of the running server
+INSERT INTO permission (name) VALUES ('logging:manage');
+INSERT INTO role_permission (role_id, permission_id)
+    SELECT r.id, p.id FROM role r, permission p WHERE r.name = 'admin' AND p.name = 'logging:manage';
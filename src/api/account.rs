@@ -0,0 +1,412 @@
+use crate::application::AppState;
+use crate::common::hash_password;
+use crate::config::AppConfig;
+use crate::entity::prelude::*;
+use crate::entity::sea_orm_active_enums::UserTokenPurpose;
+use crate::entity::{refresh_token, role, user_role, user_token, users, workspace};
+use crate::error::ApiError;
+use crate::mailer::Mail;
+use crate::redact::Email;
+use crate::request::BValidJson;
+use crate::response::{ApiResponse, ApiResult};
+use axum::extract::State;
+use axum::routing::post;
+use axum::{debug_handler, Router};
+use base64::engine::general_purpose::URL_SAFE_NO_PAD;
+use base64::Engine;
+use rand::RngCore;
+use sea_orm::prelude::*;
+use sea_orm::{Set, TransactionTrait};
+use serde::Deserialize;
+use sha2::{Digest, Sha256};
+use validator::Validate;
+
+/// Role granted to users who sign up by themselves.
+const SIGNUP_ROLE: &str = "member";
+
+/// Response of `forgot-password`, identical whether the account exists or not.
+const RESET_MAIL_SENT: &str =
+    "If the email belongs to an account, a password reset link has been sent.";
+
+/// Response of `register`, identical whether the email is already registered or not.
+const SIGNUP_MAIL_SENT: &str = "Registration received, please check your email to continue.";
+
+#[derive(Debug, Deserialize, Validate)]
+pub struct RegisterParams {
+    #[validate(length(
+        min = 1,
+        max = 14,
+        message = "fullname must be between 1 and 14 characters"
+    ))]
+    fullname: String,
+    #[validate(custom(
+        function = "crate::request::is_email_valid",
+        message = "invalid email format, please check."
+    ))]
+    email: String,
+    #[validate(custom(
+        function = "crate::request::is_password_strong",
+        message = "password must be 8 to 128 characters with at least one letter and one digit."
+    ))]
+    password: String,
+}
+
+#[derive(Debug, Deserialize, Validate)]
+pub struct TokenParams {
+    #[validate(length(min = 1, message = "token can not be empty."))]
+    token: String,
+}
+
+#[derive(Debug, Deserialize, Validate)]
+pub struct ForgotPasswordParams {
+    #[validate(custom(
+        function = "crate::request::is_email_valid",
+        message = "invalid email format, please check."
+    ))]
+    email: String,
+}
+
+#[derive(Debug, Deserialize, Validate)]
+pub struct ResetPasswordParams {
+    #[validate(length(min = 1, message = "token can not be empty."))]
+    token: String,
+    #[validate(custom(
+        function = "crate::request::is_password_strong",
+        message = "password must be 8 to 128 characters with at least one letter and one digit."
+    ))]
+    password: String,
+}
+
+/// Public self-service account routes.
+pub(crate) fn routes() -> Router<AppState> {
+    Router::new()
+        .route("/register", post(register))
+        .route("/verify-email", post(verify_email))
+        .route("/forgot-password", post(forgot_password))
+        .route("/reset-password", post(reset_password))
+}
+
+/// Signs up a new user in a personal workspace and mails an email verification link.
+///
+/// An already registered email gets a notice mail instead, the response and its timing
+/// are the same so that the endpoint can not be used to probe for accounts.
+#[debug_handler]
+#[tracing::instrument(name = "register", skip_all, fields(email = %Email(&params.email)))]
+pub async fn register(
+    State(state): State<AppState>,
+    BValidJson(params): BValidJson<RegisterParams>,
+) -> ApiResult<()> {
+    let db = state.db();
+
+    let existing = Users::find()
+        .filter(users::Column::Email.eq(&params.email))
+        .one(db)
+        .await?;
+    if existing.is_some() {
+        // hash anyway, a registered email must take as long as a new one
+        if let Err(e) = hash_password(&params.password).await {
+            tracing::error!("error hashing the password of a duplicate signup: {:?}", e);
+        }
+        tracing::warn!("signup with a registered email: {}", Email(&params.email));
+        send_mail(
+            &state,
+            Mail {
+                to: params.email,
+                subject: "Your account already exists".to_string(),
+                body: "Someone tried to sign up with this email address. If it was you, \
+                       use the password reset instead."
+                    .to_string(),
+            },
+        )
+        .await;
+        return Ok(ApiResponse::success(SIGNUP_MAIL_SENT, None));
+    }
+
+    let password_hash = hash_password(&params.password).await?;
+
+    let txn = db.begin().await?;
+    let user = create_account(&txn, params.fullname, params.email, password_hash, false).await?;
+
+    let config = AppConfig::get().account();
+    let token = issue_user_token(
+        &txn,
+        user.id,
+        UserTokenPurpose::VerifyEmail,
+        config.verify_email_ttl(),
+    )
+    .await?;
+
+    txn.commit().await?;
+    tracing::info!("user signed up, id: {}, workspace: {}", user.id, user.ws_id);
+
+    send_mail(
+        &state,
+        Mail {
+            to: user.email,
+            subject: "Verify your email".to_string(),
+            body: format!(
+                "Open the link below to verify your email:\n{}/verify-email?token={}",
+                config.link_base_url(),
+                token
+            ),
+        },
+    )
+    .await;
+
+    Ok(ApiResponse::success(SIGNUP_MAIL_SENT, None))
+}
+
+/// Marks the email of the token's user as verified.
+#[debug_handler]
+#[tracing::instrument(name = "verify_email", skip_all)]
+pub async fn verify_email(
+    State(AppState { db, .. }): State<AppState>,
+    BValidJson(TokenParams { token }): BValidJson<TokenParams>,
+) -> ApiResult<()> {
+    let txn = db.begin().await?;
+
+    let user_id = consume_user_token(&txn, &token, UserTokenPurpose::VerifyEmail).await?;
+    Users::update_many()
+        .col_expr(users::Column::EmailVerified, Expr::value(true))
+        .filter(users::Column::Id.eq(user_id))
+        .exec(&txn)
+        .await?;
+
+    txn.commit().await?;
+    tracing::info!("email verified, user: {}", user_id);
+
+    Ok(ApiResponse::success("Email verified successfully!", None))
+}
+
+/// Mails a password reset link if the email belongs to an account.
+#[debug_handler]
+#[tracing::instrument(name = "forgot_password", skip_all, fields(email = %Email(&email)))]
+pub async fn forgot_password(
+    State(state): State<AppState>,
+    BValidJson(ForgotPasswordParams { email }): BValidJson<ForgotPasswordParams>,
+) -> ApiResult<()> {
+    let db = state.db();
+
+    let Some(user) = Users::find()
+        .filter(users::Column::Email.eq(&email))
+        .one(db)
+        .await?
+    else {
+        tracing::warn!(
+            "password reset requested for unknown email: {}",
+            Email(&email)
+        );
+        return Ok(ApiResponse::success(RESET_MAIL_SENT, None));
+    };
+
+    let config = AppConfig::get().account();
+    let token = issue_user_token(
+        db,
+        user.id,
+        UserTokenPurpose::ResetPassword,
+        config.reset_password_ttl(),
+    )
+    .await?;
+
+    send_mail(
+        &state,
+        Mail {
+            to: user.email,
+            subject: "Reset your password".to_string(),
+            body: format!(
+                "Open the link below to choose a new password:\n{}/reset-password?token={}\n\
+                 If you did not request a password reset, ignore this mail.",
+                config.link_base_url(),
+                token
+            ),
+        },
+    )
+    .await;
+
+    Ok(ApiResponse::success(RESET_MAIL_SENT, None))
+}
+
+/// Sets a new password and signs the user out of every session.
+#[debug_handler]
+#[tracing::instrument(name = "reset_password", skip_all)]
+pub async fn reset_password(
+    State(AppState { db, .. }): State<AppState>,
+    BValidJson(ResetPasswordParams { token, password }): BValidJson<ResetPasswordParams>,
+) -> ApiResult<()> {
+    let password_hash = hash_password(&password).await?;
+
+    let txn = db.begin().await?;
+
+    let user_id = consume_user_token(&txn, &token, UserTokenPurpose::ResetPassword).await?;
+    Users::update_many()
+        .col_expr(users::Column::PasswordHash, Expr::value(password_hash))
+        .filter(users::Column::Id.eq(user_id))
+        .exec(&txn)
+        .await?;
+
+    // other reset links of the user are no longer needed
+    UserToken::update_many()
+        .col_expr(user_token::Column::UsedAt, Expr::current_timestamp().into())
+        .filter(user_token::Column::UserId.eq(user_id))
+        .filter(user_token::Column::Purpose.eq(UserTokenPurpose::ResetPassword))
+        .filter(user_token::Column::UsedAt.is_null())
+        .exec(&txn)
+        .await?;
+
+    // refresh tokens issued with the old password must not outlive it
+    RefreshToken::update_many()
+        .col_expr(refresh_token::Column::Revoked, Expr::value(true))
+        .filter(refresh_token::Column::UserId.eq(user_id))
+        .filter(refresh_token::Column::Revoked.eq(false))
+        .exec(&txn)
+        .await?;
+
+    txn.commit().await?;
+    tracing::info!("password reset, user: {}", user_id);
+
+    Ok(ApiResponse::success("Password reset successfully!", None))
+}
+
+/// Creates a user in a new personal workspace, with the role of self-service users.
+///
+/// Must run within a transaction: `users.ws_id` and `workspace.owner_id` reference
+/// each other, so the user joins the super workspace until its own workspace exists.
+pub(crate) async fn create_account<C: ConnectionTrait>(
+    txn: &C,
+    fullname: String,
+    email: String,
+    password_hash: String,
+    email_verified: bool,
+) -> Result<users::Model, ApiError> {
+    let user = users::ActiveModel {
+        fullname: Set(fullname),
+        email: Set(email),
+        password_hash: Set(password_hash),
+        email_verified: Set(email_verified),
+        ws_id: Set(0),
+        ..Default::default()
+    }
+    .insert(txn)
+    .await?;
+
+    let workspace = workspace::ActiveModel {
+        name: Set(format!("ws-{}", xid::new())),
+        owner_id: Set(user.id),
+        ..Default::default()
+    }
+    .insert(txn)
+    .await?;
+
+    let user = users::ActiveModel {
+        ws_id: Set(workspace.id),
+        ..user.into()
+    }
+    .update(txn)
+    .await?;
+
+    if let Some(role) = Role::find()
+        .filter(role::Column::Name.eq(SIGNUP_ROLE))
+        .one(txn)
+        .await?
+    {
+        user_role::ActiveModel {
+            user_id: Set(user.id),
+            role_id: Set(role.id),
+        }
+        .insert(txn)
+        .await?;
+    }
+
+    Ok(user)
+}
+
+/// Stores the hash of a new single-use token and returns the token to mail to the user.
+pub(crate) async fn issue_user_token<C: ConnectionTrait>(
+    db: &C,
+    user_id: i64,
+    purpose: UserTokenPurpose,
+    ttl: u64,
+) -> Result<String, ApiError> {
+    let token = generate_token();
+    let expires_at = expires_in(ttl)?;
+
+    user_token::ActiveModel {
+        user_id: Set(user_id),
+        purpose: Set(purpose),
+        token_hash: Set(hash_token(&token)),
+        expires_at: Set(expires_at),
+        ..Default::default()
+    }
+    .insert(db)
+    .await?;
+
+    Ok(token)
+}
+
+/// Marks an unused, unexpired token as used and returns its user id.
+///
+/// Consuming is a single conditional `UPDATE`, so a token can only be used once
+/// even when presented concurrently.
+pub(crate) async fn consume_user_token<C: ConnectionTrait>(
+    db: &C,
+    token: &str,
+    purpose: UserTokenPurpose,
+) -> Result<i64, ApiError> {
+    let consumed = UserToken::update_many()
+        .col_expr(user_token::Column::UsedAt, Expr::current_timestamp().into())
+        .filter(user_token::Column::TokenHash.eq(hash_token(token)))
+        .filter(user_token::Column::Purpose.eq(purpose))
+        .filter(user_token::Column::UsedAt.is_null())
+        .filter(Expr::col(user_token::Column::ExpiresAt).gt(Expr::current_timestamp()))
+        .exec_with_returning(db)
+        .await?;
+
+    consumed
+        .first()
+        .map(|token| token.user_id)
+        .ok_or_else(|| ApiError::BizError("The token is invalid or has expired!".to_string()))
+}
+
+/// 32 random bytes, URL safe encoded.
+fn generate_token() -> String {
+    let mut bytes = [0u8; 32];
+    rand::rngs::OsRng.fill_bytes(&mut bytes);
+    URL_SAFE_NO_PAD.encode(bytes)
+}
+
+/// Hex encoded sha256 of the token, the only form that is stored.
+fn hash_token(token: &str) -> String {
+    format!("{:x}", Sha256::digest(token.as_bytes()))
+}
+
+fn expires_in(seconds: u64) -> Result<DateTimeWithTimeZone, ApiError> {
+    let expires_at = jsonwebtoken::get_current_timestamp().saturating_add(seconds);
+    DateTimeUtc::from_timestamp(expires_at as i64, 0)
+        .map(|expires_at| expires_at.fixed_offset())
+        .ok_or_else(|| anyhow::anyhow!("invalid token expiration: {}", expires_at).into())
+}
+
+/// Sends a mail, failures are logged but do not fail the request.
+async fn send_mail(state: &AppState, mail: Mail) {
+    let to = mail.to.clone();
+    if let Err(e) = state.mailer().send(mail).await {
+        tracing::error!("error sending mail to {}: {:?}", Email(&to), e);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_tokens_are_random_and_stored_hashed() {
+        let token = generate_token();
+        assert_ne!(token, generate_token());
+        assert_eq!(token.len(), 43);
+
+        let hash = hash_token(&token);
+        assert_eq!(hash.len(), 64);
+        assert_eq!(hash, hash_token(&token));
+        assert!(!hash.contains(&token));
+    }
+}
@@ -0,0 +1,22 @@
+use crate::application::AppState;
+use crate::handlers::api_key;
+use crate::middleware::require_permission;
+use axum::routing::{delete, get, post};
+use axum::Router;
+
+/// Define API key management api for the application.
+pub(crate) fn routes() -> Router<AppState> {
+    Router::new()
+        .route(
+            "/create_api_key",
+            post(api_key::create).route_layer(require_permission("api_key:manage")),
+        )
+        .route(
+            "/get_api_keys",
+            get(api_key::list).route_layer(require_permission("api_key:manage")),
+        )
+        .route(
+            "/revoke_api_key/{id}",
+            delete(api_key::revoke).route_layer(require_permission("api_key:manage")),
+        )
+}
@@ -0,0 +1,48 @@
+use crate::application::AppState;
+use crate::health::{CheckReport, HealthReport, HealthStatus};
+use axum::extract::State;
+use axum::routing::get;
+use axum::{Json, Router};
+use http::StatusCode;
+use std::collections::BTreeMap;
+
+/// Unauthenticated probes for orchestrators and load balancers.
+///
+/// The reports are served as is, without the `ApiResponse` envelope, as probes only look
+/// at the status code.
+pub(crate) fn routes() -> Router<AppState> {
+    Router::new()
+        .route("/live", get(live))
+        .route("/ready", get(ready))
+}
+
+/// Answers as long as the process handles requests, without touching any dependency.
+pub async fn live() -> Json<serde_json::Value> {
+    Json(serde_json::json!({ "status": HealthStatus::Up }))
+}
+
+/// Answers `200 OK` if every dependency is up, `503 Service Unavailable` with the failing
+/// checks otherwise, and while the server drains its connections on shutdown.
+pub async fn ready(State(state): State<AppState>) -> (StatusCode, Json<HealthReport>) {
+    if !state.readiness().is_ready() {
+        let server = CheckReport {
+            status: HealthStatus::Down,
+            latency_ms: 0.0,
+            details: None,
+            error: Some("not accepting traffic, starting or shutting down".to_string()),
+        };
+        let report = HealthReport {
+            status: HealthStatus::Down,
+            checks: BTreeMap::from([("server".to_string(), server)]),
+        };
+        return (StatusCode::SERVICE_UNAVAILABLE, Json(report));
+    }
+
+    let report = state.health_checks().run().await;
+    let status = match report.status {
+        HealthStatus::Up => StatusCode::OK,
+        HealthStatus::Down => StatusCode::SERVICE_UNAVAILABLE,
+    };
+
+    (status, Json(report))
+}
@@ -0,0 +1,18 @@
+use crate::application::AppState;
+use crate::handlers::logging;
+use crate::middleware::require_permission;
+use axum::routing::{get, put};
+use axum::Router;
+
+/// Define the runtime logging api for the application.
+pub(crate) fn routes() -> Router<AppState> {
+    Router::new()
+        .route(
+            "/get_log_filter",
+            get(logging::get).route_layer(require_permission("logging:manage")),
+        )
+        .route(
+            "/update_log_filter",
+            put(logging::update).route_layer(require_permission("logging:manage")),
+        )
+}
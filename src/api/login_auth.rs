@@ -1,16 +1,22 @@
 use crate::application::AppState;
-use crate::auth::{get_jwt, Principal};
-use crate::common::verify_password;
+use crate::auth::{get_jwt, Principal, Session};
+use crate::common::{hash_password, password_needs_rehash, verify_dummy_password, verify_password};
 use crate::entity::prelude::*;
-use crate::entity::users;
+use crate::entity::{permission, refresh_token, role, role_permission, users};
 use crate::error::ApiError;
 use crate::middleware::get_auth_layer;
+use crate::redact::{Email, JwtError, Token};
 use crate::request::BValidJson;
 use crate::response::{ApiResponse, ApiResult};
+use crate::session::{CookieSession, SessionCookies};
 use axum::extract::{ConnectInfo, State};
+use axum::response::{IntoResponse, Response};
 use axum::routing::{get, post};
-use axum::{debug_handler, Extension, Router};
+use axum::{debug_handler, Extension, Json, Router};
+use http::{header, HeaderMap, Method};
+use jsonwebtoken::jwk::JwkSet;
 use sea_orm::prelude::*;
+use sea_orm::{QueryOrder, QuerySelect, Set, TransactionTrait};
 use serde::{Deserialize, Serialize};
 use std::net::SocketAddr;
 use validator::Validate;
@@ -25,61 +31,433 @@ pub struct LoginParams {
     password: String,
 }
 
+#[derive(Debug, Deserialize, Validate)]
+pub struct RefreshParams {
+    /// Taken from the refresh token cookie in cookie session mode when omitted.
+    #[validate(length(min = 1, message = "refresh_token can not be empty."))]
+    refresh_token: Option<String>,
+}
+
 #[derive(Debug, Serialize)]
 pub struct LoginResponse {
     access_token: String,
+    refresh_token: String,
+}
+
+/// Result of the first login step: tokens, or a second factor still to verify.
+#[derive(Debug, Serialize)]
+#[serde(untagged)]
+pub enum LoginOutcome {
+    Tokens(LoginResponse),
+    MfaRequired {
+        mfa_required: bool,
+        mfa_token: String,
+    },
+    /// The tokens were set as cookies, scripts only get the CSRF token to echo.
+    Session {
+        csrf_token: String,
+    },
+}
+
+impl LoginOutcome {
+    pub(crate) fn message(&self) -> &'static str {
+        match self {
+            Self::Tokens(_) | Self::Session { .. } => "login success",
+            Self::MfaRequired { .. } => "second factor required",
+        }
+    }
 }
 
 pub(crate) fn routes() -> Router<AppState> {
     Router::new()
         .route("/get_user_info", get(get_user_info))
+        .route("/logout", post(logout))
         .route_layer(get_auth_layer())
         .route("/login", post(login))
+        .route("/refresh", post(refresh))
 }
 
 #[debug_handler]
-#[tracing::instrument(name = "login", skip_all, fields(account = %account, IP = %addr))]
+#[tracing::instrument(name = "login", skip_all, fields(account = %Email(&account), IP = %addr))]
 pub async fn login(
-    State(AppState { db }): State<AppState>,
+    State(AppState {
+        db,
+        login_guard,
+        cookie_session,
+        ..
+    }): State<AppState>,
     ConnectInfo(addr): ConnectInfo<SocketAddr>,
+    headers: HeaderMap,
     BValidJson(LoginParams { account, password }): BValidJson<LoginParams>,
-) -> ApiResult<LoginResponse> {
-    tracing::info!("start login, account: {}", account);
+) -> Result<Response, ApiError> {
+    tracing::info!("start login, account: {}", Email(&account));
+    let ip = login_guard.client_ip(&headers, addr);
+
+    if let Err(remaining) = login_guard.check(&account, ip) {
+        tracing::warn!(
+            "login rejected, account or IP is locked: {}",
+            Email(&account)
+        );
+        // round up, so clients never retry while still locked
+        let seconds = remaining.as_secs() + u64::from(remaining.subsec_nanos() > 0);
+        return Err(ApiError::AccountLockedError(seconds));
+    }
 
     let user = Users::find()
         .filter(users::Column::Email.eq(&account))
         .one(&db)
+        .await?;
+
+    // every failure below looks the same to the client, including its timing
+    let verified = match &user {
+        Some(user) => verify_password(&password, &user.password_hash)
+            .await
+            .unwrap_or_else(|e| {
+                tracing::error!(
+                    "error verifying password, account: {}: {:?}",
+                    Email(&account),
+                    e
+                );
+                false
+            }),
+        None => {
+            verify_dummy_password(&password).await;
+            false
+        }
+    };
+    let Some(user) = user.filter(|_| verified) else {
+        tracing::error!("invalid credentials, account: {}", Email(&account));
+        login_guard.record_failure(&account, ip);
+        return Err(ApiError::InvalidCredentialsError);
+    };
+    // self-service accounts sign in once their email is verified, until then they look
+    // like wrong passwords
+    if !user.email_verified {
+        tracing::warn!("login with an unverified email, user: {}", user.id);
+        return Err(ApiError::InvalidCredentialsError);
+    }
+
+    // upgrade hashes of an older algorithm or parameters while the password is at hand
+    if password_needs_rehash(&user.password_hash) {
+        upgrade_password_hash(&db, &user, &password).await;
+    }
+
+    let outcome = complete_login(&db, &user).await?;
+    tracing::info!("password verified, IP: {}, user: {}", addr, user.id);
+    // with a second factor pending, the failures only reset once `mfa::verify` passes
+    if let LoginOutcome::Tokens(_) = outcome {
+        login_guard.record_success(&account);
+    }
+
+    Ok(login_response(
+        cookie_session.as_deref(),
+        outcome.message(),
+        outcome,
+    ))
+}
+
+/// Finishes the login of an authenticated user in a new session: issues the token pair,
+/// or an "mfa pending" token if the user has a second factor to verify first.
+pub(crate) async fn complete_login(
+    db: &DatabaseConnection,
+    user: &users::Model,
+) -> Result<LoginOutcome, ApiError> {
+    let family_id = xid::new().to_string();
+
+    let mfa_enabled = UserMfa::find_by_id(user.id)
+        .one(db)
+        .await?
+        .is_some_and(|mfa| mfa.enabled);
+    if mfa_enabled {
+        let mfa_token = get_jwt()
+            .encode_mfa_pending(user.id, &family_id)
+            .map_err(|e| ApiError::InternalError(e.into()))?;
+        tracing::info!("second factor required, user: {}", user.id);
+
+        return Ok(LoginOutcome::MfaRequired {
+            mfa_required: true,
+            mfa_token,
+        });
+    }
+
+    let (tokens, _) = issue_token_pair(db, user, &family_id).await?;
+    tracing::info!(
+        "login success, user: {}, access_token: {}",
+        user.id,
+        Token(&tokens.access_token)
+    );
+
+    Ok(LoginOutcome::Tokens(tokens))
+}
+
+/// Responds with the outcome of a login.
+///
+/// In cookie session mode the tokens are set as cookies instead, and the body only
+/// carries the CSRF token.
+pub(crate) fn login_response(
+    cookie_session: Option<&CookieSession>,
+    message: &str,
+    outcome: LoginOutcome,
+) -> Response {
+    let (Some(session), LoginOutcome::Tokens(tokens)) = (cookie_session, &outcome) else {
+        return ApiResponse::success(message, Some(outcome)).into_response();
+    };
+
+    let SessionCookies {
+        cookies,
+        csrf_token,
+    } = session.login_cookies(&tokens.access_token, &tokens.refresh_token);
+    let mut response =
+        ApiResponse::success(message, Some(LoginOutcome::Session { csrf_token })).into_response();
+    for cookie in cookies {
+        response.headers_mut().append(header::SET_COOKIE, cookie);
+    }
+
+    response
+}
+
+/// Replaces the stored hash with one of the current algorithm, failures only cost the upgrade.
+async fn upgrade_password_hash(db: &DatabaseConnection, user: &users::Model, password: &str) {
+    let result = match hash_password(password).await {
+        Ok(password_hash) => Users::update_many()
+            .col_expr(users::Column::PasswordHash, Expr::value(password_hash))
+            .filter(users::Column::Id.eq(user.id))
+            .filter(users::Column::PasswordHash.eq(&user.password_hash))
+            .exec(db)
+            .await
+            .map(|_| ())
+            .map_err(anyhow::Error::from),
+        Err(e) => Err(e),
+    };
+
+    match result {
+        Ok(()) => tracing::info!("password hash upgraded, user: {}", user.id),
+        Err(e) => tracing::error!("error upgrading password hash, user: {}: {:?}", user.id, e),
+    }
+}
+
+/// Exchanges a refresh token for a new access/refresh token pair.
+///
+/// The presented refresh token is rotated: it is marked as revoked and replaced by
+/// the newly issued one within the same family. Presenting a token that was already
+/// rotated is treated as token theft, and the whole family is revoked.
+///
+/// In cookie session mode the refresh token may come from its cookie, which then
+/// requires the CSRF token and is rotated in place.
+#[debug_handler]
+#[tracing::instrument(name = "refresh", skip_all)]
+pub async fn refresh(
+    State(AppState {
+        db, cookie_session, ..
+    }): State<AppState>,
+    headers: HeaderMap,
+    BValidJson(RefreshParams { refresh_token }): BValidJson<RefreshParams>,
+) -> Result<Response, ApiError> {
+    let (refresh_token, cookie_session) = match (refresh_token, cookie_session.as_deref()) {
+        (Some(token), _) => (token, None),
+        (None, Some(session)) => {
+            session.check_csrf(&Method::POST, &headers)?;
+            let token = session.refresh_token(&headers).ok_or_else(|| {
+                ApiError::UnAuthenticatedError("Session cookie is not found!".to_string())
+            })?;
+            (token.to_string(), Some(session))
+        }
+        (None, None) => {
+            return Err(ApiError::ValidationError(
+                "refresh_token can not be empty.".to_string(),
+            ))
+        }
+    };
+
+    let claims = get_jwt().decode_refresh(&refresh_token).map_err(|e| {
+        tracing::error!("refresh token decode error: {}", JwtError(&e));
+        ApiError::UnAuthenticatedError("Invalid refresh token!".to_string())
+    })?;
+
+    let txn = db.begin().await?;
+
+    let stored = RefreshToken::find()
+        .filter(refresh_token::Column::Jti.eq(&claims.jti))
+        .lock_exclusive()
+        .one(&txn)
         .await?
         .ok_or_else(|| {
-            tracing::error!("user not found, account: {}", account);
-            ApiError::BizError("user or password is not correct!".to_string())
+            tracing::error!("refresh token not found, jti: {}", claims.jti);
+            ApiError::UnAuthenticatedError("Invalid refresh token!".to_string())
         })?;
 
-    if !verify_password(&password, user.password_hash.as_str())? {
-        tracing::error!("password is not correct, account: {}", account);
-        return Ok(ApiResponse::error("password is not correct".to_string()));
+    if stored.revoked {
+        let revoked = revoke_family(&txn, &stored.family_id).await?;
+        txn.commit().await?;
+        tracing::warn!(
+            "refresh token reuse detected, jti: {}, family: {}, revoked: {}",
+            stored.jti,
+            stored.family_id,
+            revoked
+        );
+        return Err(ApiError::UnAuthenticatedError(
+            "Refresh token has been revoked!".to_string(),
+        ));
     }
 
-    let principal = Principal {
-        id: user.id.to_string(),
-        name: user.fullname,
-        email: user.email,
-    };
-    let access_token = get_jwt().encode(principal)?;
+    let user = Users::find_by_id(stored.user_id)
+        .one(&txn)
+        .await?
+        .ok_or_else(|| ApiError::UnAuthenticatedError("Invalid refresh token!".to_string()))?;
 
-    tracing::info!(
-        "login success, IP: {}, access_token: {}",
-        addr,
-        access_token
-    );
+    let (tokens, new_jti) = issue_token_pair(&txn, &user, &stored.family_id).await?;
 
-    Ok(ApiResponse::success(
-        "login success",
-        Some(LoginResponse { access_token }),
+    let family_id = stored.family_id.clone();
+    let mut rotated: refresh_token::ActiveModel = stored.into();
+    rotated.revoked = Set(true);
+    rotated.replaced_by = Set(Some(new_jti));
+    rotated.update(&txn).await?;
+
+    txn.commit().await?;
+    tracing::info!("refresh token rotated, family: {}", family_id);
+
+    Ok(login_response(
+        cookie_session,
+        "refresh success",
+        LoginOutcome::Tokens(tokens),
     ))
 }
 
+/// Logs out the current session.
+///
+/// The presented access token is added to the revocation denylist, and the refresh
+/// token family it belongs to is revoked so the session can not be renewed. Session
+/// cookies are removed from the browser.
+#[debug_handler]
+#[tracing::instrument(name = "logout", skip_all, fields(user = %principal))]
+pub async fn logout(
+    State(AppState {
+        db,
+        revocation,
+        cookie_session,
+        ..
+    }): State<AppState>,
+    Extension(principal): Extension<Principal>,
+    Session(claims): Session,
+) -> Result<Response, ApiError> {
+    revocation.revoke(&claims.jti, claims.exp).await?;
+
+    if let Some(family_id) = claims.sid.as_deref() {
+        let revoked = revoke_family(&db, family_id).await?;
+        tracing::info!("refresh token family {} revoked: {}", family_id, revoked);
+    }
+
+    tracing::info!("logout success, jti: {}", claims.jti);
+    let mut response = ApiResponse::<()>::success("logout success", None).into_response();
+    if let Some(session) = cookie_session {
+        for cookie in session.logout_cookies() {
+            response.headers_mut().append(header::SET_COOKIE, cookie);
+        }
+    }
+
+    Ok(response)
+}
+
+/// Publishes the public keys used to verify our tokens as a JWK Set.
+///
+/// The document is served as is, without the `ApiResponse` envelope, so that
+/// standard JWT libraries of other services can consume it.
+pub async fn jwks() -> Json<JwkSet> {
+    Json(get_jwt().jwks().clone())
+}
+
 #[debug_handler]
 pub async fn get_user_info(Extension(principal): Extension<Principal>) -> ApiResult<Principal> {
     Ok(ApiResponse::success("", Some(principal)))
 }
+
+/// Issues an access token and a refresh token for the user and persists the
+/// refresh token as a member of the given family.
+///
+/// Returns the token pair together with the `jti` of the new refresh token.
+pub(crate) async fn issue_token_pair<C: ConnectionTrait>(
+    db: &C,
+    user: &users::Model,
+    family_id: &str,
+) -> Result<(LoginResponse, String), ApiError> {
+    let jwt = get_jwt();
+    let (roles, permissions) = load_authorities(db, user).await?;
+    let principal = Principal {
+        roles,
+        permissions,
+        ..Principal::from(user)
+    };
+    let access_token = jwt
+        .encode(&principal, family_id)
+        .map_err(|e| ApiError::InternalError(e.into()))?;
+    let refresh = jwt
+        .encode_refresh(user.id, family_id)
+        .map_err(|e| ApiError::InternalError(e.into()))?;
+
+    let expires_at = DateTimeUtc::from_timestamp(refresh.expires_at as i64, 0)
+        .ok_or_else(|| anyhow::anyhow!("invalid refresh token expiration"))?;
+
+    refresh_token::ActiveModel {
+        jti: Set(refresh.jti.clone()),
+        family_id: Set(family_id.to_string()),
+        user_id: Set(user.id),
+        expires_at: Set(expires_at.fixed_offset()),
+        revoked: Set(false),
+        ..Default::default()
+    }
+    .insert(db)
+    .await?;
+
+    let tokens = LoginResponse {
+        access_token,
+        refresh_token: refresh.token,
+    };
+
+    Ok((tokens, refresh.jti))
+}
+
+/// Loads the role names of the user and the permissions granted by those roles.
+pub(crate) async fn load_authorities<C: ConnectionTrait>(
+    db: &C,
+    user: &users::Model,
+) -> Result<(Vec<String>, Vec<String>), ApiError> {
+    let roles = user
+        .find_related(Role)
+        .order_by_asc(role::Column::Name)
+        .all(db)
+        .await?;
+    if roles.is_empty() {
+        return Ok((vec![], vec![]));
+    }
+
+    let permissions = Permission::find()
+        .inner_join(RolePermission)
+        .filter(role_permission::Column::RoleId.is_in(roles.iter().map(|role| role.id)))
+        .distinct()
+        .order_by_asc(permission::Column::Name)
+        .all(db)
+        .await?;
+
+    Ok((
+        roles.into_iter().map(|role| role.name).collect(),
+        permissions
+            .into_iter()
+            .map(|permission| permission.name)
+            .collect(),
+    ))
+}
+
+/// Revokes every refresh token of the family, returning the number of tokens revoked.
+pub(crate) async fn revoke_family<C: ConnectionTrait>(
+    db: &C,
+    family_id: &str,
+) -> Result<u64, ApiError> {
+    let result = RefreshToken::update_many()
+        .col_expr(refresh_token::Column::Revoked, Expr::value(true))
+        .filter(refresh_token::Column::FamilyId.eq(family_id))
+        .filter(refresh_token::Column::Revoked.eq(false))
+        .exec(db)
+        .await?;
+
+    Ok(result.rows_affected)
+}
@@ -0,0 +1,23 @@
+use crate::application::AppState;
+use crate::metrics::CONTENT_TYPE;
+use axum::extract::State;
+use axum::routing::get;
+use axum::Router;
+use http::header;
+
+/// Unauthenticated scrape endpoint for Prometheus.
+///
+/// Served on the admin port when `server.admin_port` is set, on the main port otherwise.
+pub(crate) fn routes() -> Router<AppState> {
+    Router::new().route("/metrics", get(metrics))
+}
+
+/// Renders every metric in the Prometheus text format, without the `ApiResponse` envelope.
+pub async fn metrics(
+    State(state): State<AppState>,
+) -> ([(header::HeaderName, &'static str); 1], String) {
+    (
+        [(header::CONTENT_TYPE, CONTENT_TYPE)],
+        state.metrics().render(),
+    )
+}
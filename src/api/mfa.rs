@@ -0,0 +1,296 @@
+use crate::api::login_auth::{issue_token_pair, login_response, LoginOutcome};
+use crate::application::AppState;
+use crate::auth::{get_jwt, Principal, Session};
+use crate::config::AppConfig;
+use crate::entity::prelude::*;
+use crate::entity::{mfa_recovery_code, user_mfa};
+use crate::error::ApiError;
+use crate::mfa;
+use crate::middleware::get_auth_layer;
+use crate::redact::JwtError;
+use crate::request::BValidJson;
+use crate::response::{ApiResponse, ApiResult};
+use axum::extract::{ConnectInfo, State};
+use axum::response::Response;
+use axum::routing::post;
+use axum::{debug_handler, Extension, Router};
+use http::HeaderMap;
+use sea_orm::prelude::*;
+use sea_orm::{Set, TransactionTrait};
+use serde::{Deserialize, Serialize};
+use std::net::SocketAddr;
+use validator::Validate;
+
+/// The only message returned for a failed second step of the login, whether the mfa
+/// token or the code is wrong.
+const INVALID_MFA: &str = "mfa token or code is not correct!";
+
+#[derive(Debug, Deserialize, Validate)]
+pub struct ConfirmParams {
+    #[validate(length(min = 1, message = "code can not be empty."))]
+    code: String,
+}
+
+#[derive(Debug, Deserialize, Validate)]
+pub struct VerifyParams {
+    #[validate(length(min = 1, message = "mfa_token can not be empty."))]
+    mfa_token: String,
+    /// A TOTP code or an unused recovery code.
+    #[validate(length(min = 1, message = "code can not be empty."))]
+    code: String,
+}
+
+#[derive(Debug, Serialize)]
+pub struct EnrollResponse {
+    secret: String,
+    provisioning_uri: String,
+}
+
+#[derive(Debug, Serialize)]
+pub struct RecoveryCodesResponse {
+    recovery_codes: Vec<String>,
+}
+
+/// TOTP enrollment for the signed-in user, and the second step of the login.
+pub(crate) fn routes() -> Router<AppState> {
+    Router::new()
+        .route("/enroll", post(enroll))
+        .route("/confirm", post(confirm))
+        .route_layer(get_auth_layer())
+        .route("/verify", post(verify))
+}
+
+/// Starts a TOTP enrollment and returns the secret to scan into an authenticator app.
+///
+/// The secret is only used once it is confirmed with a code; enrolling again before
+/// that replaces it.
+#[debug_handler]
+#[tracing::instrument(name = "mfa_enroll", skip_all, fields(user = %principal))]
+pub async fn enroll(
+    State(AppState { db, .. }): State<AppState>,
+    Extension(principal): Extension<Principal>,
+    _session: Session,
+) -> ApiResult<EnrollResponse> {
+    let existing = UserMfa::find_by_id(principal.id).one(&db).await?;
+    if existing.as_ref().is_some_and(|mfa| mfa.enabled) {
+        return Err(ApiError::BizError(
+            "Two-factor authentication is already enabled!".to_string(),
+        ));
+    }
+
+    let secret = mfa::generate_secret();
+    let totp = mfa::totp(&secret, AppConfig::get().mfa().issuer(), &principal.email)?;
+
+    let txn = db.begin().await?;
+    if existing.is_some() {
+        UserMfa::delete_by_id(principal.id).exec(&txn).await?;
+    }
+    user_mfa::ActiveModel {
+        user_id: Set(principal.id),
+        secret: Set(secret.clone()),
+        enabled: Set(false),
+        last_used_step: Set(0),
+        ..Default::default()
+    }
+    .insert(&txn)
+    .await?;
+    txn.commit().await?;
+
+    tracing::info!("mfa enrollment started, user: {}", principal.id);
+    Ok(ApiResponse::success(
+        "Scan the secret and confirm it with a code.",
+        Some(EnrollResponse {
+            secret,
+            provisioning_uri: totp.get_url(),
+        }),
+    ))
+}
+
+/// Enables TOTP once the user proves the secret works, and returns fresh recovery codes.
+///
+/// The recovery codes are shown only here, only their hashes are stored.
+#[debug_handler]
+#[tracing::instrument(name = "mfa_confirm", skip_all, fields(user = %principal))]
+pub async fn confirm(
+    State(AppState { db, .. }): State<AppState>,
+    Extension(principal): Extension<Principal>,
+    _session: Session,
+    BValidJson(ConfirmParams { code }): BValidJson<ConfirmParams>,
+) -> ApiResult<RecoveryCodesResponse> {
+    let pending = UserMfa::find_by_id(principal.id)
+        .one(&db)
+        .await?
+        .filter(|mfa| !mfa.enabled)
+        .ok_or_else(|| {
+            ApiError::BizError("No two-factor enrollment to confirm, please enroll first!".into())
+        })?;
+
+    let totp = mfa::totp(
+        &pending.secret,
+        AppConfig::get().mfa().issuer(),
+        &principal.email,
+    )?;
+    let Some(step) = mfa::verify_code(&totp, &code, 0, jsonwebtoken::get_current_timestamp())
+    else {
+        return Err(ApiError::BizError("The code is not correct!".to_string()));
+    };
+
+    let recovery_codes = mfa::generate_recovery_codes(AppConfig::get().mfa().recovery_codes());
+
+    let txn = db.begin().await?;
+    user_mfa::ActiveModel {
+        enabled: Set(true),
+        last_used_step: Set(step as i64),
+        ..pending.into()
+    }
+    .update(&txn)
+    .await?;
+
+    MfaRecoveryCode::delete_many()
+        .filter(mfa_recovery_code::Column::UserId.eq(principal.id))
+        .exec(&txn)
+        .await?;
+    MfaRecoveryCode::insert_many(recovery_codes.iter().map(|code| {
+        mfa_recovery_code::ActiveModel {
+            user_id: Set(principal.id),
+            code_hash: Set(mfa::hash_recovery_code(code)),
+            ..Default::default()
+        }
+    }))
+    .exec(&txn)
+    .await?;
+    txn.commit().await?;
+
+    tracing::info!("mfa enabled, user: {}", principal.id);
+    Ok(ApiResponse::success(
+        "Two-factor authentication enabled, keep the recovery codes safe.",
+        Some(RecoveryCodesResponse { recovery_codes }),
+    ))
+}
+
+/// Exchanges an "mfa pending" token and a TOTP or recovery code for a token pair.
+///
+/// Wrong codes count as failed logins of the account, like wrong passwords.
+#[debug_handler]
+#[tracing::instrument(name = "mfa_verify", skip_all, fields(IP = %addr))]
+pub async fn verify(
+    State(AppState {
+        db,
+        revocation,
+        login_guard,
+        cookie_session,
+        ..
+    }): State<AppState>,
+    ConnectInfo(addr): ConnectInfo<SocketAddr>,
+    headers: HeaderMap,
+    BValidJson(VerifyParams { mfa_token, code }): BValidJson<VerifyParams>,
+) -> Result<Response, ApiError> {
+    let ip = login_guard.client_ip(&headers, addr);
+    // a bad token and a bad code look the same to the client, like failed logins
+    let invalid = || ApiError::UnAuthenticatedError(INVALID_MFA.to_string());
+
+    let claims = get_jwt().decode_mfa_pending(&mfa_token).map_err(|e| {
+        tracing::error!("mfa token decode error: {}", JwtError(&e));
+        invalid()
+    })?;
+    if revocation.is_revoked(&claims.jti).await? {
+        tracing::warn!("mfa token reused, jti: {}", claims.jti);
+        return Err(invalid());
+    }
+
+    let user_id: i64 = claims.sub.parse().map_err(|_| invalid())?;
+    let family_id = claims.sid.as_deref().ok_or_else(invalid)?;
+    let user = Users::find_by_id(user_id)
+        .one(&db)
+        .await?
+        .ok_or_else(invalid)?;
+
+    if let Err(remaining) = login_guard.check(&user.email, ip) {
+        tracing::warn!("mfa rejected, account or IP is locked, user: {}", user.id);
+        let seconds = remaining.as_secs() + u64::from(remaining.subsec_nanos() > 0);
+        return Err(ApiError::AccountLockedError(seconds));
+    }
+
+    let Some(user_mfa) = UserMfa::find_by_id(user.id)
+        .one(&db)
+        .await?
+        .filter(|mfa| mfa.enabled)
+    else {
+        return Err(invalid());
+    };
+
+    if !verify_totp_code(&db, &user_mfa, &user.email, &code).await?
+        && !consume_recovery_code(&db, user.id, &code).await?
+    {
+        tracing::error!("invalid mfa code, user: {}", user.id);
+        login_guard.record_failure(&user.email, ip);
+        return Err(invalid());
+    }
+    login_guard.record_success(&user.email);
+
+    // the pending token is single-use, like the codes it was exchanged with
+    revocation.revoke(&claims.jti, claims.exp).await?;
+
+    let (tokens, _) = issue_token_pair(&db, &user, family_id).await?;
+    tracing::info!("mfa verified, user: {}", user.id);
+
+    Ok(login_response(
+        cookie_session.as_deref(),
+        "login success",
+        LoginOutcome::Tokens(tokens),
+    ))
+}
+
+/// Checks a TOTP code and records its step, so it can not be used again.
+///
+/// The step is advanced with a conditional `UPDATE`, a code presented concurrently
+/// is only accepted once.
+async fn verify_totp_code(
+    db: &DatabaseConnection,
+    user_mfa: &user_mfa::Model,
+    account: &str,
+    code: &str,
+) -> Result<bool, ApiError> {
+    let totp = mfa::totp(&user_mfa.secret, AppConfig::get().mfa().issuer(), account)?;
+    let last_used_step = user_mfa.last_used_step.max(0) as u64;
+    let Some(step) = mfa::verify_code(
+        &totp,
+        code,
+        last_used_step,
+        jsonwebtoken::get_current_timestamp(),
+    ) else {
+        return Ok(false);
+    };
+
+    let result = UserMfa::update_many()
+        .col_expr(user_mfa::Column::LastUsedStep, Expr::value(step as i64))
+        .filter(user_mfa::Column::UserId.eq(user_mfa.user_id))
+        .filter(user_mfa::Column::LastUsedStep.lt(step as i64))
+        .exec(db)
+        .await?;
+
+    Ok(result.rows_affected == 1)
+}
+
+/// Marks an unused recovery code of the user as used.
+async fn consume_recovery_code(
+    db: &DatabaseConnection,
+    user_id: i64,
+    code: &str,
+) -> Result<bool, ApiError> {
+    let result = MfaRecoveryCode::update_many()
+        .col_expr(
+            mfa_recovery_code::Column::UsedAt,
+            Expr::current_timestamp().into(),
+        )
+        .filter(mfa_recovery_code::Column::UserId.eq(user_id))
+        .filter(mfa_recovery_code::Column::CodeHash.eq(mfa::hash_recovery_code(code)))
+        .filter(mfa_recovery_code::Column::UsedAt.is_null())
+        .exec(db)
+        .await?;
+
+    if result.rows_affected == 1 {
+        tracing::warn!("recovery code used, user: {}", user_id);
+    }
+    Ok(result.rows_affected == 1)
+}
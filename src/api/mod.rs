@@ -1,24 +1,70 @@
 use crate::application::AppState;
+use crate::config::AppConfig;
 use crate::error::ApiError;
 use crate::handlers;
+use crate::limits;
 use crate::middleware::get_auth_layer;
+use axum::middleware::from_fn_with_state;
 use axum::{routing::get, Router};
+use std::time::Duration;
 
+mod account;
+mod api_key;
+mod health;
+mod logging;
 mod login_auth;
+mod metrics;
+mod mfa;
+mod oidc;
 pub(crate) mod user;
 mod workspace;
 
+pub(crate) use login_auth::load_authorities;
+
 /// Creates and configures the application API routes.
+///
+/// Route groups may override the server's request timeout with `limits::timeout` and its
+/// body limit with `limits::body_limit`.
 pub async fn build_routes() -> Router<AppState> {
-    Router::new()
+    let routes = Router::new()
         .route("/", get(handlers::index))
         .nest("/api", user::routes())
         .nest("/api", workspace::routes())
+        .nest("/api", api_key::routes())
+        .nest("/api", logging::routes())
         .route_layer(get_auth_layer())
-        .nest("/auth", login_auth::routes())
+        .nest("/auth", with_auth_limits(login_auth::routes()))
+        .nest("/auth", with_auth_limits(account::routes()))
+        .nest("/auth/mfa", with_auth_limits(mfa::routes()))
+        .nest("/auth/oidc", oidc::routes())
+        .nest("/health", health::routes())
+        .route("/.well-known/jwks.json", get(login_auth::jwks))
         .fallback(handlers::fallback)
         .method_not_allowed_fallback(async || -> ApiError {
             tracing::warn!("Method not allowed!");
             ApiError::MethodNotAllowedError
-        })
+        });
+
+    // without an admin port, the admin routes are served with the others
+    if AppConfig::get().server().admin_port().is_none() {
+        routes.merge(build_admin_routes())
+    } else {
+        routes
+    }
+}
+
+/// Creates the unauthenticated operator routes, served on the admin port if configured.
+pub fn build_admin_routes() -> Router<AppState> {
+    Router::new().merge(metrics::routes())
+}
+
+/// Applies the request timeout and body limit of the `/auth` routes, see `ServerConfig`.
+fn with_auth_limits(routes: Router<AppState>) -> Router<AppState> {
+    let server = AppConfig::get().server();
+    routes
+        .layer(limits::body_limit(server.auth_body_limit()))
+        .layer(from_fn_with_state(
+            Duration::from_secs(server.auth_request_timeout()),
+            limits::timeout,
+        ))
 }
@@ -0,0 +1,274 @@
+use crate::api::account::create_account;
+use crate::api::login_auth::{complete_login, login_response};
+use crate::application::AppState;
+use crate::auth::get_jwt;
+use crate::config::AppConfig;
+use crate::entity::prelude::*;
+use crate::entity::{refresh_token, user_identity, users};
+use crate::error::ApiError;
+use crate::oidc::{IdTokenClaims, OidcProvider};
+use crate::request::{BPath, BQuery};
+use crate::session::cookie;
+use axum::extract::State;
+use axum::response::{AppendHeaders, IntoResponse, Redirect, Response};
+use axum::routing::get;
+use axum::{debug_handler, Router};
+use http::{header, HeaderMap};
+use sea_orm::prelude::*;
+use sea_orm::{Set, TransactionTrait};
+use serde::Deserialize;
+use std::collections::HashMap;
+use std::sync::Arc;
+use std::time::Duration;
+
+/// Cookie carrying the pending login from the redirect to the provider to its callback.
+const STATE_COOKIE: &str = "oidc_state";
+
+/// Query of the provider's redirect back to us.
+#[derive(Debug, Deserialize)]
+pub struct CallbackParams {
+    code: Option<String>,
+    state: Option<String>,
+    error: Option<String>,
+    error_description: Option<String>,
+}
+
+/// Public login routes of the configured OpenID Connect providers.
+pub(crate) fn routes() -> Router<AppState> {
+    Router::new()
+        .route("/{provider}/login", get(login))
+        .route("/{provider}/callback", get(callback))
+}
+
+/// Redirects the browser to the provider's login page.
+///
+/// The `state`, `nonce` and PKCE code verifier of the login travel to the callback in a
+/// signed, short-lived cookie, so any instance of the server can complete the login.
+#[debug_handler]
+#[tracing::instrument(name = "oidc_login", skip_all, fields(provider = %provider))]
+pub async fn login(
+    State(AppState { oidc, .. }): State<AppState>,
+    BPath(provider): BPath<String>,
+) -> Result<Response, ApiError> {
+    let provider = oidc.get(&provider).ok_or(ApiError::NotFoundError)?;
+    let request = provider.authorization_request().await?;
+
+    let ttl = AppConfig::get().oidc().state_ttl();
+    let extra = HashMap::from([
+        ("nonce".to_string(), request.nonce.into()),
+        ("code_verifier".to_string(), request.code_verifier.into()),
+    ]);
+    let state_token = get_jwt()
+        .encode_oidc_state(
+            provider.name(),
+            &request.state,
+            extra,
+            Duration::from_secs(ttl),
+        )
+        .map_err(|e| ApiError::InternalError(e.into()))?;
+
+    tracing::info!("redirecting to the identity provider");
+    Ok((
+        [(
+            header::SET_COOKIE,
+            state_cookie(provider, &state_token, ttl),
+        )],
+        Redirect::to(&request.url),
+    )
+        .into_response())
+}
+
+/// Completes the login when the provider redirects back: verifies the ID token, links
+/// the external account to a user and logs that user in.
+///
+/// A provider account that is not linked yet is linked to the user with the same
+/// email, or to a new user in a personal workspace, but only if the provider verified
+/// the email.
+#[debug_handler]
+#[tracing::instrument(name = "oidc_callback", skip_all, fields(provider = %provider))]
+pub async fn callback(
+    State(AppState {
+        db,
+        oidc,
+        cookie_session,
+        ..
+    }): State<AppState>,
+    BPath(provider): BPath<String>,
+    headers: HeaderMap,
+    BQuery(params): BQuery<CallbackParams>,
+) -> Result<Response, ApiError> {
+    let provider = oidc.get(&provider).ok_or(ApiError::NotFoundError)?;
+    let invalid_state = || {
+        ApiError::UnAuthenticatedError("Login state is invalid or expired, please retry!".into())
+    };
+
+    if let Some(error) = params.error {
+        tracing::warn!(
+            "identity provider refused the login: {} {:?}",
+            error,
+            params.error_description
+        );
+        return Err(ApiError::UnAuthenticatedError(format!(
+            "Login at {} failed: {}",
+            provider.name(),
+            error
+        )));
+    }
+
+    let claims = cookie(&headers, STATE_COOKIE)
+        .and_then(|token| get_jwt().decode_oidc_state(token).ok())
+        .filter(|claims| claims.sub == provider.name())
+        .filter(|claims| claims.sid.is_some() && claims.sid == params.state)
+        .ok_or_else(invalid_state)?;
+    let extra = |name: &str| claims.extra.get(name).and_then(|value| value.as_str());
+    let (Some(code), Some(nonce), Some(code_verifier)) =
+        (params.code, extra("nonce"), extra("code_verifier"))
+    else {
+        return Err(invalid_state());
+    };
+
+    let identity = provider
+        .exchange_code(&code, code_verifier, nonce)
+        .await
+        .map_err(|e| {
+            tracing::error!("error verifying the identity provider login: {:?}", e);
+            ApiError::UnAuthenticatedError(
+                "Login at the identity provider could not be verified!".to_string(),
+            )
+        })?;
+
+    let user = link_user(&db, provider.name(), &identity).await?;
+    let outcome = complete_login(&db, &user).await?;
+
+    Ok((
+        AppendHeaders([(header::SET_COOKIE, state_cookie(provider, "", 0))]),
+        login_response(cookie_session.as_deref(), outcome.message(), outcome),
+    )
+        .into_response())
+}
+
+/// Returns the user linked to the provider account, linking one first if needed.
+async fn link_user(
+    db: &DatabaseConnection,
+    provider: &str,
+    identity: &IdTokenClaims,
+) -> Result<users::Model, ApiError> {
+    let linked = UserIdentity::find()
+        .filter(user_identity::Column::Provider.eq(provider))
+        .filter(user_identity::Column::Subject.eq(&identity.sub))
+        .one(db)
+        .await?;
+    if let Some(linked) = linked {
+        return Users::find_by_id(linked.user_id)
+            .one(db)
+            .await?
+            .ok_or_else(|| {
+                anyhow::anyhow!("linked user {} does not exist", linked.user_id).into()
+            });
+    }
+
+    // an unverified email could be anyone's, it must never take over an account
+    let Some(email) = identity
+        .email
+        .as_deref()
+        .filter(|_| identity.email_verified)
+    else {
+        tracing::warn!(
+            "{} account {} has no verified email",
+            provider,
+            identity.sub
+        );
+        return Err(ApiError::BizError(
+            "The identity provider did not share a verified email address!".to_string(),
+        ));
+    };
+
+    let txn = db.begin().await?;
+
+    let user = match Users::find()
+        .filter(users::Column::Email.eq(email))
+        .one(&txn)
+        .await?
+    {
+        Some(user) if user.email_verified => user,
+        // whoever signed up with the email never proved to own it, the password they
+        // chose must not keep working once the owner signs in
+        Some(mut user) => {
+            Users::update_many()
+                .col_expr(users::Column::PasswordHash, Expr::value(""))
+                .col_expr(users::Column::EmailVerified, Expr::value(true))
+                .filter(users::Column::Id.eq(user.id))
+                .exec(&txn)
+                .await?;
+            RefreshToken::update_many()
+                .col_expr(refresh_token::Column::Revoked, Expr::value(true))
+                .filter(refresh_token::Column::UserId.eq(user.id))
+                .filter(refresh_token::Column::Revoked.eq(false))
+                .exec(&txn)
+                .await?;
+            tracing::warn!(
+                "unverified user {} claimed by {} account {}, password cleared",
+                user.id,
+                provider,
+                identity.sub
+            );
+
+            user.password_hash = String::new();
+            user.email_verified = true;
+            user
+        }
+        None => {
+            let fullname = identity
+                .name
+                .as_deref()
+                .filter(|name| !name.trim().is_empty())
+                .unwrap_or_else(|| email.split('@').next().unwrap_or(email));
+            // no password, the user can set one with the password reset
+            create_account(
+                &txn,
+                fullname.chars().take(64).collect(),
+                email.to_string(),
+                String::new(),
+                true,
+            )
+            .await?
+        }
+    };
+
+    user_identity::ActiveModel {
+        user_id: Set(user.id),
+        provider: Set(provider.to_string()),
+        subject: Set(identity.sub.clone()),
+        email: Set(Some(email.to_string())),
+        ..Default::default()
+    }
+    .insert(&txn)
+    .await?;
+
+    txn.commit().await?;
+    tracing::info!(
+        "{} account {} linked to user {}",
+        provider,
+        identity.sub,
+        user.id
+    );
+
+    Ok(user)
+}
+
+/// The state cookie, only sent back to the callback; an empty value removes it.
+fn state_cookie(provider: &Arc<OidcProvider>, value: &str, max_age: u64) -> String {
+    let secure = AppConfig::get()
+        .oidc()
+        .redirect_base_url()
+        .starts_with("https://");
+
+    format!(
+        "{}={}; Path=/auth/oidc/{}; Max-Age={}; HttpOnly; SameSite=Lax{}",
+        STATE_COOKIE,
+        value,
+        provider.name(),
+        max_age,
+        if secure { "; Secure" } else { "" }
+    )
+}
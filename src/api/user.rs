@@ -1,5 +1,6 @@
 use crate::application::AppState;
 use crate::handlers::user;
+use crate::middleware::require_permission;
 use axum::routing::{delete, patch};
 use axum::{
     routing::{get, post},
@@ -9,12 +10,24 @@ use axum::{
 /// Define user-related api for the application.
 pub(crate) fn routes() -> Router<AppState> {
     Router::new()
-        .route("/create_user", post(user::create))
-        .route("/get_user", get(user::query_all_by_id_or_name))
-        .route("/query_by_keyword", get(user::query_by_keyword))
+        .route(
+            "/create_user",
+            post(user::create).route_layer(require_permission("user:create")),
+        )
+        .route(
+            "/get_user",
+            get(user::query_all_by_id_or_name).route_layer(require_permission("user:read")),
+        )
+        .route(
+            "/query_by_keyword",
+            get(user::query_by_keyword).route_layer(require_permission("user:read")),
+        )
         .route(
             "/update_user_ws_by_id/{id}/{ws_id}",
-            patch(user::update_ws_by_id),
+            patch(user::update_ws_by_id).route_layer(require_permission("user:update")),
+        )
+        .route(
+            "/delete_user_by_id/{id}",
+            delete(user::delete_by_id).route_layer(require_permission("user:delete")),
         )
-        .route("/delete_user_by_id/{id}", delete(user::delete_by_id))
 }
@@ -1,9 +1,13 @@
 use crate::application::AppState;
 use crate::handlers::workspace;
+use crate::middleware::require_permission;
 use axum::routing::post;
 use axum::Router;
 
 /// Define workspace-related api for the application.
 pub(crate) fn routes() -> Router<AppState> {
-    Router::new().route("/create_workspace", post(workspace::create_workspace))
+    Router::new().route(
+        "/create_workspace",
+        post(workspace::create_workspace).route_layer(require_permission("workspace:create")),
+    )
 }
@@ -0,0 +1,143 @@
+use crate::api::load_authorities;
+use crate::auth::Principal;
+use crate::entity::prelude::*;
+use crate::entity::{api_key, users};
+use crate::error::ApiError;
+use base64::engine::general_purpose::URL_SAFE_NO_PAD;
+use base64::Engine;
+use rand::RngCore;
+use sea_orm::prelude::*;
+use sea_orm::Condition;
+use sha2::{Digest, Sha256};
+
+/// Marks a bearer credential as an API key; JWTs always start with `eyJ`.
+pub const KEY_PREFIX: &str = "ak_";
+
+/// Length of the random part of the key kept in clear text, to tell keys apart in lists.
+const ID_LEN: usize = 8;
+
+/// A newly generated API key, shown to its creator exactly once.
+#[derive(Debug, Clone)]
+pub struct GeneratedKey {
+    /// `ak_<id>_<secret>`, the credential presented as `Authorization: Bearer <key>`.
+    pub key: String,
+    /// `ak_<id>`, stored in clear text.
+    pub prefix: String,
+    /// Hex encoded sha256 of `key`, the only form of the secret that is stored.
+    pub hash: String,
+}
+
+/// Returns `true` if a bearer credential is an API key rather than a JWT.
+pub fn is_api_key(token: &str) -> bool {
+    token.starts_with(KEY_PREFIX)
+}
+
+/// Generates a key with 256 random bits of secret.
+pub fn generate() -> GeneratedKey {
+    let mut id = [0u8; ID_LEN / 2];
+    let mut secret = [0u8; 32];
+    rand::rngs::OsRng.fill_bytes(&mut id);
+    rand::rngs::OsRng.fill_bytes(&mut secret);
+
+    let id: String = id.iter().map(|byte| format!("{:02x}", byte)).collect();
+    let prefix = format!("{}{}", KEY_PREFIX, id);
+    let secret = URL_SAFE_NO_PAD.encode(secret);
+    let key = format!("{}_{}", prefix, secret);
+
+    GeneratedKey {
+        hash: hash_key(&key),
+        key,
+        prefix,
+    }
+}
+
+/// Hex encoded sha256 of the key.
+pub fn hash_key(key: &str) -> String {
+    format!("{:x}", Sha256::digest(key.as_bytes()))
+}
+
+/// Resolves an API key to the principal it acts as, and records its use.
+///
+/// The key acts as its creator within the key's workspace, with the permissions of its
+/// scopes that the creator still holds. Keys of creators who left the workspace are
+/// rejected.
+pub async fn authenticate<C: ConnectionTrait>(db: &C, key: &str) -> Result<Principal, ApiError> {
+    let invalid = || ApiError::UnAuthenticatedError("Invalid API key!".to_string());
+
+    let used = ApiKey::update_many()
+        .col_expr(
+            api_key::Column::LastUsedAt,
+            Expr::current_timestamp().into(),
+        )
+        .filter(api_key::Column::KeyHash.eq(hash_key(key)))
+        .filter(api_key::Column::RevokedAt.is_null())
+        .filter(
+            Condition::any()
+                .add(api_key::Column::ExpiresAt.is_null())
+                .add(Expr::col(api_key::Column::ExpiresAt).gt(Expr::current_timestamp())),
+        )
+        .exec_with_returning(db)
+        .await?;
+    let Some(api_key) = used.into_iter().next() else {
+        tracing::warn!("unknown, revoked or expired API key: {}", prefix_of(key));
+        return Err(invalid());
+    };
+
+    let user = Users::find_by_id(api_key.user_id)
+        .one(db)
+        .await?
+        .ok_or_else(invalid)?;
+    if user.ws_id != api_key.ws_id {
+        tracing::warn!(
+            "API key {} of user {} who left workspace {}",
+            prefix_of(key),
+            user.id,
+            api_key.ws_id
+        );
+        return Err(invalid());
+    }
+
+    let (_, permissions) = load_authorities(db, &user).await?;
+    Ok(principal(&api_key, &user, &permissions))
+}
+
+/// The principal an API key acts as, given the current permissions of its creator.
+pub fn principal(api_key: &api_key::Model, user: &users::Model, granted: &[String]) -> Principal {
+    Principal {
+        id: user.id,
+        name: user.fullname.clone(),
+        email: user.email.clone(),
+        ws_id: Some(api_key.ws_id),
+        roles: vec![],
+        permissions: api_key
+            .scopes
+            .split_whitespace()
+            .filter(|scope| granted.iter().any(|permission| permission == scope))
+            .map(str::to_string)
+            .collect(),
+    }
+}
+
+/// The clear text part of a key, safe to log.
+fn prefix_of(key: &str) -> &str {
+    key.get(..KEY_PREFIX.len() + ID_LEN).unwrap_or(KEY_PREFIX)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_generated_keys_are_prefixed_and_stored_hashed() {
+        let generated = generate();
+
+        assert!(is_api_key(&generated.key));
+        assert!(generated.key.starts_with(&format!("{}_", generated.prefix)));
+        assert_eq!(generated.prefix.len(), KEY_PREFIX.len() + ID_LEN);
+        assert_eq!(prefix_of(&generated.key), generated.prefix);
+        assert_eq!(generated.hash, hash_key(&generated.key));
+        assert!(!generated.hash.contains(&generated.key));
+        assert_ne!(generated.key, generate().key);
+        assert!(!is_api_key("eyJhbGciOiJIUzI1NiJ9.e30.sig"));
+    }
+}
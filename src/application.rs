@@ -1,17 +1,26 @@
 use crate::config::AppConfig;
-use crate::{database, logger};
-use axum::extract::{DefaultBodyLimit, Request};
+use crate::health::{HealthCheck, HealthChecks};
+use crate::logger::LogFilter;
+use crate::login_guard::LoginGuard;
+use crate::mailer::Mailer;
+use crate::metrics::Metrics;
+use crate::oidc::OidcProviders;
+use crate::request_id::RequestId;
+use crate::revocation::RevocationStore;
+use crate::session::CookieSession;
+use crate::shutdown::Readiness;
+use crate::{
+    api, auth, cors, database, limits, logger, login_guard, mailer, metrics, password, redact,
+    request_id, revocation, shutdown, telemetry,
+};
+use axum::extract::{MatchedPath, Request};
 use axum::http::Response;
-use axum::Router;
-use bytesize::ByteSize;
+use axum::{middleware, Extension, Router};
 use sea_orm::DatabaseConnection;
 use std::fmt::{Display, Formatter};
-use std::net::SocketAddr;
+use std::sync::Arc;
 use std::time::Duration;
-use tower_http::cors;
-use tower_http::cors::{AllowMethods, CorsLayer};
 use tower_http::normalize_path::NormalizePathLayer;
-use tower_http::timeout::TimeoutLayer;
 use tower_http::trace::{OnResponse, TraceLayer};
 use tracing::Span;
 
@@ -21,6 +30,20 @@ use tracing::Span;
 #[derive(Clone)]
 pub struct AppState {
     pub db: DatabaseConnection,
+    pub revocation: Arc<dyn RevocationStore>,
+    pub mailer: Arc<dyn Mailer>,
+    pub login_guard: Arc<LoginGuard>,
+    pub oidc: Arc<OidcProviders>,
+    /// Browser sessions in cookies, `None` when clients send bearer tokens.
+    pub cookie_session: Option<Arc<CookieSession>>,
+    /// Changes the log levels at runtime.
+    pub log_filter: LogFilter,
+    /// Cleared while the server drains its connections on shutdown.
+    pub readiness: Readiness,
+    /// Dependencies probed by `/health/ready`.
+    pub health_checks: HealthChecks,
+    /// Request, query and pool metrics scraped from `/metrics`.
+    pub metrics: Arc<Metrics>,
 }
 
 /// Server instance responsible for starting and configuring the HTTP server.
@@ -36,23 +59,106 @@ struct Latency(Duration);
 
 impl AppState {
     /// Creates a new application state with the given database connection.
-    fn new(db: DatabaseConnection) -> Self {
-        Self { db }
+    ///
+    /// The connection is expected to be instrumented with `metrics` already, see
+    /// `database::instrument`, like every copy of it held by the other resources.
+    #[allow(clippy::too_many_arguments)]
+    pub fn new(
+        db: DatabaseConnection,
+        revocation: Arc<dyn RevocationStore>,
+        mailer: Arc<dyn Mailer>,
+        login_guard: Arc<LoginGuard>,
+        oidc: Arc<OidcProviders>,
+        cookie_session: Option<Arc<CookieSession>>,
+        metrics: Arc<Metrics>,
+        log_filter: LogFilter,
+    ) -> Self {
+        let health_checks = HealthChecks::new(&db);
+        Self {
+            db,
+            revocation,
+            mailer,
+            login_guard,
+            oidc,
+            cookie_session,
+            log_filter,
+            readiness: Readiness::default(),
+            health_checks,
+            metrics,
+        }
     }
 
     /// Returns a reference to the database connection.
     pub fn db(&self) -> &DatabaseConnection {
         &self.db
     }
+
+    /// Returns the store of revoked access tokens.
+    pub fn revocation(&self) -> &Arc<dyn RevocationStore> {
+        &self.revocation
+    }
+
+    /// Returns the mailer used for verification and password reset mails.
+    pub fn mailer(&self) -> &Arc<dyn Mailer> {
+        &self.mailer
+    }
+
+    /// Returns the failed login tracker.
+    pub fn login_guard(&self) -> &Arc<LoginGuard> {
+        &self.login_guard
+    }
+
+    /// Returns the OpenID Connect identity providers.
+    pub fn oidc(&self) -> &Arc<OidcProviders> {
+        &self.oidc
+    }
+
+    /// Returns whether the server accepts traffic.
+    pub fn readiness(&self) -> &Readiness {
+        &self.readiness
+    }
+
+    /// Returns the health checks of the server's dependencies.
+    pub fn health_checks(&self) -> &HealthChecks {
+        &self.health_checks
+    }
+
+    /// Adds a check to the readiness probe, e.g. for a new external dependency.
+    pub fn register_health_check(&mut self, check: Arc<dyn HealthCheck>) {
+        self.health_checks.register(check);
+    }
+
+    /// Returns the metrics of the server.
+    pub fn metrics(&self) -> &Arc<Metrics> {
+        &self.metrics
+    }
+
+    /// Returns the handle changing the log levels.
+    pub fn log_filter(&self) -> &LogFilter {
+        &self.log_filter
+    }
+
+    /// Returns the cookie session settings, `None` in bearer mode.
+    pub fn cookie_session(&self) -> Option<&Arc<CookieSession>> {
+        self.cookie_session.as_ref()
+    }
 }
 
 /// Starts the application server with the provided router.
 ///
 /// # Process
-/// 1. Initializes logging system
-/// 2. Establishes database connection
-/// 3. Creates application state
-/// 4. Starts HTTP server with configured routes
+/// 1. Initializes logging system, with runtime adjustable levels, and the OpenTelemetry
+///    span export
+/// 2. Validates the revocation, JWT, mail, password hashing, login guard and CORS
+///    configuration
+/// 3. Establishes database connection
+/// 4. Creates the token revocation store and its background cleanup task
+/// 5. Creates the mailer, the login guard, the OIDC providers, the cookie session and
+///    application state
+/// 6. Starts HTTP server with configured routes, and the admin server serving `/metrics`
+///    when an admin port is configured
+/// 7. On SIGTERM or SIGINT, drains in-flight requests, closes the database connections
+///    and flushes the spans and log lines not written yet
 ///
 /// # Arguments
 /// * `router` - The application router containing all route definitions
@@ -60,19 +166,66 @@ impl AppState {
 /// # Returns
 /// * `anyhow::Result<()>` - Result indicating server startup success or failure
 pub async fn run(router: Router<AppState>) -> anyhow::Result<()> {
-    // Initialize logging and tracing
-    logger::init();
+    // Initialize logging and tracing, with the span export if enabled
+    let logger = logger::init()?;
     tracing::info!("Starting the application server......");
 
-    // Initialize database connection
-    let db_connection = database::init().await?;
+    // Refuse to start without a usable revocation cleanup, with an insecure JWT
+    // configuration, mails that never reach the users, unusable hashing parameters,
+    // an unenforceable login guard or a CORS policy letting any site make
+    // authenticated requests
+    let config = AppConfig::get();
+    revocation::check_config(config)?;
+    auth::check_config(config)?;
+    mailer::check_config(config)?;
+    password::check_config(config)?;
+    login_guard::check_config(config)?;
+    cors::check_config(config)?;
+
+    // Initialize database connection, instrumented before any store gets a copy of it,
+    // so that all queries are recorded as metrics and spans
+    let mut db_connection = database::init().await?;
+    let metrics = Arc::new(Metrics::new(&db_connection));
+    database::instrument(&mut db_connection, metrics.clone());
+
+    // Create token revocation store and purge expired entries in the background
+    let revocation_store = revocation::build_store(config.revocation(), &db_connection);
+    revocation::spawn_cleanup(
+        revocation_store.clone(),
+        Duration::from_secs(config.revocation().cleanup_interval()),
+    );
 
     // Create application state with database connection
-    let app_state = AppState::new(db_connection);
+    let mailer = mailer::build_mailer(config.mail());
+    let login_guard = Arc::new(LoginGuard::new(config.login_guard()));
+    login_guard::spawn_cleanup(
+        login_guard.clone(),
+        Duration::from_secs(config.login_guard().window()),
+    );
+    let oidc = Arc::new(OidcProviders::from_config(config.oidc())?);
+    let cookie_session = CookieSession::from_config(
+        config.session(),
+        config.jwt().expiration(),
+        config.jwt().refresh_expiration(),
+    )?
+    .map(Arc::new);
+    let app_state = AppState::new(
+        db_connection,
+        revocation_store,
+        mailer,
+        login_guard,
+        oidc,
+        cookie_session,
+        metrics,
+        logger.filter().clone(),
+    );
 
     // Create server instance and start
-    let server = Server::new(AppConfig::get());
-    server.start(app_state, router).await
+    let server = Server::new(config);
+    let result = server.start(app_state, router).await;
+
+    logger.shutdown().await;
+    result
 }
 
 impl Server {
@@ -83,6 +236,9 @@ impl Server {
 
     /// Starts the HTTP server and begins listening for requests.
     ///
+    /// Returns after a shutdown signal, once the connections are drained or the
+    /// shutdown timeout expired, and the database pool is closed.
+    ///
     /// # Arguments
     /// * `state` - Application state to be shared with handlers
     /// * `router` - Router containing the route definitions
@@ -90,73 +246,135 @@ impl Server {
         let server_config = self.config.server();
         tracing::info!("Server config: {:?}", server_config);
 
-        let routes = self.create_routes(state, router);
+        let db = state.db().clone();
+        let readiness = state.readiness().clone();
+
+        if let Some(admin_port) = server_config.admin_port() {
+            let addr = format!("{}:{}", server_config.get_host(), admin_port);
+            let listener = tokio::net::TcpListener::bind(&addr).await?;
+            tracing::info!("The admin server is listening on: {}", addr);
+            let admin = build_admin_app(state.clone());
+            tokio::spawn(async move {
+                let server =
+                    axum::serve(listener, admin).with_graceful_shutdown(shutdown::signal());
+                if let Err(e) = server.await {
+                    tracing::error!("The admin server failed: {:?}", e);
+                }
+            });
+        }
+
+        let routes = build_app(state, router);
 
         let addr = format!("{}:{}", server_config.get_host(), server_config.get_port());
 
         let listener = tokio::net::TcpListener::bind(&addr).await.unwrap();
         tracing::info!("The Application is listening on: {}", addr);
-        axum::serve(
+        readiness.set_ready(true);
+        shutdown::serve(
             listener,
-            routes.into_make_service_with_connect_info::<SocketAddr>(),
+            routes,
+            shutdown::signal(),
+            readiness,
+            Duration::from_secs(server_config.shutdown_timeout()),
         )
         .await?;
 
+        db.close().await?;
+        tracing::info!("database connections closed, bye!");
+
         Ok(())
     }
+}
 
-    /// Configures routes with tracing middleware and application state.
-    fn create_routes(&self, state: AppState, router: Router<AppState>) -> Router {
-        // request timeout, default 60s
-        let timeout = TimeoutLayer::new(Duration::from_secs(60));
+/// Configures routes with tracing middleware and application state.
+///
+/// Used by the server, and by integration tests to drive the full middleware stack.
+pub fn build_app(state: AppState, router: Router<AppState>) -> Router {
+    // request timeout and body size limit, route groups may override them
+    let server_config = AppConfig::get().server();
+    let timeout = middleware::from_fn_with_state(
+        Duration::from_secs(server_config.request_timeout()),
+        limits::timeout,
+    );
+    let body_limit = limits::body_limit(server_config.body_limit());
 
-        let body_limit = DefaultBodyLimit::max(
-            // body size limit 10MB
-            ByteSize::mib(10).as_u64() as usize,
-        );
+    // request counts and latencies, labeled by route template
+    let metrics = middleware::from_fn_with_state(state.metrics().clone(), metrics::track);
 
-        let cors = CorsLayer::new()
-            .allow_origin(cors::Any)
-            .allow_methods(AllowMethods::list(vec![
-                http::Method::GET,
-                http::Method::POST,
-                http::Method::PUT,
-                http::Method::DELETE,
-                http::Method::PATCH,
-                http::Method::OPTIONS,
-            ]))
-            .allow_headers(cors::Any)
-            .allow_credentials(false)
-            .max_age(Duration::from_secs(3600));
-
-        let tracing = TraceLayer::new_for_http()
-            .make_span_with(|request: &Request| {
-                let method = request.method();
-                let path = request.uri().path();
-                let id = xid::new(); // Generate unique request ID
-
-                tracing::info_span!("Api Request: ", id = %id, method = %method, path = %path)
-            })
-            .on_request(())
-            .on_failure(())
-            .on_response(LatencyOnResponse);
-
-        //  remove trailing slashes from request paths.
-        let normalize_path = NormalizePathLayer::trim_trailing_slash();
-
-        Router::new()
-            .merge(router)
-            .layer(timeout)
-            .layer(body_limit)
-            .layer(tracing)
-            .layer(cors)
-            .layer(normalize_path)
-            .with_state(state)
-    }
+    let csrf_header = state.cookie_session().map(|session| session.csrf_header());
+    let cors = cors::build_layer(server_config.cors(), csrf_header)
+        .expect("The CORS policy is checked before the server starts");
+
+    let tracing = TraceLayer::new_for_http()
+        .make_span_with(|request: &Request| {
+            let method = request.method();
+            let path = request.uri().path();
+            let route = request
+                .extensions()
+                .get::<MatchedPath>()
+                .map_or(path, |route| route.as_str());
+            // set by the request id layer, which runs first
+            let id = request
+                .extensions()
+                .get::<RequestId>()
+                .map(RequestId::to_string)
+                .unwrap_or_default();
+
+            let span = tracing::info_span!(
+                "Api Request: ",
+                id = %id,
+                method = %method,
+                path = %path,
+                otel.name = %format!("{} {}", method, route),
+                otel.kind = "server",
+                http.response.status_code = tracing::field::Empty,
+            );
+            // continue the trace of the caller's `traceparent` header
+            telemetry::continue_trace(&span, request.headers());
+            span
+        })
+        // the credentials of the `logging.redacted_headers` are masked
+        .on_request(|request: &Request, _: &Span| {
+            tracing::debug!(
+                headers = ?redact::Headers(request.headers()),
+                "started processing request."
+            )
+        })
+        .on_failure(())
+        .on_response(LatencyOnResponse);
+
+    // honor or generate the `X-Request-Id` of the request, and echo it on the response
+    let request_id = middleware::from_fn(request_id::propagate);
+
+    //  remove trailing slashes from request paths.
+    let normalize_path = NormalizePathLayer::trim_trailing_slash();
+
+    Router::new()
+        .merge(router)
+        .layer(timeout)
+        .layer(metrics)
+        .layer(body_limit)
+        .layer(tracing)
+        .layer(request_id)
+        .layer(cors)
+        .layer(normalize_path)
+        // expose the state to middlewares that run outside of handlers, e.g. `JWTAuth`
+        .layer(Extension(state.clone()))
+        .with_state(state)
+}
+
+/// Serves the admin routes, e.g. `/metrics`, on their own port, unreachable from the
+/// public network.
+pub fn build_admin_app(state: AppState) -> Router {
+    api::build_admin_routes().with_state(state)
 }
 
 impl<B> OnResponse<B> for LatencyOnResponse {
-    fn on_response(self, response: &Response<B>, latency: Duration, _span: &Span) {
+    fn on_response(self, response: &Response<B>, latency: Duration, span: &Span) {
+        span.record(
+            "http.response.status_code",
+            i64::from(response.status().as_u16()),
+        );
         tracing::info!(
             latency = %Latency(latency),
             status = %response.status().as_u16(),
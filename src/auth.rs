@@ -1,5 +1,16 @@
+use crate::config::{self, AppConfig};
+use crate::entity::users;
+use crate::error::ApiError;
+use crate::jwk::{self, KeyPair};
+use crate::redact::Email;
+use anyhow::{bail, Context};
+use axum::extract::FromRequestParts;
+use http::request::Parts;
+use jsonwebtoken::errors::{Error as JwtError, ErrorKind};
+use jsonwebtoken::jwk::JwkSet;
 use jsonwebtoken::{
-    decode, encode, get_current_timestamp, Algorithm, DecodingKey, EncodingKey, Header, Validation,
+    decode, decode_header, encode, get_current_timestamp, Algorithm, DecodingKey, EncodingKey,
+    Header, Validation,
 };
 use serde::{Deserialize, Serialize};
 use std::collections::HashMap;
@@ -8,18 +19,52 @@ use std::sync::LazyLock;
 use std::time::Duration;
 
 static DEFAULT_KEY: &str = "MIIEpAIBAAKCAQEAu6L5Jk7J2Yc6X5r2Z2b4L8a9V1C7H3pN6tK8jW0xYv3fGqS";
-static JWT_INSTANCE: LazyLock<Jwt> = LazyLock::new(Jwt::default);
+static JWT_INSTANCE: LazyLock<Jwt> = LazyLock::new(|| {
+    JwtConfig::from_settings(AppConfig::get().jwt())
+        .and_then(Jwt::new)
+        .expect("Failed to initialize JWT")
+});
 
-#[derive(Debug, Clone, Serialize)]
+/// The authenticated caller, carried in access tokens and exposed to handlers as an extension.
+#[derive(Debug, Clone, PartialEq, Serialize)]
 pub struct Principal {
-    pub id: String,
+    pub id: i64,
     pub name: String,
     pub email: String,
+    /// Workspace of the caller, `None` for legacy tokens issued before it was embedded.
+    pub ws_id: Option<i64>,
+    pub roles: Vec<String>,
+    /// Permissions granted through `roles`, resolved at login.
+    pub permissions: Vec<String>,
+}
+
+/// Distinguishes short-lived access tokens from long-lived refresh tokens.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum TokenType {
+    #[default]
+    Access,
+    Refresh,
+    /// Issued after the password check of an account with a second factor, only
+    /// exchangeable at `/auth/mfa/verify`.
+    #[serde(rename = "mfa_pending")]
+    MfaPending,
+    /// Carries an OpenID Connect login from the redirect to the provider to its callback.
+    #[serde(rename = "oidc_state")]
+    OidcState,
+}
+
+/// A freshly issued refresh token together with the data needed to persist it.
+#[derive(Debug, Clone)]
+pub struct IssuedRefreshToken {
+    pub token: String,
+    pub jti: String,
+    pub expires_at: u64,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct Claims {
-    /// 主题 (Subject) - 通常是用户ID
+    /// 主题 (Subject) - 用户ID
     pub sub: String,
     /// 签发者 (Issuer)
     pub iss: String,
@@ -34,18 +79,48 @@ pub struct Claims {
     /// JWT ID - 唯一标识
     pub jti: String,
     /// 自定义声明：用户角色
+    #[serde(default)]
     pub roles: Vec<String>,
+    /// 自定义声明：用户权限
+    #[serde(default)]
+    pub permissions: Vec<String>,
+    /// 自定义声明：用户名
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub name: Option<String>,
+    /// 自定义声明：用户邮箱
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub email: Option<String>,
+    /// 自定义声明：用户所属工作空间ID
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub ws_id: Option<i64>,
+    /// 令牌类型 (Token Type) - access 或 refresh
+    #[serde(default)]
+    pub typ: TokenType,
+    /// 会话ID (Session ID) - 所属 refresh token 家族
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub sid: Option<String>,
     /// 自定义声明：额外数据
     #[serde(flatten)]
     pub extra: HashMap<String, serde_json::Value>,
 }
 
-#[derive(Debug)]
 pub struct JwtConfig {
     pub secret: String,
     pub issuer: String,
     pub audience: String,
     pub expiration: Duration,
+    pub refresh_expiration: Duration,
+    pub mfa_expiration: Duration,
+    /// Accept access tokens that still pack `id:name:email` into `sub`.
+    ///
+    /// Only needed while tokens issued before the structured claims are still in circulation.
+    pub accept_legacy_claims: bool,
+    /// Signing algorithm, `HS256` signs with `secret` and ignores `keys`.
+    pub algorithm: Algorithm,
+    /// Key id of the signing key, the first key is used when `None`.
+    pub active_kid: Option<String>,
+    /// Asymmetric keys, all of them are accepted for verification.
+    pub keys: Vec<KeyPair>,
 }
 
 impl Default for JwtConfig {
@@ -55,74 +130,381 @@ impl Default for JwtConfig {
             issuer: "https://www.axum-template.com".to_string(),
             audience: "https://www.axum-template.com".to_string(),
             expiration: Duration::from_secs(3600),
+            refresh_expiration: Duration::from_secs(30 * 24 * 3600),
+            mfa_expiration: Duration::from_secs(300),
+            accept_legacy_claims: true,
+            algorithm: Algorithm::HS256,
+            active_kid: None,
+            keys: vec![],
         }
     }
 }
 
+impl JwtConfig {
+    /// Builds the configuration from the `jwt` section of the application config,
+    /// loading the configured key files.
+    pub fn from_settings(settings: &config::jwt::JwtConfig) -> anyhow::Result<Self> {
+        let algorithm = jwk::parse_algorithm(settings.algorithm())?;
+        let keys = settings
+            .keys()
+            .iter()
+            .map(|key| KeyPair::load(key, algorithm))
+            .collect::<anyhow::Result<Vec<_>>>()?;
+
+        Ok(Self {
+            secret: settings
+                .resolve_secret()?
+                .unwrap_or_else(|| DEFAULT_KEY.to_string()),
+            issuer: settings.issuer().to_string(),
+            audience: settings.audience().to_string(),
+            expiration: Duration::from_secs(settings.expiration()),
+            refresh_expiration: Duration::from_secs(settings.refresh_expiration()),
+            mfa_expiration: Duration::from_secs(settings.mfa_expiration()),
+            accept_legacy_claims: settings.accept_legacy_claims(),
+            algorithm,
+            active_kid: settings.active_kid().map(str::to_string),
+            keys,
+        })
+    }
+
+    /// Returns `true` when tokens would be signed with the built-in `DEFAULT_KEY`.
+    pub fn uses_default_secret(&self) -> bool {
+        self.algorithm == Algorithm::HS256 && self.secret == DEFAULT_KEY
+    }
+
+    /// Rejects signing secrets that must never be used in production.
+    fn check_secret(&self, is_prod: bool) -> anyhow::Result<()> {
+        if self.algorithm != Algorithm::HS256 {
+            return Ok(());
+        }
+        if self.secret.is_empty() {
+            bail!("The JWT secret is empty");
+        }
+        if self.uses_default_secret() {
+            if is_prod {
+                bail!(
+                    "Refusing to start in prod with the built-in JWT secret, \
+                     configure jwt.secret_env or jwt.secret_file"
+                );
+            }
+            tracing::warn!("JWT tokens are signed with the built-in development secret");
+        }
+
+        Ok(())
+    }
+}
+
+/// Validates the JWT configuration before the server starts.
+///
+/// Loads the signing and verification keys, so that unreadable or mismatched keys fail
+/// the startup instead of the first request. Also fails when `RUN_ENV=prod` and tokens
+/// would be signed with the built-in `DEFAULT_KEY`, which is public in this repository.
+pub fn check_config(config: &AppConfig) -> anyhow::Result<()> {
+    let jwt_config = JwtConfig::from_settings(config.jwt())?;
+    jwt_config.check_secret(config.is_prod())?;
+    Jwt::new(jwt_config).map(drop)
+}
+
+/// A key accepted when verifying tokens, `kid` is `None` for the shared secret.
+struct VerificationKey {
+    kid: Option<String>,
+    decoding: DecodingKey,
+    validation: Validation,
+}
+
 pub struct Jwt {
     encode_secret: EncodingKey,
-    decode_secret: DecodingKey,
     header: Header,
-    validation: Validation,
+    /// Verification keys, the active key first.
+    keys: Vec<VerificationKey>,
+    jwks: JwkSet,
     expires_in: Duration,
+    refresh_expires_in: Duration,
+    mfa_expires_in: Duration,
     audience: String,
     issuer: String,
+    accept_legacy_claims: bool,
 }
 
 impl Jwt {
-    pub fn new(config: JwtConfig) -> Self {
-        let algorithm = Algorithm::HS256;
-        let mut validation = Validation::new(algorithm);
-        validation.set_issuer(&[&config.issuer]);
-        validation.set_audience(&[&config.audience]);
-        validation.set_required_spec_claims(&["jti", "iat", "exp", "nbf", "iss", "aud", "sub"]);
+    pub fn new(config: JwtConfig) -> anyhow::Result<Self> {
+        let validation = |algorithm| {
+            let mut validation = Validation::new(algorithm);
+            validation.set_issuer(&[&config.issuer]);
+            validation.set_audience(&[&config.audience]);
+            validation.set_required_spec_claims(&["jti", "iat", "exp", "nbf", "iss", "aud", "sub"]);
+            validation
+        };
 
-        Self {
-            encode_secret: EncodingKey::from_secret(config.secret.as_bytes()),
-            decode_secret: DecodingKey::from_secret(config.secret.as_bytes()),
-            header: Header::new(algorithm),
-            validation,
+        let (encode_secret, header, keys, jwks) = if config.algorithm == Algorithm::HS256 {
+            let key = VerificationKey {
+                kid: None,
+                decoding: DecodingKey::from_secret(config.secret.as_bytes()),
+                validation: validation(Algorithm::HS256),
+            };
+            let encode_secret = EncodingKey::from_secret(config.secret.as_bytes());
+            // shared secrets are never published
+            let jwks = JwkSet { keys: vec![] };
+            (
+                encode_secret,
+                Header::new(Algorithm::HS256),
+                vec![key],
+                jwks,
+            )
+        } else {
+            let mut pairs = config.keys;
+            let active_kid = config
+                .active_kid
+                .or_else(|| pairs.first().map(|pair| pair.kid.clone()))
+                .context("No JWT keys are configured")?;
+            for (index, pair) in pairs.iter().enumerate() {
+                if pairs[..index].iter().any(|other| other.kid == pair.kid) {
+                    bail!("Duplicate JWT key id: {}", pair.kid);
+                }
+            }
+            // the active key goes first, it verifies tokens that carry no `kid`
+            pairs.sort_by_key(|pair| pair.kid != active_kid);
+
+            let active = pairs
+                .first_mut()
+                .filter(|pair| pair.kid == active_kid)
+                .with_context(|| format!("Active JWT key {} is not configured", active_kid))?;
+            let encode_secret = active
+                .encoding
+                .take()
+                .with_context(|| format!("Active JWT key {} has no private key", active_kid))?;
+            let mut header = Header::new(active.algorithm);
+            header.kid = Some(active_kid);
+
+            let jwks = JwkSet {
+                keys: pairs.iter().map(|pair| pair.jwk.clone()).collect(),
+            };
+            let keys = pairs
+                .into_iter()
+                .map(|pair| VerificationKey {
+                    kid: Some(pair.kid),
+                    decoding: pair.decoding,
+                    validation: validation(pair.algorithm),
+                })
+                .collect();
+            (encode_secret, header, keys, jwks)
+        };
+
+        Ok(Self {
+            encode_secret,
+            header,
+            keys,
+            jwks,
             expires_in: config.expiration,
+            refresh_expires_in: config.refresh_expiration,
+            mfa_expires_in: config.mfa_expiration,
             audience: config.audience,
             issuer: config.issuer,
+            accept_legacy_claims: config.accept_legacy_claims,
+        })
+    }
+
+    /// Returns the public verification keys as a JWK Set, empty for `HS256`.
+    pub fn jwks(&self) -> &JwkSet {
+        &self.jwks
+    }
+
+    /// Issues an access token for the principal, bound to the given refresh token family.
+    pub fn encode(&self, principal: &Principal, session_id: &str) -> Result<String, JwtError> {
+        let mut claims = self.claims(
+            principal.id.to_string(),
+            TokenType::Access,
+            self.expires_in,
+            session_id,
+        );
+        claims.roles = principal.roles.clone();
+        claims.permissions = principal.permissions.clone();
+        claims.name = Some(principal.name.clone());
+        claims.email = Some(principal.email.clone());
+        claims.ws_id = principal.ws_id;
+
+        encode(&self.header, &claims, &self.encode_secret)
+    }
+
+    /// Issues a refresh token for the user as a member of the given token family.
+    pub fn encode_refresh(
+        &self,
+        user_id: i64,
+        family_id: &str,
+    ) -> Result<IssuedRefreshToken, JwtError> {
+        let claims = self.claims(
+            user_id.to_string(),
+            TokenType::Refresh,
+            self.refresh_expires_in,
+            family_id,
+        );
+
+        Ok(IssuedRefreshToken {
+            token: encode(&self.header, &claims, &self.encode_secret)?,
+            jti: claims.jti,
+            expires_at: claims.exp,
+        })
+    }
+
+    /// Issues an "mfa pending" token for a user who passed the password check, carrying
+    /// the refresh token family the session will be created in.
+    pub fn encode_mfa_pending(&self, user_id: i64, family_id: &str) -> Result<String, JwtError> {
+        let claims = self.claims(
+            user_id.to_string(),
+            TokenType::MfaPending,
+            self.mfa_expires_in,
+            family_id,
+        );
+
+        encode(&self.header, &claims, &self.encode_secret)
+    }
+
+    /// Issues a token carrying a pending OpenID Connect login: `sub` is the provider,
+    /// `sid` the `state` parameter sent to it, `extra` the values needed by the callback.
+    pub fn encode_oidc_state(
+        &self,
+        provider: &str,
+        state: &str,
+        extra: HashMap<String, serde_json::Value>,
+        expires_in: Duration,
+    ) -> Result<String, JwtError> {
+        let mut claims = self.claims(
+            provider.to_string(),
+            TokenType::OidcState,
+            expires_in,
+            state,
+        );
+        claims.extra = extra;
+
+        encode(&self.header, &claims, &self.encode_secret)
+    }
+
+    /// Validates an access token and returns the principal it carries.
+    pub fn decode(&self, token: &str) -> Result<Principal, JwtError> {
+        let claims = self.decode_access(token)?;
+        self.principal(&claims)
+    }
+
+    /// Validates an access token and returns its claims.
+    pub fn decode_access(&self, token: &str) -> Result<Claims, JwtError> {
+        self.decode_claims(token, TokenType::Access)
+    }
+
+    /// Validates a refresh token and returns its claims.
+    pub fn decode_refresh(&self, token: &str) -> Result<Claims, JwtError> {
+        self.decode_claims(token, TokenType::Refresh)
+    }
+
+    /// Validates an "mfa pending" token and returns its claims.
+    pub fn decode_mfa_pending(&self, token: &str) -> Result<Claims, JwtError> {
+        self.decode_claims(token, TokenType::MfaPending)
+    }
+
+    /// Validates a pending OpenID Connect login token and returns its claims.
+    pub fn decode_oidc_state(&self, token: &str) -> Result<Claims, JwtError> {
+        self.decode_claims(token, TokenType::OidcState)
+    }
+
+    /// Extracts the principal carried by access token claims.
+    ///
+    /// Legacy tokens packing `id:name:email` into `sub` are accepted only while
+    /// `accept_legacy_claims` is enabled; they carry no workspace and no roles.
+    pub fn principal(&self, claims: &Claims) -> Result<Principal, JwtError> {
+        if claims.name.is_none() && claims.sub.contains(':') {
+            if !self.accept_legacy_claims {
+                return Err(ErrorKind::InvalidSubject.into());
+            }
+            return Self::legacy_principal(&claims.sub);
         }
+
+        Ok(Principal {
+            id: parse_user_id(&claims.sub)?,
+            name: claims
+                .name
+                .clone()
+                .ok_or_else(|| ErrorKind::MissingRequiredClaim("name".to_string()))?,
+            email: claims
+                .email
+                .clone()
+                .ok_or_else(|| ErrorKind::MissingRequiredClaim("email".to_string()))?,
+            ws_id: Some(
+                claims
+                    .ws_id
+                    .ok_or_else(|| ErrorKind::MissingRequiredClaim("ws_id".to_string()))?,
+            ),
+            roles: claims.roles.clone(),
+            permissions: claims.permissions.clone(),
+        })
+    }
+
+    fn legacy_principal(sub: &str) -> Result<Principal, JwtError> {
+        let mut parts = sub.splitn(3, ':');
+        let (Some(id), Some(name), Some(email)) = (parts.next(), parts.next(), parts.next()) else {
+            return Err(ErrorKind::InvalidSubject.into());
+        };
+
+        Ok(Principal {
+            id: parse_user_id(id)?,
+            name: name.to_string(),
+            email: email.to_string(),
+            ws_id: None,
+            roles: vec![],
+            permissions: vec![],
+        })
     }
 
-    pub fn encode(&self, principal: Principal) -> anyhow::Result<String> {
+    fn claims(&self, sub: String, typ: TokenType, expires_in: Duration, sid: &str) -> Claims {
         let current_timestamp = get_current_timestamp();
-        let claims = Claims {
-            sub: format!("{}:{}:{}", principal.id, principal.name, principal.email), // will be extracted in decode for '/get_user_info'
+        Claims {
+            sub,
             iss: self.issuer.clone(),
             aud: self.audience.clone(),
-            exp: current_timestamp.saturating_add(self.expires_in.as_secs()),
+            exp: current_timestamp.saturating_add(expires_in.as_secs()),
             nbf: current_timestamp,
             iat: current_timestamp,
             jti: xid::new().to_string(),
             roles: vec![],
+            permissions: vec![],
+            name: None,
+            email: None,
+            ws_id: None,
+            typ,
+            sid: Some(sid.to_string()),
             extra: Default::default(),
-        };
-
-        Ok(encode(&self.header, &claims, &self.encode_secret)?)
+        }
     }
 
-    pub fn decode(&self, token: &str) -> anyhow::Result<Principal> {
-        let claims = decode::<Claims>(token, &self.decode_secret, &self.validation)?.claims;
+    fn decode_claims(&self, token: &str, expected: TokenType) -> Result<Claims, JwtError> {
+        let key = self.verification_key(token)?;
+        let claims = decode::<Claims>(token, &key.decoding, &key.validation)?.claims;
+        if claims.typ != expected {
+            return Err(ErrorKind::InvalidToken.into());
+        }
 
-        let mut parts = claims.sub.splitn(3, ':');
+        Ok(claims)
+    }
 
-        let principal = Principal {
-            id: parts.next().unwrap().to_string(),
-            name: parts.next().unwrap().to_string(),
-            email: parts.next().unwrap_or("default role").to_string(),
+    /// Selects the verification key by the `kid` of the token header.
+    fn verification_key(&self, token: &str) -> Result<&VerificationKey, JwtError> {
+        let key = match decode_header(token)?.kid {
+            Some(kid) => self
+                .keys
+                .iter()
+                .find(|key| key.kid.as_deref() == Some(kid.as_str())),
+            None => self.keys.first(),
         };
 
-        Ok(principal)
+        key.ok_or_else(|| ErrorKind::InvalidToken.into())
     }
 }
 
+fn parse_user_id(value: &str) -> Result<i64, JwtError> {
+    value.parse().map_err(|_| ErrorKind::InvalidSubject.into())
+}
+
 impl Default for Jwt {
     fn default() -> Self {
-        Self::new(JwtConfig::default())
+        Self::new(JwtConfig::default()).expect("Default JWT configuration is valid")
     }
 }
 
@@ -130,8 +512,217 @@ pub fn get_jwt() -> &'static Jwt {
     &JWT_INSTANCE
 }
 
+impl From<&users::Model> for Principal {
+    fn from(user: &users::Model) -> Self {
+        Self {
+            id: user.id,
+            name: user.fullname.clone(),
+            email: user.email.clone(),
+            ws_id: Some(user.ws_id),
+            roles: vec![],
+            permissions: vec![],
+        }
+    }
+}
+
+impl Principal {
+    /// Returns `true` if one of the caller's roles grants `permission`.
+    pub fn has_permission(&self, permission: &str) -> bool {
+        self.permissions.iter().any(|granted| granted == permission)
+    }
+}
+
 impl Display for Principal {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        write!(f, "{}:{}:{}", self.id, self.name, self.email)
+        write!(f, "{}:{}:{}", self.id, self.name, Email(&self.email))
+    }
+}
+
+/// Claims of the access token the request was authenticated with.
+///
+/// Only a signed-in user has a session, requests authenticated with an API key are
+/// refused with `403 Forbidden`; used by endpoints that manage the account itself.
+#[derive(Debug, Clone)]
+pub struct Session(pub Claims);
+
+impl<S> FromRequestParts<S> for Session
+where
+    S: Send + Sync,
+{
+    type Rejection = ApiError;
+
+    async fn from_request_parts(parts: &mut Parts, _state: &S) -> Result<Self, Self::Rejection> {
+        if let Some(claims) = parts.extensions.get::<Claims>() {
+            return Ok(Self(claims.clone()));
+        }
+        if parts.extensions.get::<Principal>().is_some() {
+            return Err(ApiError::ForbiddenError(
+                "API keys can not be used here, please login".to_string(),
+            ));
+        }
+
+        Err(ApiError::UnAuthenticatedError(
+            "Authorization header is not found!".to_string(),
+        ))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn principal() -> Principal {
+        Principal {
+            id: 1,
+            name: "Bobby: the 1st".to_string(),
+            email: "bobby@none.co".to_string(),
+            ws_id: Some(3),
+            roles: vec!["member".to_string()],
+            permissions: vec!["user:read".to_string()],
+        }
+    }
+
+    fn legacy_token(jwt: &Jwt, sub: &str) -> String {
+        let mut claims = jwt.claims(sub.to_string(), TokenType::Access, jwt.expires_in, "f");
+        claims.sid = None;
+        encode(&jwt.header, &claims, &jwt.encode_secret).unwrap()
+    }
+
+    #[test]
+    fn test_access_and_refresh_tokens_are_not_interchangeable() {
+        let jwt = Jwt::default();
+        let access_token = jwt.encode(&principal(), "family").unwrap();
+        let refresh = jwt.encode_refresh(1, "family").unwrap();
+
+        assert_eq!(jwt.decode(&access_token).unwrap().id, 1);
+        assert!(jwt.decode_refresh(&access_token).is_err());
+
+        let claims = jwt.decode_refresh(&refresh.token).unwrap();
+        assert_eq!(claims.jti, refresh.jti);
+        assert_eq!(claims.sid.as_deref(), Some("family"));
+        assert!(jwt.decode(&refresh.token).is_err());
+
+        let mfa_token = jwt.encode_mfa_pending(1, "family").unwrap();
+        assert_eq!(jwt.decode_mfa_pending(&mfa_token).unwrap().sub, "1");
+        assert!(jwt.decode(&mfa_token).is_err());
+        assert!(jwt.decode_refresh(&mfa_token).is_err());
+        assert!(jwt.decode_mfa_pending(&access_token).is_err());
+    }
+
+    #[test]
+    fn test_principal_round_trips_through_structured_claims() {
+        let jwt = Jwt::default();
+        let token = jwt.encode(&principal(), "family").unwrap();
+
+        let claims = jwt.decode_access(&token).unwrap();
+        assert_eq!(claims.sub, "1");
+        assert_eq!(jwt.decode(&token).unwrap(), principal());
+    }
+
+    #[test]
+    fn test_legacy_claims_during_migration_window() {
+        let jwt = Jwt::default();
+        let token = legacy_token(&jwt, "7:Alice:alice@none.co");
+
+        let principal = jwt.decode(&token).unwrap();
+        assert_eq!(principal.id, 7);
+        assert_eq!(principal.email, "alice@none.co");
+        assert_eq!(principal.ws_id, None);
+
+        let strict = Jwt::new(JwtConfig {
+            accept_legacy_claims: false,
+            ..Default::default()
+        })
+        .unwrap();
+        assert!(strict.decode(&token).is_err());
+    }
+
+    fn asymmetric(active_kid: &str, keys: Vec<KeyPair>) -> Jwt {
+        Jwt::new(JwtConfig {
+            algorithm: Algorithm::RS256,
+            active_kid: Some(active_kid.to_string()),
+            keys,
+            ..Default::default()
+        })
+        .unwrap()
+    }
+
+    #[test]
+    fn test_key_rotation_keeps_tokens_of_retired_keys_valid() {
+        use crate::jwk::tests::*;
+
+        let old = asymmetric(
+            "2024",
+            vec![KeyPair::from_pem("2024", Algorithm::ES256, Some(EC_PRIVATE), EC_PUBLIC).unwrap()],
+        );
+        let old_token = old.encode(&principal(), "family").unwrap();
+        assert_eq!(
+            decode_header(&old_token).unwrap().kid.as_deref(),
+            Some("2024")
+        );
+
+        let rotated = asymmetric(
+            "2025",
+            vec![
+                KeyPair::from_pem("2024", Algorithm::ES256, None, EC_PUBLIC).unwrap(),
+                KeyPair::from_pem("2025", Algorithm::RS256, Some(RSA_PRIVATE), RSA_PUBLIC).unwrap(),
+            ],
+        );
+        let new_token = rotated.encode(&principal(), "family").unwrap();
+        assert_eq!(
+            decode_header(&new_token).unwrap().kid.as_deref(),
+            Some("2025")
+        );
+
+        assert_eq!(rotated.decode(&old_token).unwrap(), principal());
+        assert_eq!(rotated.decode(&new_token).unwrap(), principal());
+        assert!(old.decode(&new_token).is_err());
+
+        let kids: Vec<_> = rotated
+            .jwks()
+            .keys
+            .iter()
+            .filter_map(|key| key.common.key_id.as_deref())
+            .collect();
+        assert_eq!(kids, vec!["2025", "2024"]);
+        assert!(Jwt::default().jwks().keys.is_empty());
+    }
+
+    #[test]
+    fn test_active_key_requires_a_private_key() {
+        use crate::jwk::tests::*;
+
+        let result = Jwt::new(JwtConfig {
+            algorithm: Algorithm::EdDSA,
+            keys: vec![KeyPair::from_pem("k1", Algorithm::EdDSA, None, ED_PUBLIC).unwrap()],
+            ..Default::default()
+        });
+        assert!(result.is_err());
+    }
+
+    #[test]
+    fn test_default_secret_is_refused_in_prod() {
+        let config = JwtConfig::default();
+        assert!(config.uses_default_secret());
+        assert!(config.check_secret(false).is_ok());
+        assert!(config.check_secret(true).is_err());
+
+        let configured = JwtConfig {
+            secret: "a-secret-from-the-environment".to_string(),
+            ..Default::default()
+        };
+        assert!(configured.check_secret(true).is_ok());
+    }
+
+    #[test]
+    fn test_malformed_subject_fails_without_panicking() {
+        let jwt = Jwt::default();
+        for sub in ["7:Alice", "abc:Alice:alice@none.co", "abc", ""] {
+            let err = jwt.decode(&legacy_token(&jwt, sub)).unwrap_err();
+            assert!(matches!(
+                err.kind(),
+                ErrorKind::InvalidSubject | ErrorKind::MissingRequiredClaim(_)
+            ));
+        }
     }
 }
@@ -1,3 +1,5 @@
+use crate::password::get_password_hashers;
+use anyhow::Context;
 use serde::{Deserialize, Deserializer, Serialize};
 use std::fmt::Display;
 use std::str::FromStr;
@@ -92,26 +94,51 @@ fn default_page_size() -> u64 {
     DEFAULT_PAGE_SIZE
 }
 
-/// Hash password with bcrypt
-pub fn hash_password(password: &str) -> anyhow::Result<String> {
-    Ok(bcrypt::hash(password, bcrypt::DEFAULT_COST)?)
+/// Hash password with the configured algorithm, Argon2id by default
+pub async fn hash_password(password: &str) -> anyhow::Result<String> {
+    let password = password.to_string();
+    blocking(move || get_password_hashers().hash(&password)).await?
 }
 
-/// Verify that a password is equivalent to the hash provided
-pub fn verify_password(password: &str, hashed_password: &str) -> anyhow::Result<bool> {
-    Ok(bcrypt::verify(password, hashed_password)?)
+/// Verify that a password is equivalent to the hash provided, of any supported algorithm
+pub async fn verify_password(password: &str, hashed_password: &str) -> anyhow::Result<bool> {
+    let (password, hashed_password) = (password.to_string(), hashed_password.to_string());
+    blocking(move || get_password_hashers().verify(&password, &hashed_password)).await?
+}
+
+/// Spend the time of a password verification when there is no hash to verify against
+pub async fn verify_dummy_password(password: &str) {
+    let password = password.to_string();
+    if let Err(e) = blocking(move || get_password_hashers().verify_dummy(&password)).await {
+        tracing::error!("error verifying the dummy password hash: {:?}", e);
+    }
+}
+
+/// Whether the hash should be upgraded to the configured algorithm and parameters
+pub fn password_needs_rehash(hashed_password: &str) -> bool {
+    get_password_hashers().needs_rehash(hashed_password)
+}
+
+/// Runs password hashing on the blocking thread pool, it takes tens of milliseconds of
+/// CPU that would stall every request sharing the async worker.
+async fn blocking<T: Send + 'static>(
+    task: impl FnOnce() -> T + Send + 'static,
+) -> anyhow::Result<T> {
+    tokio::task::spawn_blocking(task)
+        .await
+        .context("The password hashing task failed")
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
 
-    #[test]
-    fn test_hash_password() {
+    #[tokio::test]
+    async fn test_hash_password() {
         let password = "123456";
         // let hashed_password = hash_password(password).unwrap();
         let hashed_password = "$2b$12$155JdL0FeO7MPgIKC3OPZuKkhPiaok0ErA4g7.XQJSdLTjzGzP.bW";
         println!("hashed_password: {}", hashed_password);
-        assert!(verify_password(password, hashed_password).unwrap());
+        assert!(verify_password(password, hashed_password).await.unwrap());
     }
 }
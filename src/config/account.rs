@@ -0,0 +1,38 @@
+use serde::Deserialize;
+
+/// Self-service account configuration: signup, email verification and password reset.
+#[derive(Debug, Default, Deserialize)]
+pub struct AccountConfig {
+    /// Base URL of the page handling the links sent by mail
+    link_base_url: Option<String>,
+    /// Lifetime of email verification tokens (seconds)
+    verify_email_ttl: Option<u64>,
+    /// Lifetime of password reset tokens (seconds)
+    reset_password_ttl: Option<u64>,
+}
+
+impl AccountConfig {
+    /// Returns the base URL of the links sent by mail, without a trailing slash.
+    ///
+    /// Default: `http://127.0.0.1:3005`
+    pub fn link_base_url(&self) -> &str {
+        self.link_base_url
+            .as_deref()
+            .unwrap_or("http://127.0.0.1:3005")
+            .trim_end_matches('/')
+    }
+
+    /// Returns the lifetime of email verification tokens.
+    ///
+    /// Default: `86400` (24 hours)
+    pub fn verify_email_ttl(&self) -> u64 {
+        self.verify_email_ttl.unwrap_or(86400)
+    }
+
+    /// Returns the lifetime of password reset tokens.
+    ///
+    /// Default: `3600` (1 hour)
+    pub fn reset_password_ttl(&self) -> u64 {
+        self.reset_password_ttl.unwrap_or(3600)
+    }
+}
@@ -0,0 +1,86 @@
+use serde::Deserialize;
+
+/// Cross-origin resource sharing policy, the `cors` section of the server configuration.
+///
+/// Without the section any origin may call the API, without credentials.
+#[derive(Debug, Default, Deserialize)]
+pub struct CorsConfig {
+    /// Exact origins allowed to call the API, e.g. `https://app.example.com`; `*` allows any
+    allowed_origins: Option<Vec<String>>,
+    /// Regular expressions matching whole origins, e.g. `https://[a-z0-9-]+\.example\.com`
+    #[serde(default)]
+    allowed_origin_patterns: Vec<String>,
+    /// Allowed request methods
+    allowed_methods: Option<Vec<String>>,
+    /// Allowed request headers; `*` allows any
+    allowed_headers: Option<Vec<String>>,
+    /// Response headers readable by scripts of other origins
+    #[serde(default)]
+    exposed_headers: Vec<String>,
+    /// Allow cookies and `Authorization` headers on cross-origin requests
+    allow_credentials: Option<bool>,
+    /// How long browsers may cache a preflight response (seconds)
+    max_age: Option<u64>,
+}
+
+impl CorsConfig {
+    /// Returns the exact origins allowed to call the API.
+    ///
+    /// Default: `["*"]`
+    pub fn allowed_origins(&self) -> Vec<&str> {
+        match &self.allowed_origins {
+            Some(origins) => origins.iter().map(String::as_str).collect(),
+            None if self.allowed_origin_patterns.is_empty() => vec!["*"],
+            None => vec![],
+        }
+    }
+
+    /// Returns the patterns of allowed origins.
+    ///
+    /// Default: none
+    pub fn allowed_origin_patterns(&self) -> &[String] {
+        &self.allowed_origin_patterns
+    }
+
+    /// Returns the allowed request methods.
+    ///
+    /// Default: `GET`, `POST`, `PUT`, `DELETE`, `PATCH`, `OPTIONS`
+    pub fn allowed_methods(&self) -> Vec<&str> {
+        match &self.allowed_methods {
+            Some(methods) => methods.iter().map(String::as_str).collect(),
+            None => vec!["GET", "POST", "PUT", "DELETE", "PATCH", "OPTIONS"],
+        }
+    }
+
+    /// Returns the allowed request headers.
+    ///
+    /// Default: `["*"]`, or `Authorization` and `Content-Type` with credentials
+    pub fn allowed_headers(&self) -> Vec<&str> {
+        match &self.allowed_headers {
+            Some(headers) => headers.iter().map(String::as_str).collect(),
+            None if self.allow_credentials() => vec!["Authorization", "Content-Type"],
+            None => vec!["*"],
+        }
+    }
+
+    /// Returns the response headers exposed to other origins.
+    ///
+    /// Default: none
+    pub fn exposed_headers(&self) -> &[String] {
+        &self.exposed_headers
+    }
+
+    /// Returns `true` if cross-origin requests may carry credentials.
+    ///
+    /// Default: `false`
+    pub fn allow_credentials(&self) -> bool {
+        self.allow_credentials.unwrap_or(false)
+    }
+
+    /// Returns how long preflight responses may be cached.
+    ///
+    /// Default: `3600` (1 hour)
+    pub fn max_age(&self) -> u64 {
+        self.max_age.unwrap_or(3600)
+    }
+}
@@ -0,0 +1,156 @@
+use anyhow::Context;
+use serde::Deserialize;
+
+/// JWT configuration.
+///
+/// With the default `HS256` algorithm tokens are signed with a shared secret and no
+/// key files are needed. The secret is resolved from `secret_file`, then from the
+/// environment variable named by `secret_env`, then from `secret` itself. With an asymmetric algorithm (`RS256`, `ES256`, `EdDSA`) tokens
+/// are signed with the private key of `active_kid`, while every configured public key is
+/// accepted for verification and published in the JWKS document, so keys can be rotated
+/// without downtime.
+#[derive(Debug, Default, Deserialize)]
+pub struct JwtConfig {
+    /// Shared `HS256` secret, prefer `secret_env` or `secret_file` outside of development
+    secret: Option<String>,
+    /// Name of the environment variable holding the shared secret
+    secret_env: Option<String>,
+    /// Path to a file holding the shared secret
+    secret_file: Option<String>,
+    /// Token issuer (`iss`)
+    issuer: Option<String>,
+    /// Token audience (`aud`)
+    audience: Option<String>,
+    /// Access token lifetime (seconds)
+    expiration: Option<u64>,
+    /// Refresh token lifetime (seconds)
+    refresh_expiration: Option<u64>,
+    /// Lifetime of the token exchanged for a session after the second factor (seconds)
+    mfa_expiration: Option<u64>,
+    /// Accept access tokens that still pack `id:name:email` into `sub`
+    accept_legacy_claims: Option<bool>,
+    /// Signing algorithm: `HS256`, `RS256`, `ES256` or `EdDSA`
+    algorithm: Option<String>,
+    /// Key id (`kid`) of the key used to sign new tokens
+    active_kid: Option<String>,
+    /// Signing and verification keys
+    keys: Option<Vec<JwtKeyConfig>>,
+}
+
+/// A single asymmetric key, identified by its `kid`.
+#[derive(Debug, Deserialize)]
+pub struct JwtKeyConfig {
+    /// Key id published in the token header and in the JWKS document
+    kid: String,
+    /// Algorithm of this key, falls back to the section's `algorithm`
+    algorithm: Option<String>,
+    /// Path to the PKCS#8 PEM private key, only required for the active key
+    private_key: Option<String>,
+    /// Path to the PEM public key
+    public_key: String,
+}
+
+impl JwtConfig {
+    /// Resolves the shared secret from its configured source.
+    ///
+    /// Returns `Ok(None)` when no secret is configured at all.
+    pub fn resolve_secret(&self) -> anyhow::Result<Option<String>> {
+        if let Some(path) = self.secret_file.as_deref() {
+            let secret = std::fs::read_to_string(path)
+                .with_context(|| format!("Failed to read JWT secret file {}", path))?;
+            return Ok(Some(secret.trim_end().to_string()));
+        }
+        if let Some(name) = self.secret_env.as_deref() {
+            let secret = std::env::var(name)
+                .with_context(|| format!("JWT secret environment variable {} is not set", name))?;
+            return Ok(Some(secret));
+        }
+
+        Ok(self.secret.clone())
+    }
+
+    /// Returns the token issuer.
+    ///
+    /// Default: `https://www.axum-template.com`
+    pub fn issuer(&self) -> &str {
+        self.issuer
+            .as_deref()
+            .unwrap_or("https://www.axum-template.com")
+    }
+
+    /// Returns the token audience.
+    ///
+    /// Default: `https://www.axum-template.com`
+    pub fn audience(&self) -> &str {
+        self.audience
+            .as_deref()
+            .unwrap_or("https://www.axum-template.com")
+    }
+
+    /// Returns the access token lifetime in seconds.
+    ///
+    /// Default: `3600` (1 hour)
+    pub fn expiration(&self) -> u64 {
+        self.expiration.unwrap_or(3600)
+    }
+
+    /// Returns the refresh token lifetime in seconds.
+    ///
+    /// Default: `2592000` (30 days)
+    pub fn refresh_expiration(&self) -> u64 {
+        self.refresh_expiration.unwrap_or(30 * 24 * 3600)
+    }
+
+    /// Returns the lifetime of "mfa pending" tokens in seconds.
+    ///
+    /// Default: `300` (5 minutes)
+    pub fn mfa_expiration(&self) -> u64 {
+        self.mfa_expiration.unwrap_or(300)
+    }
+
+    /// Returns whether legacy `id:name:email` subjects are still accepted.
+    ///
+    /// Default: `true`
+    pub fn accept_legacy_claims(&self) -> bool {
+        self.accept_legacy_claims.unwrap_or(true)
+    }
+
+    /// Returns the signing algorithm name.
+    ///
+    /// Default: `HS256`
+    pub fn algorithm(&self) -> &str {
+        self.algorithm.as_deref().unwrap_or("HS256")
+    }
+
+    /// Returns the key id used for signing, if configured.
+    ///
+    /// Default: the first configured key
+    pub fn active_kid(&self) -> Option<&str> {
+        self.active_kid.as_deref()
+    }
+
+    /// Returns the configured keys.
+    ///
+    /// Default: none
+    pub fn keys(&self) -> &[JwtKeyConfig] {
+        self.keys.as_deref().unwrap_or_default()
+    }
+}
+
+impl JwtKeyConfig {
+    pub fn kid(&self) -> &str {
+        &self.kid
+    }
+
+    pub fn algorithm(&self) -> Option<&str> {
+        self.algorithm.as_deref()
+    }
+
+    pub fn private_key(&self) -> Option<&str> {
+        self.private_key.as_deref()
+    }
+
+    pub fn public_key(&self) -> &str {
+        &self.public_key
+    }
+}
@@ -0,0 +1,111 @@
+use serde::Deserialize;
+use std::collections::BTreeMap;
+
+/// Format of the log lines.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum LogFormat {
+    /// Human-readable lines, for development.
+    #[default]
+    Pretty,
+    /// One JSON object per line with the fields of the enclosing spans, for log shippers.
+    Json,
+}
+
+/// Destination of the log lines.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum LogOutput {
+    #[default]
+    Stdout,
+    /// Files in `dir`, rotated daily and deleted after `max_files` days.
+    File,
+}
+
+/// Logging configuration.
+#[derive(Debug, Default, Deserialize)]
+pub struct LoggingConfig {
+    /// Format of the log lines
+    format: Option<LogFormat>,
+    /// Where the log lines are written
+    output: Option<LogOutput>,
+    /// Directory of the log files
+    dir: Option<String>,
+    /// Name of the log files, suffixed with their date
+    file_prefix: Option<String>,
+    /// Number of daily files kept
+    max_files: Option<usize>,
+    /// Level of every module without its own level
+    level: Option<String>,
+    /// Levels of single modules, e.g. `sea_orm: warn`
+    #[serde(default)]
+    modules: BTreeMap<String, String>,
+    /// Headers whose values are masked in the log output, case-insensitive
+    redacted_headers: Option<Vec<String>>,
+}
+
+impl LoggingConfig {
+    /// Returns the format of the log lines.
+    ///
+    /// Default: `pretty`
+    pub fn format(&self) -> LogFormat {
+        self.format.unwrap_or_default()
+    }
+
+    /// Returns where the log lines are written.
+    ///
+    /// Default: `stdout`
+    pub fn output(&self) -> LogOutput {
+        self.output.unwrap_or_default()
+    }
+
+    /// Returns the directory of the log files.
+    ///
+    /// Default: `logs`
+    pub fn dir(&self) -> &str {
+        self.dir.as_deref().unwrap_or("logs")
+    }
+
+    /// Returns the name of the log files.
+    ///
+    /// Default: `axum_template.log`
+    pub fn file_prefix(&self) -> &str {
+        self.file_prefix.as_deref().unwrap_or("axum_template.log")
+    }
+
+    /// Returns the number of daily log files kept.
+    ///
+    /// Default: `7`
+    pub fn max_files(&self) -> usize {
+        self.max_files.unwrap_or(7)
+    }
+
+    /// Returns the filter directives of the levels, e.g. `info,sea_orm=warn`.
+    ///
+    /// Default: `info`
+    pub fn filter(&self) -> String {
+        let mut directives = vec![self.level.as_deref().unwrap_or("info").to_string()];
+        directives.extend(
+            self.modules
+                .iter()
+                .map(|(module, level)| format!("{}={}", module, level)),
+        );
+        directives.join(",")
+    }
+
+    /// Returns the headers whose values are masked in the log output.
+    ///
+    /// Default: `Authorization`, `Cookie`, `Set-Cookie`, `X-CSRF-Token`, `X-API-Key`
+    pub fn redacted_headers(&self) -> Vec<&str> {
+        match &self.redacted_headers {
+            Some(headers) => headers.iter().map(String::as_str).collect(),
+            None => vec![
+                "Authorization",
+                "Cookie",
+                "Set-Cookie",
+                "X-CSRF-Token",
+                "X-API-Key",
+            ],
+        }
+    }
+}
@@ -0,0 +1,71 @@
+use serde::Deserialize;
+
+/// Brute-force protection for `/auth/login`.
+///
+/// Failed attempts are counted per account and per client IP. Once a counter reaches
+/// its threshold the account or IP is locked, and every further failure doubles the
+/// lockout up to `max_lockout`. Counters are forgotten after `window` without failures.
+#[derive(Debug, Default, Deserialize)]
+pub struct LoginGuardConfig {
+    /// Failed attempts before an account is locked
+    max_failures_per_account: Option<u32>,
+    /// Failed attempts before a client IP is locked
+    max_failures_per_ip: Option<u32>,
+    /// First lockout duration (seconds)
+    lockout: Option<u64>,
+    /// Upper bound of the lockout duration (seconds)
+    max_lockout: Option<u64>,
+    /// Time without failures after which the counters reset (seconds)
+    window: Option<u64>,
+    /// Header set by a trusted reverse proxy with the client IP, e.g. `X-Forwarded-For`
+    client_ip_header: Option<String>,
+}
+
+impl LoginGuardConfig {
+    /// Returns the failed attempts allowed per account.
+    ///
+    /// Default: `5`
+    pub fn max_failures_per_account(&self) -> u32 {
+        self.max_failures_per_account.unwrap_or(5)
+    }
+
+    /// Returns the failed attempts allowed per client IP.
+    ///
+    /// Default: `20`
+    pub fn max_failures_per_ip(&self) -> u32 {
+        self.max_failures_per_ip.unwrap_or(20)
+    }
+
+    /// Returns the first lockout duration.
+    ///
+    /// Default: `60` (1 minute)
+    pub fn lockout(&self) -> u64 {
+        self.lockout.unwrap_or(60)
+    }
+
+    /// Returns the upper bound of the lockout duration.
+    ///
+    /// Default: `3600` (1 hour)
+    pub fn max_lockout(&self) -> u64 {
+        self.max_lockout.unwrap_or(3600)
+    }
+
+    /// Returns the time without failures after which the counters reset.
+    ///
+    /// Default: `900` (15 minutes)
+    pub fn window(&self) -> u64 {
+        self.window.unwrap_or(900)
+    }
+
+    /// Returns the header holding the client IP, `None` to use the peer address of the
+    /// connection.
+    ///
+    /// Only set it when every request passes through a reverse proxy that overwrites or
+    /// appends to the header, otherwise clients can pick the IP they are counted as.
+    /// The last address of the header is used, the one the proxy appended.
+    ///
+    /// Default: `None`
+    pub fn client_ip_header(&self) -> Option<&str> {
+        self.client_ip_header.as_deref()
+    }
+}
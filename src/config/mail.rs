@@ -0,0 +1,46 @@
+use serde::Deserialize;
+
+/// How outgoing mails are delivered.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum MailTransport {
+    /// Write mails to the log, for local development.
+    #[default]
+    Log,
+    /// Write every mail as an `.eml` file into `dir`.
+    File,
+}
+
+/// Outgoing mail configuration.
+#[derive(Debug, Default, Deserialize)]
+pub struct MailConfig {
+    /// Delivery transport
+    transport: Option<MailTransport>,
+    /// Sender address
+    from: Option<String>,
+    /// Directory the `file` transport writes to
+    dir: Option<String>,
+}
+
+impl MailConfig {
+    /// Returns the delivery transport.
+    ///
+    /// Default: `log`
+    pub fn transport(&self) -> MailTransport {
+        self.transport.unwrap_or_default()
+    }
+
+    /// Returns the sender address.
+    ///
+    /// Default: `no-reply@axum-template.com`
+    pub fn from(&self) -> &str {
+        self.from.as_deref().unwrap_or("no-reply@axum-template.com")
+    }
+
+    /// Returns the directory used by the `file` transport.
+    ///
+    /// Default: `mails`
+    pub fn dir(&self) -> &str {
+        self.dir.as_deref().unwrap_or("mails")
+    }
+}
@@ -0,0 +1,26 @@
+use serde::Deserialize;
+
+/// Two-factor authentication configuration.
+#[derive(Debug, Default, Deserialize)]
+pub struct MfaConfig {
+    /// Issuer shown by authenticator apps
+    issuer: Option<String>,
+    /// Number of recovery codes generated when TOTP is confirmed
+    recovery_codes: Option<usize>,
+}
+
+impl MfaConfig {
+    /// Returns the issuer of the TOTP provisioning URI.
+    ///
+    /// Default: `axum-template`
+    pub fn issuer(&self) -> &str {
+        self.issuer.as_deref().unwrap_or("axum-template")
+    }
+
+    /// Returns the number of recovery codes generated per enrollment.
+    ///
+    /// Default: `10`
+    pub fn recovery_codes(&self) -> usize {
+        self.recovery_codes.unwrap_or(10)
+    }
+}
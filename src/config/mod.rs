@@ -1,6 +1,17 @@
+use crate::config::account::AccountConfig;
 pub(crate) use crate::config::database::DbConfig;
 use crate::config::database::DbPoolConfig;
+use crate::config::jwt::JwtConfig;
+use crate::config::logging::LoggingConfig;
+use crate::config::login_guard::LoginGuardConfig;
+use crate::config::mail::MailConfig;
+use crate::config::mfa::MfaConfig;
+use crate::config::oidc::OidcConfig;
+use crate::config::password::PasswordConfig;
+use crate::config::revocation::RevocationConfig;
 use crate::config::server::ServerConfig;
+use crate::config::session::SessionConfig;
+use crate::config::telemetry::TelemetryConfig;
 use anyhow::{Context, Result};
 use config::{Config, FileFormat};
 use serde::Deserialize;
@@ -9,8 +20,32 @@ use std::sync::LazyLock;
 
 pub mod server;
 
+pub mod cors;
+
 pub mod database;
 
+pub mod revocation;
+
+pub mod jwt;
+
+pub mod mail;
+
+pub mod account;
+
+pub mod password;
+
+pub mod login_guard;
+
+pub mod mfa;
+
+pub mod oidc;
+
+pub mod session;
+
+pub mod telemetry;
+
+pub mod logging;
+
 /// Lazily initialized global application configuration.
 ///
 /// This static instance will be initialized only once on first access.
@@ -19,9 +54,34 @@ static APP_CONFIG: LazyLock<AppConfig> =
 
 #[derive(Debug, Deserialize)]
 pub struct AppConfig {
+    /// Runtime environment the configuration was loaded for, from `RUN_ENV`
+    #[serde(skip)]
+    run_env: String,
     server: ServerConfig,
     database: DbConfig,
     pool: DbPoolConfig,
+    #[serde(default)]
+    revocation: RevocationConfig,
+    #[serde(default)]
+    jwt: JwtConfig,
+    #[serde(default)]
+    mail: MailConfig,
+    #[serde(default)]
+    account: AccountConfig,
+    #[serde(default)]
+    password: PasswordConfig,
+    #[serde(default)]
+    login_guard: LoginGuardConfig,
+    #[serde(default)]
+    mfa: MfaConfig,
+    #[serde(default)]
+    oidc: OidcConfig,
+    #[serde(default)]
+    session: SessionConfig,
+    #[serde(default)]
+    telemetry: TelemetryConfig,
+    #[serde(default)]
+    logging: LoggingConfig,
 }
 impl AppConfig {
     /// Loads configuration from multiple sources with the following priority:
@@ -57,7 +117,8 @@ impl AppConfig {
             )
             .build()
             .with_context(|| "Failed to load config file".to_string())?
-            .try_deserialize()
+            .try_deserialize::<Self>()
+            .map(|config| Self { run_env, ..config })
             .with_context(|| "Failed to deserialize config file".to_string())
     }
 
@@ -66,6 +127,16 @@ impl AppConfig {
         &APP_CONFIG
     }
 
+    /// Returns the runtime environment, e.g. `dev` or `prod`.
+    pub fn run_env(&self) -> &str {
+        &self.run_env
+    }
+
+    /// Returns `true` when running with the production configuration.
+    pub fn is_prod(&self) -> bool {
+        self.run_env == "prod"
+    }
+
     /// Returns the server configuration.
     pub fn server(&self) -> &ServerConfig {
         &self.server
@@ -80,6 +151,61 @@ impl AppConfig {
     pub fn pool(&self) -> &DbPoolConfig {
         &self.pool
     }
+
+    /// Returns the token revocation configuration.
+    pub fn revocation(&self) -> &RevocationConfig {
+        &self.revocation
+    }
+
+    /// Returns the JWT signing configuration.
+    pub fn jwt(&self) -> &JwtConfig {
+        &self.jwt
+    }
+
+    /// Returns the outgoing mail configuration.
+    pub fn mail(&self) -> &MailConfig {
+        &self.mail
+    }
+
+    /// Returns the self-service account configuration.
+    pub fn account(&self) -> &AccountConfig {
+        &self.account
+    }
+
+    /// Returns the password hashing configuration.
+    pub fn password(&self) -> &PasswordConfig {
+        &self.password
+    }
+
+    /// Returns the login brute-force protection configuration.
+    pub fn login_guard(&self) -> &LoginGuardConfig {
+        &self.login_guard
+    }
+
+    /// Returns the two-factor authentication configuration.
+    pub fn mfa(&self) -> &MfaConfig {
+        &self.mfa
+    }
+
+    /// Returns the OpenID Connect login configuration.
+    pub fn oidc(&self) -> &OidcConfig {
+        &self.oidc
+    }
+
+    /// Returns the browser session configuration.
+    pub fn session(&self) -> &SessionConfig {
+        &self.session
+    }
+
+    /// Returns the OpenTelemetry trace export configuration.
+    pub fn telemetry(&self) -> &TelemetryConfig {
+        &self.telemetry
+    }
+
+    /// Returns the logging configuration.
+    pub fn logging(&self) -> &LoggingConfig {
+        &self.logging
+    }
 }
 
 #[cfg(test)]
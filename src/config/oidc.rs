@@ -0,0 +1,82 @@
+use serde::Deserialize;
+use std::collections::BTreeMap;
+
+/// OpenID Connect login configuration.
+#[derive(Debug, Default, Deserialize)]
+pub struct OidcConfig {
+    /// Public base URL of this server, providers redirect to `{base}/auth/oidc/{provider}/callback`
+    redirect_base_url: Option<String>,
+    /// Lifetime of a login between the redirect to the provider and its callback (seconds)
+    state_ttl: Option<u64>,
+    /// Identity providers by name, the name is part of the login and callback paths
+    #[serde(default)]
+    providers: BTreeMap<String, OidcProviderConfig>,
+}
+
+/// An OpenID Connect identity provider.
+#[derive(Debug, Clone, Deserialize)]
+pub struct OidcProviderConfig {
+    /// Issuer URL, the discovery document is read from `{issuer}/.well-known/openid-configuration`
+    issuer: String,
+    client_id: String,
+    /// Omitted for public clients, which rely on PKCE alone
+    client_secret: Option<String>,
+    /// Requested scopes, `openid` is always included
+    scopes: Option<Vec<String>>,
+}
+
+impl OidcConfig {
+    /// Returns the public base URL of this server, without a trailing slash.
+    ///
+    /// Default: `http://127.0.0.1:3005`
+    pub fn redirect_base_url(&self) -> &str {
+        self.redirect_base_url
+            .as_deref()
+            .unwrap_or("http://127.0.0.1:3005")
+            .trim_end_matches('/')
+    }
+
+    /// Returns the lifetime of a pending login.
+    ///
+    /// Default: `600` (10 minutes)
+    pub fn state_ttl(&self) -> u64 {
+        self.state_ttl.unwrap_or(600)
+    }
+
+    /// Returns the configured providers by name.
+    ///
+    /// Default: none, OIDC login is disabled
+    pub fn providers(&self) -> &BTreeMap<String, OidcProviderConfig> {
+        &self.providers
+    }
+}
+
+impl OidcProviderConfig {
+    /// Returns the issuer URL, without a trailing slash.
+    pub fn issuer(&self) -> &str {
+        self.issuer.trim_end_matches('/')
+    }
+
+    /// Returns the client id registered at the provider.
+    pub fn client_id(&self) -> &str {
+        &self.client_id
+    }
+
+    /// Returns the client secret, `None` for public clients.
+    pub fn client_secret(&self) -> Option<&str> {
+        self.client_secret.as_deref()
+    }
+
+    /// Returns the requested scopes, space separated.
+    ///
+    /// Default: `openid email profile`
+    pub fn scopes(&self) -> String {
+        match &self.scopes {
+            Some(scopes) => std::iter::once("openid")
+                .chain(scopes.iter().map(String::as_str).filter(|s| *s != "openid"))
+                .collect::<Vec<_>>()
+                .join(" "),
+            None => "openid email profile".to_string(),
+        }
+    }
+}
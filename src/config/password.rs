@@ -0,0 +1,66 @@
+use serde::Deserialize;
+
+/// Algorithm used to hash new passwords.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum PasswordAlgorithm {
+    #[default]
+    Argon2id,
+    /// Only for compatibility, bcrypt ignores everything after the first 72 bytes.
+    Bcrypt,
+}
+
+/// Password hashing configuration.
+///
+/// Existing hashes of any supported algorithm keep verifying; hashes that do not
+/// match the current algorithm and parameters are upgraded on the next login.
+#[derive(Debug, Default, Deserialize)]
+pub struct PasswordConfig {
+    /// Algorithm for new hashes
+    algorithm: Option<PasswordAlgorithm>,
+    /// Argon2 memory cost (KiB)
+    argon2_memory_kib: Option<u32>,
+    /// Argon2 number of iterations
+    argon2_iterations: Option<u32>,
+    /// Argon2 degree of parallelism
+    argon2_parallelism: Option<u32>,
+    /// Bcrypt cost factor
+    bcrypt_cost: Option<u32>,
+}
+
+impl PasswordConfig {
+    /// Returns the algorithm used for new hashes.
+    ///
+    /// Default: `argon2id`
+    pub fn algorithm(&self) -> PasswordAlgorithm {
+        self.algorithm.unwrap_or_default()
+    }
+
+    /// Returns the Argon2 memory cost in KiB.
+    ///
+    /// Default: `19456` (19 MiB)
+    pub fn argon2_memory_kib(&self) -> u32 {
+        self.argon2_memory_kib.unwrap_or(19456)
+    }
+
+    /// Returns the Argon2 number of iterations.
+    ///
+    /// Default: `2`
+    pub fn argon2_iterations(&self) -> u32 {
+        self.argon2_iterations.unwrap_or(2)
+    }
+
+    /// Returns the Argon2 degree of parallelism.
+    ///
+    /// Default: `1`
+    pub fn argon2_parallelism(&self) -> u32 {
+        self.argon2_parallelism.unwrap_or(1)
+    }
+
+    /// Returns the bcrypt cost factor.
+    ///
+    /// Default: `12`
+    pub fn bcrypt_cost(&self) -> u32 {
+        self.bcrypt_cost.unwrap_or(bcrypt::DEFAULT_COST)
+    }
+}
@@ -0,0 +1,37 @@
+use serde::Deserialize;
+
+/// Backend used to persist revoked access tokens.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum RevocationBackend {
+    /// Process-local store, lost on restart and not shared between instances.
+    #[default]
+    Memory,
+    /// Shared store backed by the `revoked_token` table.
+    Postgres,
+}
+
+/// Token revocation (denylist) configuration.
+#[derive(Debug, Default, Deserialize)]
+pub struct RevocationConfig {
+    /// Where revoked token ids are stored
+    store: Option<RevocationBackend>,
+    /// Interval between purges of expired denylist entries (seconds)
+    cleanup_interval: Option<u64>,
+}
+
+impl RevocationConfig {
+    /// Returns the configured revocation backend.
+    ///
+    /// Default: `memory`
+    pub fn store(&self) -> RevocationBackend {
+        self.store.unwrap_or_default()
+    }
+
+    /// Returns the interval between purges of expired entries.
+    ///
+    /// Default: `300` (5 minutes)
+    pub fn cleanup_interval(&self) -> u64 {
+        self.cleanup_interval.unwrap_or(300)
+    }
+}
@@ -1,14 +1,31 @@
+use crate::config::cors::CorsConfig;
+use bytesize::ByteSize;
 use serde::Deserialize;
 
 /// Server configuration for HTTP server settings.
 ///
-/// Contains host and port configuration for the web server.
+/// Contains host, port, request limits and CORS configuration for the web server.
 #[derive(Debug, Deserialize)]
 pub struct ServerConfig {
     /// HTTP server hostname or IP address to bind to
     host: Option<String>,
     /// HTTP server port to listen on
     port: Option<u16>,
+    /// Time to handle a request before answering `408 Request Timeout` (seconds)
+    request_timeout: Option<u64>,
+    /// Largest accepted request body, e.g. `10MiB`
+    body_limit: Option<ByteSize>,
+    /// Time to handle a request of the `/auth` routes (seconds)
+    auth_request_timeout: Option<u64>,
+    /// Largest accepted request body of the `/auth` routes, e.g. `16KiB`
+    auth_body_limit: Option<ByteSize>,
+    /// Time to finish in-flight requests after a shutdown signal (seconds)
+    shutdown_timeout: Option<u64>,
+    /// Port of the admin server serving `/metrics`, on the main port when not set
+    admin_port: Option<u16>,
+    /// Cross-origin resource sharing policy
+    #[serde(default)]
+    cors: CorsConfig,
 }
 
 impl ServerConfig {
@@ -25,4 +42,53 @@ impl ServerConfig {
     pub fn get_port(&self) -> u16 {
         self.port.unwrap_or(3000)
     }
+
+    /// Returns the default time to handle a request, route groups may override it.
+    ///
+    /// Default: `60`
+    pub fn request_timeout(&self) -> u64 {
+        self.request_timeout.unwrap_or(60)
+    }
+
+    /// Returns the default largest request body, route groups may override it.
+    ///
+    /// Default: `10MiB`
+    pub fn body_limit(&self) -> ByteSize {
+        self.body_limit.unwrap_or(ByteSize::mib(10))
+    }
+
+    /// Returns the time to handle a request of the `/auth` routes, which should answer
+    /// quickly.
+    ///
+    /// Default: `10`
+    pub fn auth_request_timeout(&self) -> u64 {
+        self.auth_request_timeout.unwrap_or(10)
+    }
+
+    /// Returns the largest request body of the `/auth` routes, which only take small
+    /// JSON documents.
+    ///
+    /// Default: `16KiB`
+    pub fn auth_body_limit(&self) -> ByteSize {
+        self.auth_body_limit.unwrap_or(ByteSize::kib(16))
+    }
+
+    /// Returns how long in-flight requests may run after a shutdown signal.
+    ///
+    /// Default: `30`
+    pub fn shutdown_timeout(&self) -> u64 {
+        self.shutdown_timeout.unwrap_or(30)
+    }
+
+    /// Returns the port of the admin server, `None` to serve its routes on the main port.
+    ///
+    /// Default: none
+    pub fn admin_port(&self) -> Option<u16> {
+        self.admin_port
+    }
+
+    /// Returns the cross-origin resource sharing policy.
+    pub fn cors(&self) -> &CorsConfig {
+        &self.cors
+    }
 }
@@ -0,0 +1,75 @@
+use serde::Deserialize;
+
+/// How clients carry their access and refresh tokens.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum SessionMode {
+    /// Tokens are returned in the response body and sent as `Authorization: Bearer`.
+    #[default]
+    Bearer,
+    /// Tokens are kept in `HttpOnly` cookies, state-changing requests need a CSRF token.
+    Cookie,
+}
+
+/// `SameSite` attribute of the session cookies.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum SameSite {
+    #[default]
+    Strict,
+    Lax,
+    /// Only allowed together with `cookie_secure`.
+    None,
+}
+
+/// Browser session configuration.
+#[derive(Debug, Default, Deserialize)]
+pub struct SessionConfig {
+    /// Whether tokens travel in the body and `Authorization` header, or in cookies
+    mode: Option<SessionMode>,
+    /// Send the cookies over HTTPS only
+    cookie_secure: Option<bool>,
+    /// `SameSite` attribute of the cookies
+    same_site: Option<SameSite>,
+    /// `Domain` attribute of the cookies, host-only when not set
+    cookie_domain: Option<String>,
+    /// Header echoing the CSRF cookie on state-changing requests
+    csrf_header: Option<String>,
+}
+
+impl SessionConfig {
+    /// Returns how clients carry their tokens.
+    ///
+    /// Default: `bearer`
+    pub fn mode(&self) -> SessionMode {
+        self.mode.unwrap_or_default()
+    }
+
+    /// Returns `true` if the cookies are only sent over HTTPS.
+    ///
+    /// Default: `true`
+    pub fn cookie_secure(&self) -> bool {
+        self.cookie_secure.unwrap_or(true)
+    }
+
+    /// Returns the `SameSite` attribute of the cookies.
+    ///
+    /// Default: `strict`
+    pub fn same_site(&self) -> SameSite {
+        self.same_site.unwrap_or_default()
+    }
+
+    /// Returns the `Domain` attribute of the cookies.
+    ///
+    /// Default: none, the cookies are host-only
+    pub fn cookie_domain(&self) -> Option<&str> {
+        self.cookie_domain.as_deref()
+    }
+
+    /// Returns the name of the CSRF request header.
+    ///
+    /// Default: `X-CSRF-Token`
+    pub fn csrf_header(&self) -> &str {
+        self.csrf_header.as_deref().unwrap_or("X-CSRF-Token")
+    }
+}
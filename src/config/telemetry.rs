@@ -0,0 +1,55 @@
+use serde::Deserialize;
+
+/// OpenTelemetry trace export configuration.
+#[derive(Debug, Default, Deserialize)]
+pub struct TelemetryConfig {
+    /// Export spans and propagate W3C trace context
+    enabled: Option<bool>,
+    /// OTLP/HTTP traces endpoint of the collector
+    endpoint: Option<String>,
+    /// `service.name` of the exported spans
+    service_name: Option<String>,
+    /// Share of new traces to sample, traces continued from a caller follow its decision
+    sample_ratio: Option<f64>,
+    /// Time to send a batch of spans before giving up (seconds)
+    export_timeout: Option<u64>,
+}
+
+impl TelemetryConfig {
+    /// Returns `true` if spans are exported.
+    ///
+    /// Default: `false`
+    pub fn enabled(&self) -> bool {
+        self.enabled.unwrap_or(false)
+    }
+
+    /// Returns the OTLP/HTTP endpoint receiving the spans.
+    ///
+    /// Default: `http://localhost:4318/v1/traces`
+    pub fn endpoint(&self) -> &str {
+        self.endpoint
+            .as_deref()
+            .unwrap_or("http://localhost:4318/v1/traces")
+    }
+
+    /// Returns the name of the service in the exported spans.
+    ///
+    /// Default: `axum_template`
+    pub fn service_name(&self) -> &str {
+        self.service_name.as_deref().unwrap_or("axum_template")
+    }
+
+    /// Returns the share of new traces that are sampled, between `0.0` and `1.0`.
+    ///
+    /// Default: `1.0`
+    pub fn sample_ratio(&self) -> f64 {
+        self.sample_ratio.unwrap_or(1.0).clamp(0.0, 1.0)
+    }
+
+    /// Returns the time to send a batch of spans.
+    ///
+    /// Default: `10`
+    pub fn export_timeout(&self) -> u64 {
+        self.export_timeout.unwrap_or(10)
+    }
+}
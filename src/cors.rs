@@ -0,0 +1,181 @@
+use crate::config::cors::CorsConfig;
+use crate::config::AppConfig;
+use crate::request_id::REQUEST_ID_HEADER;
+use anyhow::{bail, Context};
+use http::{HeaderName, HeaderValue, Method};
+use regex::Regex;
+use std::time::Duration;
+use tower_http::cors::{AllowHeaders, AllowMethods, AllowOrigin, CorsLayer, ExposeHeaders};
+
+/// Validates the CORS policy before the server starts.
+///
+/// Fails on unparsable values, and on wildcards combined with `allow_credentials`, which
+/// would let any site make authenticated requests on behalf of its visitors.
+pub fn check_config(config: &AppConfig) -> anyhow::Result<()> {
+    build_layer(config.server().cors(), None).map(|_| ())
+}
+
+/// Builds the CORS layer of the policy.
+///
+/// `csrf_header` is allowed in addition to the configured headers, so browser apps in
+/// cookie session mode can send it, and `X-Request-Id` is always exposed.
+pub fn build_layer(
+    config: &CorsConfig,
+    csrf_header: Option<&HeaderName>,
+) -> anyhow::Result<CorsLayer> {
+    let origins = config.allowed_origins();
+    let headers = config.allowed_headers();
+    let any_origin = origins.contains(&"*");
+    let any_header = headers.contains(&"*");
+    if config.allow_credentials() {
+        if any_origin {
+            bail!("CORS allowed_origins * can not be combined with allow_credentials, list the origins");
+        }
+        if any_header {
+            bail!("CORS allowed_headers * can not be combined with allow_credentials, list the headers");
+        }
+    }
+
+    let allow_origin = if any_origin {
+        AllowOrigin::any()
+    } else {
+        let exact = origins
+            .iter()
+            .map(|origin| {
+                HeaderValue::from_str(origin)
+                    .with_context(|| format!("Invalid CORS origin {}", origin))
+            })
+            .collect::<anyhow::Result<Vec<_>>>()?;
+        let patterns = config
+            .allowed_origin_patterns()
+            .iter()
+            .map(|pattern| {
+                // a pattern must match the whole origin, not a part of a malicious one
+                Regex::new(&format!("^(?:{})$", pattern))
+                    .with_context(|| format!("Invalid CORS origin pattern {}", pattern))
+            })
+            .collect::<anyhow::Result<Vec<_>>>()?;
+
+        if patterns.is_empty() {
+            AllowOrigin::list(exact)
+        } else {
+            AllowOrigin::predicate(move |origin, _| {
+                exact.contains(origin)
+                    || origin
+                        .to_str()
+                        .is_ok_and(|origin| patterns.iter().any(|regex| regex.is_match(origin)))
+            })
+        }
+    };
+
+    let methods = config
+        .allowed_methods()
+        .iter()
+        .map(|method| {
+            Method::from_bytes(method.as_bytes())
+                .with_context(|| format!("Invalid CORS method {}", method))
+        })
+        .collect::<anyhow::Result<Vec<_>>>()?;
+
+    let allow_headers = if any_header {
+        AllowHeaders::any()
+    } else {
+        let mut names = parse_headers(headers)?;
+        names.extend(csrf_header.cloned());
+        AllowHeaders::list(names)
+    };
+
+    let mut exposed = parse_headers(config.exposed_headers().iter().map(String::as_str))?;
+    if !exposed.contains(&REQUEST_ID_HEADER) {
+        exposed.push(REQUEST_ID_HEADER);
+    }
+
+    Ok(CorsLayer::new()
+        .allow_origin(allow_origin)
+        .allow_methods(AllowMethods::list(methods))
+        .allow_headers(allow_headers)
+        .expose_headers(ExposeHeaders::list(exposed))
+        .allow_credentials(config.allow_credentials())
+        .max_age(Duration::from_secs(config.max_age())))
+}
+
+fn parse_headers<'a>(names: impl IntoIterator<Item = &'a str>) -> anyhow::Result<Vec<HeaderName>> {
+    names
+        .into_iter()
+        .map(|name| {
+            HeaderName::try_from(name).with_context(|| format!("Invalid CORS header {}", name))
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use axum::body::Body;
+    use axum::routing::get;
+    use axum::Router;
+    use http::{header, Request};
+    use tower::ServiceExt;
+
+    fn layer(config: serde_json::Value) -> anyhow::Result<CorsLayer> {
+        build_layer(&serde_json::from_value(config).unwrap(), None)
+    }
+
+    async fn allowed_origin(cors: CorsLayer, origin: &str) -> Option<HeaderValue> {
+        let app = Router::new().route("/", get(|| async {})).layer(cors);
+        let request = Request::get("/")
+            .header(header::ORIGIN, origin)
+            .body(Body::empty())
+            .unwrap();
+
+        let response = app.oneshot(request).await.unwrap();
+        response
+            .headers()
+            .get(header::ACCESS_CONTROL_ALLOW_ORIGIN)
+            .cloned()
+    }
+
+    #[test]
+    fn test_wildcards_with_credentials_are_refused() {
+        assert!(layer(serde_json::json!({})).is_ok());
+        assert!(layer(serde_json::json!({ "allow_credentials": true })).is_err());
+        assert!(layer(serde_json::json!({
+            "allowed_origins": ["https://app.none.co"],
+            "allowed_headers": ["*"],
+            "allow_credentials": true,
+        }))
+        .is_err());
+        assert!(layer(serde_json::json!({
+            "allowed_origins": ["https://app.none.co"],
+            "allow_credentials": true,
+        }))
+        .is_ok());
+        assert!(layer(serde_json::json!({ "allowed_origin_patterns": ["("] })).is_err());
+    }
+
+    #[tokio::test]
+    async fn test_origins_match_exactly_or_by_whole_pattern() {
+        let config = serde_json::json!({
+            "allowed_origins": ["https://app.none.co"],
+            "allowed_origin_patterns": ["https://[a-z0-9-]+\\.preview\\.none\\.co"],
+            "allow_credentials": true,
+        });
+
+        for origin in ["https://app.none.co", "https://pr-42.preview.none.co"] {
+            assert_eq!(
+                allowed_origin(layer(config.clone()).unwrap(), origin).await,
+                Some(HeaderValue::from_str(origin).unwrap())
+            );
+        }
+        for origin in [
+            "https://evil.co",
+            "https://pr-42.preview.none.co.evil.co",
+            "https://app.none.co.evil.co",
+        ] {
+            assert_eq!(
+                allowed_origin(layer(config.clone()).unwrap(), origin).await,
+                None
+            );
+        }
+    }
+}
@@ -1,8 +1,10 @@
-use crate::config;
+use crate::metrics::Metrics;
+use crate::{config, telemetry};
 use sea_orm::{
     ConnectOptions, ConnectionTrait, Database, DatabaseConnection, DbBackend, Statement,
 };
 use std::cmp::max;
+use std::sync::Arc;
 use std::time::Duration;
 
 pub async fn init() -> anyhow::Result<DatabaseConnection> {
@@ -30,6 +32,23 @@ pub async fn init() -> anyhow::Result<DatabaseConnection> {
     Ok(db_connection)
 }
 
+/// Records the duration of every query in `metrics`, and as a span of the current trace.
+pub fn instrument(db: &mut DatabaseConnection, metrics: Arc<Metrics>) {
+    db.set_metric_callback(move |info| {
+        metrics.observe_query(info);
+        telemetry::record_query(info);
+    });
+}
+
+/// The kind of statement from its first keyword, `OTHER` unless a plain CRUD statement.
+pub(crate) fn operation(sql: &str) -> &'static str {
+    let keyword = sql.split_whitespace().next().unwrap_or_default();
+    ["SELECT", "INSERT", "UPDATE", "DELETE"]
+        .into_iter()
+        .find(|operation| keyword.eq_ignore_ascii_case(operation))
+        .unwrap_or("OTHER")
+}
+
 async fn print_db_version(db: &DatabaseConnection) -> anyhow::Result<()> {
     let version = db
         .query_one(Statement::from_string(
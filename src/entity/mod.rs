@@ -2,6 +2,17 @@
 
 pub mod prelude;
 
+pub mod api_key;
+pub mod mfa_recovery_code;
+pub mod permission;
+pub mod refresh_token;
+pub mod revoked_token;
+pub mod role;
+pub mod role_permission;
 pub mod sea_orm_active_enums;
+pub mod user_identity;
+pub mod user_mfa;
+pub mod user_role;
+pub mod user_token;
 pub mod users;
 pub mod workspace;
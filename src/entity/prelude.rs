@@ -1,4 +1,15 @@
 //! `SeaORM` Entity, @generated by sea-orm-codegen 1.1.17
 
+pub use super::api_key::Entity as ApiKey;
+pub use super::mfa_recovery_code::Entity as MfaRecoveryCode;
+pub use super::permission::Entity as Permission;
+pub use super::refresh_token::Entity as RefreshToken;
+pub use super::revoked_token::Entity as RevokedToken;
+pub use super::role::Entity as Role;
+pub use super::role_permission::Entity as RolePermission;
+pub use super::user_identity::Entity as UserIdentity;
+pub use super::user_mfa::Entity as UserMfa;
+pub use super::user_role::Entity as UserRole;
+pub use super::user_token::Entity as UserToken;
 pub use super::users::Entity as Users;
 pub use super::workspace::Entity as Workspace;
@@ -21,3 +21,12 @@ impl IntoActiveValue<Gender> for Gender {
         sea_orm::ActiveValue::Set(self)
     }
 }
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, EnumIter, DeriveActiveEnum, Serialize, Deserialize)]
+#[sea_orm(rs_type = "String", db_type = "Enum", enum_name = "user_token_purpose")]
+pub enum UserTokenPurpose {
+    #[sea_orm(string_value = "verify_email")]
+    VerifyEmail,
+    #[sea_orm(string_value = "reset_password")]
+    ResetPassword,
+}
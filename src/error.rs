@@ -1,10 +1,14 @@
 use crate::response::ApiResponse;
 use axum::body::Body;
 use axum::extract::rejection::{JsonRejection, PathRejection, QueryRejection};
-use axum::http::StatusCode;
+use axum::http::{header, HeaderValue, StatusCode};
 use axum::response::{IntoResponse, Response};
 use axum_valid::ValidRejection;
 
+/// The only message returned for failed credential checks, whatever the reason, so that
+/// responses do not reveal whether an account exists.
+pub const INVALID_CREDENTIALS: &str = "account or password is not correct!";
+
 #[derive(Debug, thiserror::Error)]
 pub enum ApiError {
     #[error("Not Found")]
@@ -39,6 +43,18 @@ pub enum ApiError {
 
     #[error("UnAuthorized Error: {0}")]
     UnAuthenticatedError(String),
+
+    #[error("UnAuthorized Error: {}", INVALID_CREDENTIALS)]
+    InvalidCredentialsError,
+
+    #[error("Forbidden Error: {0}")]
+    ForbiddenError(String),
+
+    #[error("Account Locked Error: too many failed login attempts, retry in {0} seconds")]
+    AccountLockedError(u64),
+
+    #[error("Request Timeout Error: the request took too long to process")]
+    RequestTimeoutError,
 }
 
 impl ApiError {
@@ -49,19 +65,40 @@ impl ApiError {
             ApiError::BizError(_) => StatusCode::OK,
             ApiError::DatabaseError(_) => StatusCode::INTERNAL_SERVER_ERROR,
             ApiError::InternalError(_) => StatusCode::INTERNAL_SERVER_ERROR,
+            // bodies over the route's limit are not malformed, just too large
+            ApiError::JsonError(rejection)
+                if rejection.status() == StatusCode::PAYLOAD_TOO_LARGE =>
+            {
+                StatusCode::PAYLOAD_TOO_LARGE
+            }
             ApiError::QueryError(_)
             | ApiError::PathError(_)
             | ApiError::JsonError(_)
             | ApiError::ValidationError(_) => StatusCode::BAD_REQUEST,
-            ApiError::JWTError(_) | ApiError::UnAuthenticatedError(_) => StatusCode::UNAUTHORIZED,
+            ApiError::JWTError(_)
+            | ApiError::UnAuthenticatedError(_)
+            | ApiError::InvalidCredentialsError => StatusCode::UNAUTHORIZED,
+            ApiError::ForbiddenError(_) => StatusCode::FORBIDDEN,
+            ApiError::AccountLockedError(_) => StatusCode::TOO_MANY_REQUESTS,
+            ApiError::RequestTimeoutError => StatusCode::REQUEST_TIMEOUT,
         }
     }
 }
 impl IntoResponse for ApiError {
     fn into_response(self) -> Response {
         let status_code = self.status_code();
-        let body = axum::Json(ApiResponse::<()>::error(self.to_string()));
-        (status_code, body).into_response()
+        let retry_after = match self {
+            ApiError::AccountLockedError(seconds) => Some(seconds),
+            _ => None,
+        };
+        let body = ApiResponse::<()>::error(self.to_string());
+        let mut response = (status_code, body).into_response();
+        if let Some(seconds) = retry_after {
+            response
+                .headers_mut()
+                .insert(header::RETRY_AFTER, HeaderValue::from(seconds));
+        }
+        response
     }
 }
 
@@ -0,0 +1,142 @@
+use crate::api_key;
+use crate::application::AppState;
+use crate::auth::{Principal, Session};
+use crate::entity::api_key::{ActiveModel, Column, Model};
+use crate::entity::prelude::ApiKey;
+use crate::error::ApiError;
+use crate::request::BValidJson;
+use crate::response::{ApiResponse, ApiResult};
+use crate::tenant::Tenant;
+use axum::extract::{Path, State};
+use axum::Extension;
+use sea_orm::{prelude::*, QueryOrder, Set};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeSet;
+use validator::Validate;
+
+#[derive(Debug, Deserialize, Validate)]
+pub(crate) struct CreateApiKeyRequest {
+    #[validate(length(
+        min = 1,
+        max = 64,
+        message = "name must be between 1 and 64 characters"
+    ))]
+    pub name: String,
+    /// Permissions granted to the key, each one must be held by the caller
+    #[validate(length(min = 1, message = "at least one scope is required"))]
+    pub scopes: Vec<String>,
+    /// The key never expires if not set
+    #[validate(range(
+        min = 1,
+        max = 3650,
+        message = "expires_in_days must be between 1 and 3650"
+    ))]
+    pub expires_in_days: Option<u32>,
+}
+
+/// A new API key, the only time `key` is ever returned.
+#[derive(Debug, Serialize)]
+pub(crate) struct CreatedApiKey {
+    key: String,
+    #[serde(flatten)]
+    api_key: Model,
+}
+
+/// create an API key in the caller's workspace, acting as the caller
+#[tracing::instrument(name = "create_api_key", skip_all, fields(user = %principal, name = %params.name))]
+pub(crate) async fn create(
+    State(AppState { db, .. }): State<AppState>,
+    tenant: Tenant,
+    Extension(principal): Extension<Principal>,
+    _session: Session,
+    BValidJson(params): BValidJson<CreateApiKeyRequest>,
+) -> ApiResult<CreatedApiKey> {
+    // a key can never do more than the user who created it
+    let scopes: BTreeSet<&str> = params.scopes.iter().map(|scope| scope.trim()).collect();
+    if let Some(scope) = scopes.iter().find(|scope| !principal.has_permission(scope)) {
+        tracing::warn!(
+            "{} requested scope {} beyond its permissions",
+            principal,
+            scope
+        );
+        return Err(ApiError::ForbiddenError(format!(
+            "scope {} exceeds your permissions",
+            scope
+        )));
+    }
+
+    let expires_at = match params.expires_in_days {
+        Some(days) => {
+            let expires_at =
+                jsonwebtoken::get_current_timestamp().saturating_add(u64::from(days) * 24 * 3600);
+            let expires_at = DateTimeUtc::from_timestamp(expires_at as i64, 0)
+                .ok_or_else(|| anyhow::anyhow!("invalid API key expiration: {}", expires_at))?;
+            Some(expires_at.fixed_offset())
+        }
+        None => None,
+    };
+
+    let generated = api_key::generate();
+    let api_key = ActiveModel {
+        ws_id: Set(tenant.ws_id),
+        user_id: Set(tenant.user_id),
+        name: Set(params.name),
+        prefix: Set(generated.prefix),
+        key_hash: Set(generated.hash),
+        scopes: Set(scopes.into_iter().collect::<Vec<_>>().join(" ")),
+        expires_at: Set(expires_at),
+        ..Default::default()
+    }
+    .insert(&db)
+    .await?;
+
+    tracing::info!("API key {} created, id: {}", api_key.prefix, api_key.id);
+    Ok(ApiResponse::success(
+        "API key created, store it now, it can not be shown again!",
+        Some(CreatedApiKey {
+            key: generated.key,
+            api_key,
+        }),
+    ))
+}
+
+/// list the API keys of the caller's workspace, without their secrets
+#[tracing::instrument(name = "get_api_keys", skip_all)]
+pub(crate) async fn list(
+    State(AppState { db, .. }): State<AppState>,
+    tenant: Tenant,
+    _session: Session,
+) -> ApiResult<Vec<Model>> {
+    let api_keys = tenant
+        .find::<ApiKey>()
+        .order_by_desc(Column::Id)
+        .all(&db)
+        .await?;
+
+    Ok(ApiResponse::success("", Some(api_keys)))
+}
+
+/// revoke an API key of the caller's workspace, it is rejected from the next request on
+#[tracing::instrument(name = "revoke_api_key", skip(db, _session))]
+pub(crate) async fn revoke(
+    State(AppState { db, .. }): State<AppState>,
+    tenant: Tenant,
+    _session: Session,
+    Path(id): Path<u64>,
+) -> ApiResult<()> {
+    let revoked = tenant
+        .update_many::<ApiKey>()
+        .col_expr(Column::RevokedAt, Expr::current_timestamp().into())
+        .filter(Column::Id.eq(id as i64))
+        .filter(Column::RevokedAt.is_null())
+        .exec(&db)
+        .await?;
+
+    if revoked.rows_affected == 0 {
+        tracing::error!("API key id: {} not found", id);
+        return Ok(ApiResponse::error(format!("API key id: {} not found", id)));
+    }
+
+    tracing::info!("API key revoked, id: {}", id);
+    Ok(ApiResponse::success("API key revoked successfully!", None))
+}
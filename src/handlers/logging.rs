@@ -0,0 +1,66 @@
+use crate::application::AppState;
+use crate::auth::Principal;
+use crate::error::ApiError;
+use crate::request::BValidJson;
+use crate::response::{ApiResponse, ApiResult};
+use axum::extract::State;
+use axum::Extension;
+use serde::{Deserialize, Serialize};
+use tracing_subscriber::EnvFilter;
+use validator::Validate;
+
+#[derive(Debug, Deserialize, Validate)]
+pub(crate) struct UpdateLogFilterRequest {
+    /// Directives in the `RUST_LOG` syntax, e.g. `info,axum_template::auth=debug`
+    #[validate(length(
+        min = 1,
+        max = 1024,
+        message = "filter must be between 1 and 1024 characters"
+    ))]
+    pub filter: String,
+}
+
+#[derive(Debug, Serialize)]
+pub(crate) struct LogFilterResponse {
+    filter: String,
+}
+
+/// return the log levels in effect
+#[tracing::instrument(name = "get_log_filter", skip_all)]
+pub(crate) async fn get(
+    State(AppState { log_filter, .. }): State<AppState>,
+) -> ApiResult<LogFilterResponse> {
+    let filter = log_filter
+        .current()
+        .ok_or_else(|| anyhow::anyhow!("The log filter is not installed"))?;
+
+    Ok(ApiResponse::success("", Some(LogFilterResponse { filter })))
+}
+
+/// change the log levels of the running server, until the next change or restart
+#[tracing::instrument(name = "update_log_filter", skip_all, fields(user = %principal))]
+pub(crate) async fn update(
+    State(AppState { log_filter, .. }): State<AppState>,
+    Extension(principal): Extension<Principal>,
+    BValidJson(params): BValidJson<UpdateLogFilterRequest>,
+) -> ApiResult<LogFilterResponse> {
+    let filter = EnvFilter::builder()
+        .parse(&params.filter)
+        .map_err(|e| ApiError::ValidationError(format!("invalid filter: {}", e)))?;
+
+    let previous = log_filter.current().unwrap_or_default();
+    // recorded under the previous levels, the new ones may silence it
+    tracing::warn!(
+        "{} changes the log filter from {} to {}",
+        principal,
+        previous,
+        filter
+    );
+    log_filter.set(filter)?;
+    let filter = log_filter.current().unwrap_or_default();
+
+    Ok(ApiResponse::success(
+        "log filter changed",
+        Some(LogFilterResponse { filter }),
+    ))
+}
@@ -1,5 +1,7 @@
 use crate::error::ApiError;
 
+pub(crate) mod api_key;
+pub(crate) mod logging;
 pub(crate) mod user;
 pub(crate) mod workspace;
 
@@ -1,20 +1,27 @@
 use crate::application::AppState;
-use crate::common::{Page, Pagination};
+use crate::auth::Principal;
+use crate::common::{hash_password, Page, Pagination};
 use crate::entity::prelude::*;
 use crate::entity::sea_orm_active_enums::Gender;
-use crate::entity::users;
 use crate::entity::users::{ActiveModel, Model};
-use crate::request::BValidQuery;
-use crate::response::ApiResponse;
+use crate::entity::{users, workspace};
+use crate::error::ApiError;
+use crate::redact::Email;
+use crate::request::{BValidJson, BValidQuery};
+use crate::response::{ApiResponse, ApiResult};
+use crate::tenant::Tenant;
 use axum::extract::Path;
 use axum::extract::State;
-use axum::Json;
+use axum::Extension;
 use sea_orm::{prelude::*, Condition, QueryOrder, Set};
 use serde::Deserialize;
 use std::fmt::{Display, Formatter};
 use validator::Validate;
 
-#[derive(Debug, Deserialize, Validate, DeriveIntoActiveModel)]
+/// Permission required to move a user out of the caller's workspace.
+const TRANSFER_PERMISSION: &str = "user:transfer";
+
+#[derive(Debug, Deserialize, Validate)]
 pub(crate) struct CreateUserRequest {
     #[validate(length(
         min = 1,
@@ -28,7 +35,12 @@ pub(crate) struct CreateUserRequest {
         message = "invalid email format, please check."
     ))]
     pub email: String,
-    pub password_hash: String,
+    /// Plain text password, only its hash is stored
+    #[validate(custom(
+        function = "crate::request::is_password_strong",
+        message = "password must be 8 to 128 characters with at least one letter and one digit."
+    ))]
+    pub password: String,
     #[validate(range(min = 1, message = "ws_id must be greater than 0"))]
     pub ws_id: i64,
 }
@@ -44,13 +56,18 @@ pub(crate) struct UserQuery {
     pub pagination: Option<Pagination>,
 }
 
-/// delete user by id
+/// delete user by id, only users of the caller's workspace can be deleted
 #[tracing::instrument(name = "delete_user_by_id", skip(db))]
 pub(crate) async fn delete_by_id(
-    State(AppState { db }): State<AppState>,
+    State(AppState { db, .. }): State<AppState>,
+    tenant: Tenant,
     Path(id): Path<u64>,
 ) -> ApiResponse<()> {
-    let rt = users::Entity::delete_by_id(id as i64).exec(&db).await;
+    let rt = tenant
+        .delete_many::<Users>()
+        .filter(users::Column::Id.eq(id as i64))
+        .exec(&db)
+        .await;
 
     match rt {
         Ok(deleted_user) => {
@@ -70,19 +87,52 @@ pub(crate) async fn delete_by_id(
 }
 
 /// update user ws_id by id
-#[tracing::instrument(name = "update_ws_by_id", skip(state))]
+///
+/// The user must belong to the caller's workspace. Moving the user into another
+/// workspace additionally requires the `user:transfer` permission and that the
+/// caller owns the target workspace.
+#[tracing::instrument(name = "update_ws_by_id", skip(state, principal))]
 pub(crate) async fn update_ws_by_id(
     State(state): State<AppState>,
+    Extension(principal): Extension<Principal>,
+    tenant: Tenant,
     Path((id, ws_id)): Path<(u64, u64)>,
-) -> ApiResponse<Model> {
+) -> ApiResult<Model> {
     let db = state.db();
 
-    let rt = users::Entity::update(users::ActiveModel {
-        id: Set(id as i64),
+    if ws_id as i64 != tenant.ws_id {
+        if !principal.has_permission(TRANSFER_PERMISSION) {
+            tracing::warn!("{} lacks permission {}", principal, TRANSFER_PERMISSION);
+            return Err(ApiError::ForbiddenError(format!(
+                "permission {} is required",
+                TRANSFER_PERMISSION
+            )));
+        }
+        let owns_target = Workspace::find_by_id(ws_id as i64)
+            .filter(workspace::Column::OwnerId.eq(tenant.user_id))
+            .one(db)
+            .await?
+            .is_some();
+        if !owns_target {
+            tenant.check(ws_id as i64)?;
+        }
+    }
+
+    let Some(user) = tenant
+        .find::<Users>()
+        .filter(users::Column::Id.eq(id as i64))
+        .one(db)
+        .await?
+    else {
+        tracing::error!("User id: {} not found", id);
+        return Ok(ApiResponse::error(format!("User id: {} not found", id)));
+    };
+
+    let rt = users::ActiveModel {
         ws_id: Set(ws_id as i64),
-        ..Default::default()
-    })
-    .exec(db)
+        ..user.into()
+    }
+    .update(db)
     .await;
 
     match rt {
@@ -92,52 +142,53 @@ pub(crate) async fn update_ws_by_id(
                 user.id,
                 user.fullname
             );
-            ApiResponse::success("User updated successfully!", Some(user))
-        }
-        Err(DbErr::RecordNotUpdated) => {
-            tracing::error!("User id: {} not found", id);
-            ApiResponse::error(format!("User id: {} not found", id))
+            Ok(ApiResponse::success(
+                "User updated successfully!",
+                Some(user),
+            ))
         }
         Err(e) => {
             tracing::error!("error updating user: {:?}", e);
-            ApiResponse::error(format!("error updating user: {:?}", e))
+            Ok(ApiResponse::error(format!("error updating user: {:?}", e)))
         }
     }
 }
 
-/// create user
+/// create user in the caller's workspace
 #[tracing::instrument(name="create_user", skip(state), fields(user_data = %user_data))]
 pub(crate) async fn create(
     State(state): State<AppState>,
-    Json(user_data): Json<CreateUserRequest>,
-) -> ApiResponse<Model> {
-    if let Err(ret) = user_data.validate() {
-        tracing::error!("error validating user: {:?}", ret);
-        return ApiResponse::error(format!("error validating user: {:?}", ret.to_string()));
-    }
+    tenant: Tenant,
+    BValidJson(user_data): BValidJson<CreateUserRequest>,
+) -> ApiResult<Model> {
+    tenant.check(user_data.ws_id)?;
 
     let db = state.db();
 
+    // emails are unique across all workspaces
     let existing_user = Users::find()
         .filter(users::Column::Email.eq(&user_data.email))
         .one(db)
-        .await
-        .unwrap();
+        .await?;
 
     if existing_user.is_some() {
-        tracing::warn!("user with email {} already exists", &user_data.email);
-        return ApiResponse::error(format!(
+        tracing::warn!("user with email {} already exists", Email(&user_data.email));
+        return Ok(ApiResponse::error(format!(
             "user with email ({}) already exists",
             &user_data.email
-        ));
+        )));
     }
 
+    let password_hash = hash_password(&user_data.password).await?;
+
     let new_user = ActiveModel {
         fullname: Set(user_data.fullname),
         gender: Set(user_data.gender),
         email: Set(user_data.email),
-        password_hash: Set(user_data.password_hash),
+        password_hash: Set(password_hash),
         ws_id: Set(user_data.ws_id),
+        // the admin vouches for the email, the user signs in right away
+        email_verified: Set(true),
         ..Default::default()
     };
 
@@ -150,19 +201,23 @@ pub(crate) async fn create(
                 user.id,
                 user.fullname
             );
-            ApiResponse::success("User created successfully!", Some(user))
+            Ok(ApiResponse::success(
+                "User created successfully!",
+                Some(user),
+            ))
         }
         Err(e) => {
             tracing::error!("error creating user: {:?}", e);
-            ApiResponse::error(format!("error creating user: {:?}", e))
+            Ok(ApiResponse::error(format!("error creating user: {:?}", e)))
         }
     }
 }
 
-/// query all users by id and name
+/// query all users of the caller's workspace by id and name
 #[tracing::instrument(name="query_all_by_id_or_name", skip(state), fields(UserQuery = %params))]
 pub(crate) async fn query_all_by_id_or_name(
     State(state): State<AppState>,
+    tenant: Tenant,
     BValidQuery(params): BValidQuery<UserQuery>,
 ) -> ApiResponse<Vec<Model>> {
     let db = state.db();
@@ -176,22 +231,24 @@ pub(crate) async fn query_all_by_id_or_name(
         conditions = conditions.add(users::Column::Fullname.eq(name));
     }
 
-    let users = Users::find()
+    let users = tenant
+        .find::<Users>()
         .filter(conditions)
         .order_by_desc(users::Column::CreateAt)
         .all(db)
         .await
         .unwrap();
-    tracing::info!("query users results: {:?}", users);
+    tracing::info!("query users results: {} users", users.len());
     ApiResponse::success("success", Some(users))
 }
 
 // #[debug_handler]
 pub async fn query_by_keyword(
-    State(AppState { db }): State<AppState>,
+    State(AppState { db, .. }): State<AppState>,
+    tenant: Tenant,
     BValidQuery(params): BValidQuery<UserQuery>, // apply validator
 ) -> ApiResponse<Page<Model>> {
-    let mut query = Users::find();
+    let mut query = tenant.find::<Users>();
 
     if let Some(keyword) = params.keyword.as_ref() {
         query = query.filter(
@@ -244,7 +301,9 @@ impl Display for CreateUserRequest {
         write!(
             f,
             "CreateUserRequest {{ fullname: {}, email: {}, password: ****, ws_id: {} }}",
-            self.fullname, self.email, self.ws_id
+            self.fullname,
+            Email(&self.email),
+            self.ws_id
         )
     }
 }
@@ -3,7 +3,9 @@ use crate::entity::prelude::Workspace;
 use crate::entity::workspace;
 use crate::entity::workspace::ActiveModel;
 use crate::entity::workspace::Model;
-use crate::response::ApiResponse;
+use crate::error::ApiError;
+use crate::response::{ApiResponse, ApiResult};
+use crate::tenant::Tenant;
 use axum::extract::State;
 use axum::Json;
 use sea_orm::ColumnTrait;
@@ -17,28 +19,40 @@ pub struct CreateWorkspaceRequest {
     owner_id: u64,
 }
 
-/// create workspace
+/// create workspace, owned by the caller
 #[tracing::instrument(name="create_workspace", skip(state), fields(workspace_data = %workspace_data))]
 pub(crate) async fn create_workspace(
     State(state): State<AppState>,
+    tenant: Tenant,
     Json(workspace_data): Json<CreateWorkspaceRequest>,
-) -> ApiResponse<Model> {
+) -> ApiResult<Model> {
+    if workspace_data.owner_id as i64 != tenant.user_id {
+        tracing::warn!(
+            "user {} tried to create a workspace for user {}",
+            tenant.user_id,
+            workspace_data.owner_id
+        );
+        return Err(ApiError::ForbiddenError(
+            "workspaces can only be created for yourself".to_string(),
+        ));
+    }
+
     let db = state.db();
 
+    // workspace names are unique across all tenants
     let name_exists = Workspace::find()
         .filter(workspace::Column::Name.eq(&workspace_data.name))
         .one(db)
-        .await
-        .unwrap();
+        .await?;
     if name_exists.is_some() {
         tracing::warn!(
             "workspace with name {} already exists",
             &workspace_data.name
         );
-        return ApiResponse::error(format!(
+        return Ok(ApiResponse::error(format!(
             "workspace with name ({}) already exists",
             &workspace_data.name
-        ));
+        )));
     }
 
     let new_workspace = ActiveModel {
@@ -55,11 +69,17 @@ pub(crate) async fn create_workspace(
                 workspace.id,
                 workspace.name
             );
-            ApiResponse::success("Workspace created successfully!", Some(workspace))
+            Ok(ApiResponse::success(
+                "Workspace created successfully!",
+                Some(workspace),
+            ))
         }
         Err(e) => {
             tracing::error!("error creating workspace: {:?}", e);
-            ApiResponse::error(format!("error creating workspace: {:?}", e))
+            Ok(ApiResponse::error(format!(
+                "error creating workspace: {:?}",
+                e
+            )))
         }
     }
 }
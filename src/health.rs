@@ -0,0 +1,173 @@
+use async_trait::async_trait;
+use sea_orm::DatabaseConnection;
+use serde::Serialize;
+use std::collections::BTreeMap;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+use tokio::task::JoinSet;
+
+/// Time a single check may take before it is reported as down.
+const CHECK_TIMEOUT: Duration = Duration::from_secs(2);
+
+/// A dependency the server needs to handle requests, probed by `/health/ready`.
+///
+/// Subsystems register their checks with `AppState::register_health_check`.
+#[async_trait]
+pub trait HealthCheck: Send + Sync {
+    /// Name of the check in the readiness report.
+    fn name(&self) -> &str;
+
+    /// Fails if the dependency is unusable; may return details for the report.
+    async fn check(&self) -> anyhow::Result<Option<serde_json::Value>>;
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum HealthStatus {
+    Up,
+    Down,
+}
+
+/// Outcome of a single check.
+#[derive(Debug, Clone, Serialize)]
+pub struct CheckReport {
+    pub status: HealthStatus,
+    pub latency_ms: f64,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub details: Option<serde_json::Value>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+}
+
+/// Outcome of all checks, `up` only if every check is up.
+#[derive(Debug, Clone, Serialize)]
+pub struct HealthReport {
+    pub status: HealthStatus,
+    pub checks: BTreeMap<String, CheckReport>,
+}
+
+/// The registered health checks.
+#[derive(Clone, Default)]
+pub struct HealthChecks {
+    checks: Vec<Arc<dyn HealthCheck>>,
+}
+
+impl HealthChecks {
+    /// The checks of the database connection.
+    pub fn new(db: &DatabaseConnection) -> Self {
+        let mut checks = Self::default();
+        checks.register(Arc::new(DatabaseCheck(db.clone())));
+        if let DatabaseConnection::SqlxPostgresPoolConnection(_) = db {
+            checks.register(Arc::new(PoolCheck(db.clone())));
+        }
+        checks
+    }
+
+    pub fn register(&mut self, check: Arc<dyn HealthCheck>) {
+        self.checks.push(check);
+    }
+
+    /// Runs every check concurrently, each within `CHECK_TIMEOUT`.
+    pub async fn run(&self) -> HealthReport {
+        let mut running = JoinSet::new();
+        for check in &self.checks {
+            let check = check.clone();
+            running.spawn(async move {
+                let started = Instant::now();
+                let result = tokio::time::timeout(CHECK_TIMEOUT, check.check()).await;
+                let latency_ms = started.elapsed().as_secs_f64() * 1000.0;
+
+                let report = match result {
+                    Ok(Ok(details)) => CheckReport {
+                        status: HealthStatus::Up,
+                        latency_ms,
+                        details,
+                        error: None,
+                    },
+                    Ok(Err(e)) => CheckReport {
+                        status: HealthStatus::Down,
+                        latency_ms,
+                        details: None,
+                        error: Some(format!("{:#}", e)),
+                    },
+                    Err(_) => CheckReport {
+                        status: HealthStatus::Down,
+                        latency_ms,
+                        details: None,
+                        error: Some(format!("timed out after {:?}", CHECK_TIMEOUT)),
+                    },
+                };
+                (check.name().to_string(), report)
+            });
+        }
+
+        let mut checks = BTreeMap::new();
+        while let Some(result) = running.join_next().await {
+            match result {
+                Ok((name, report)) => {
+                    if report.status == HealthStatus::Down {
+                        tracing::warn!("health check {} is down: {:?}", name, report.error);
+                    }
+                    checks.insert(name, report);
+                }
+                Err(e) => tracing::error!("health check panicked: {:?}", e),
+            }
+        }
+
+        let all_up = checks.len() == self.checks.len()
+            && checks
+                .values()
+                .all(|report| report.status == HealthStatus::Up);
+        HealthReport {
+            status: if all_up {
+                HealthStatus::Up
+            } else {
+                HealthStatus::Down
+            },
+            checks,
+        }
+    }
+}
+
+/// Round trip to the database.
+struct DatabaseCheck(DatabaseConnection);
+
+#[async_trait]
+impl HealthCheck for DatabaseCheck {
+    fn name(&self) -> &str {
+        "database"
+    }
+
+    async fn check(&self) -> anyhow::Result<Option<serde_json::Value>> {
+        self.0.ping().await?;
+        Ok(None)
+    }
+}
+
+/// Down when every connection of the pool is in use, requests would queue for one.
+struct PoolCheck(DatabaseConnection);
+
+#[async_trait]
+impl HealthCheck for PoolCheck {
+    fn name(&self) -> &str {
+        "database_pool"
+    }
+
+    async fn check(&self) -> anyhow::Result<Option<serde_json::Value>> {
+        let pool = self.0.get_postgres_connection_pool();
+        let size = pool.size();
+        let idle = pool.num_idle() as u32;
+        let max = pool.options().get_max_connections();
+        let in_use = size.saturating_sub(idle);
+
+        if in_use >= max {
+            anyhow::bail!("all {} connections are in use", max);
+        }
+        Ok(Some(serde_json::json!({
+            "size": size,
+            "idle": idle,
+            "in_use": in_use,
+            "max": max,
+        })))
+    }
+}
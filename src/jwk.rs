@@ -0,0 +1,191 @@
+use crate::config::jwt::JwtKeyConfig;
+use anyhow::{bail, Context};
+use base64::engine::general_purpose::URL_SAFE_NO_PAD;
+use base64::Engine;
+use jsonwebtoken::jwk::{
+    AlgorithmParameters, CommonParameters, EllipticCurve, EllipticCurveKeyParameters,
+    EllipticCurveKeyType, Jwk, KeyAlgorithm, OctetKeyPairParameters, OctetKeyPairType,
+    PublicKeyUse, RSAKeyParameters, RSAKeyType,
+};
+use jsonwebtoken::{Algorithm, DecodingKey, EncodingKey};
+use p256::elliptic_curve::sec1::ToEncodedPoint;
+use rsa::pkcs1::DecodeRsaPublicKey;
+use rsa::pkcs8::DecodePublicKey;
+use rsa::traits::PublicKeyParts;
+
+/// Key material for one asymmetric key, identified by its `kid`.
+pub struct KeyPair {
+    pub kid: String,
+    pub algorithm: Algorithm,
+    /// Present only for keys that can sign new tokens.
+    pub encoding: Option<EncodingKey>,
+    pub decoding: DecodingKey,
+    /// Public key as published in the JWKS document.
+    pub jwk: Jwk,
+}
+
+impl KeyPair {
+    /// Loads the key files referenced by the configuration.
+    pub fn load(config: &JwtKeyConfig, default_algorithm: Algorithm) -> anyhow::Result<Self> {
+        let algorithm = match config.algorithm() {
+            Some(name) => parse_algorithm(name)?,
+            None => default_algorithm,
+        };
+        let public_pem = std::fs::read(config.public_key())
+            .with_context(|| format!("Failed to read public key {}", config.public_key()))?;
+        let private_pem = config
+            .private_key()
+            .map(|path| {
+                std::fs::read(path).with_context(|| format!("Failed to read private key {}", path))
+            })
+            .transpose()?;
+
+        Self::from_pem(config.kid(), algorithm, private_pem.as_deref(), &public_pem)
+            .with_context(|| format!("Invalid JWT key {}", config.kid()))
+    }
+
+    /// Builds the key pair from PEM encoded keys.
+    ///
+    /// Private keys must be PKCS#8 encoded, public keys SPKI encoded (PKCS#1 is also accepted for RSA).
+    pub fn from_pem(
+        kid: &str,
+        algorithm: Algorithm,
+        private_pem: Option<&[u8]>,
+        public_pem: &[u8],
+    ) -> anyhow::Result<Self> {
+        let public_str = std::str::from_utf8(public_pem).context("Public key is not valid PEM")?;
+
+        let (decoding, parameters) = match algorithm {
+            Algorithm::RS256 => {
+                let key = rsa::RsaPublicKey::from_public_key_pem(public_str)
+                    .or_else(|_| rsa::RsaPublicKey::from_pkcs1_pem(public_str))
+                    .context("Invalid RSA public key")?;
+                let parameters = AlgorithmParameters::RSA(RSAKeyParameters {
+                    key_type: RSAKeyType::RSA,
+                    n: URL_SAFE_NO_PAD.encode(key.n().to_bytes_be()),
+                    e: URL_SAFE_NO_PAD.encode(key.e().to_bytes_be()),
+                });
+                (DecodingKey::from_rsa_pem(public_pem)?, parameters)
+            }
+            Algorithm::ES256 => {
+                let key = p256::PublicKey::from_public_key_pem(public_str)
+                    .context("Invalid P-256 public key")?;
+                let point = key.to_encoded_point(false);
+                let (Some(x), Some(y)) = (point.x(), point.y()) else {
+                    bail!("P-256 public key has no affine coordinates");
+                };
+                let parameters = AlgorithmParameters::EllipticCurve(EllipticCurveKeyParameters {
+                    key_type: EllipticCurveKeyType::EC,
+                    curve: EllipticCurve::P256,
+                    x: URL_SAFE_NO_PAD.encode(x),
+                    y: URL_SAFE_NO_PAD.encode(y),
+                });
+                (DecodingKey::from_ec_pem(public_pem)?, parameters)
+            }
+            Algorithm::EdDSA => {
+                let key = ed25519_dalek::VerifyingKey::from_public_key_pem(public_str)
+                    .context("Invalid Ed25519 public key")?;
+                let parameters = AlgorithmParameters::OctetKeyPair(OctetKeyPairParameters {
+                    key_type: OctetKeyPairType::OctetKeyPair,
+                    curve: EllipticCurve::Ed25519,
+                    x: URL_SAFE_NO_PAD.encode(key.to_bytes()),
+                });
+                (DecodingKey::from_ed_pem(public_pem)?, parameters)
+            }
+            other => bail!("{:?} is not an asymmetric algorithm", other),
+        };
+
+        let encoding = private_pem
+            .map(|pem| match algorithm {
+                Algorithm::RS256 => EncodingKey::from_rsa_pem(pem),
+                Algorithm::ES256 => EncodingKey::from_ec_pem(pem),
+                _ => EncodingKey::from_ed_pem(pem),
+            })
+            .transpose()
+            .context("Invalid private key")?;
+
+        let jwk = Jwk {
+            common: CommonParameters {
+                public_key_use: Some(PublicKeyUse::Signature),
+                key_algorithm: Some(key_algorithm(algorithm)),
+                key_id: Some(kid.to_string()),
+                ..Default::default()
+            },
+            algorithm: parameters,
+        };
+
+        Ok(Self {
+            kid: kid.to_string(),
+            algorithm,
+            encoding,
+            decoding,
+            jwk,
+        })
+    }
+}
+
+/// Parses one of the supported signing algorithms.
+pub fn parse_algorithm(name: &str) -> anyhow::Result<Algorithm> {
+    match name {
+        "HS256" => Ok(Algorithm::HS256),
+        "RS256" => Ok(Algorithm::RS256),
+        "ES256" => Ok(Algorithm::ES256),
+        "EdDSA" => Ok(Algorithm::EdDSA),
+        other => bail!(
+            "Unsupported JWT algorithm: {}, expected one of HS256, RS256, ES256, EdDSA",
+            other
+        ),
+    }
+}
+
+fn key_algorithm(algorithm: Algorithm) -> KeyAlgorithm {
+    match algorithm {
+        Algorithm::RS256 => KeyAlgorithm::RS256,
+        Algorithm::ES256 => KeyAlgorithm::ES256,
+        _ => KeyAlgorithm::EdDSA,
+    }
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use super::*;
+
+    pub(crate) const RSA_PRIVATE: &[u8] = include_bytes!("../tests/fixtures/jwt/rsa.pem");
+    pub(crate) const RSA_PUBLIC: &[u8] = include_bytes!("../tests/fixtures/jwt/rsa.pub.pem");
+    pub(crate) const EC_PRIVATE: &[u8] = include_bytes!("../tests/fixtures/jwt/ec.pem");
+    pub(crate) const EC_PUBLIC: &[u8] = include_bytes!("../tests/fixtures/jwt/ec.pub.pem");
+    pub(crate) const ED_PRIVATE: &[u8] = include_bytes!("../tests/fixtures/jwt/ed.pem");
+    pub(crate) const ED_PUBLIC: &[u8] = include_bytes!("../tests/fixtures/jwt/ed.pub.pem");
+
+    #[test]
+    fn test_public_jwk_matches_the_decoding_key() {
+        for (algorithm, private, public) in [
+            (Algorithm::RS256, RSA_PRIVATE, RSA_PUBLIC),
+            (Algorithm::ES256, EC_PRIVATE, EC_PUBLIC),
+            (Algorithm::EdDSA, ED_PRIVATE, ED_PUBLIC),
+        ] {
+            let pair = KeyPair::from_pem("k1", algorithm, Some(private), public).unwrap();
+            assert!(pair.encoding.is_some());
+            assert_eq!(pair.jwk.common.key_id.as_deref(), Some("k1"));
+
+            // a key rebuilt from the published JWK must verify what the pair signs
+            let token = jsonwebtoken::encode(
+                &jsonwebtoken::Header::new(algorithm),
+                &serde_json::json!({ "sub": "1" }),
+                pair.encoding.as_ref().unwrap(),
+            )
+            .unwrap();
+            let mut validation = jsonwebtoken::Validation::new(algorithm);
+            validation.required_spec_claims.clear();
+            validation.validate_exp = false;
+            let from_jwk = DecodingKey::from_jwk(&pair.jwk).unwrap();
+            jsonwebtoken::decode::<serde_json::Value>(&token, &from_jwk, &validation).unwrap();
+        }
+    }
+
+    #[test]
+    fn test_rejects_unsupported_algorithms() {
+        assert!(parse_algorithm("none").is_err());
+        assert!(KeyPair::from_pem("k1", Algorithm::HS256, None, RSA_PUBLIC).is_err());
+    }
+}
@@ -1,18 +1,36 @@
 use sea_orm::DatabaseConnection;
 
 pub mod api;
+pub mod api_key;
 pub mod application;
-mod auth;
+pub mod auth;
 pub mod common;
 pub mod config;
+pub mod cors;
 pub mod database;
 pub mod entity;
 pub mod error;
 pub(crate) mod handlers;
+pub mod health;
+mod jwk;
+pub mod limits;
 pub mod logger;
+pub mod login_guard;
+pub mod mailer;
+pub mod metrics;
+pub mod mfa;
 pub mod middleware;
+pub mod oidc;
+pub mod password;
+pub mod redact;
 pub mod request;
+pub mod request_id;
 pub mod response;
+pub mod revocation;
+pub mod session;
+pub mod shutdown;
+pub mod telemetry;
+pub mod tenant;
 
 /// initialize all settings for logger and database
 pub async fn init_all_settings() -> anyhow::Result<DatabaseConnection> {
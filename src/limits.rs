@@ -0,0 +1,106 @@
+use crate::error::ApiError;
+use axum::extract::{DefaultBodyLimit, Request, State};
+use axum::middleware::Next;
+use axum::response::{IntoResponse, Response};
+use bytesize::ByteSize;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use tokio::time::Instant;
+
+/// Deadline of the request, shared by all the timeouts it passes through.
+#[derive(Debug, Clone)]
+struct Deadline(Arc<Mutex<Instant>>);
+
+/// Answers `408 Request Timeout` with the `ApiResponse` envelope when the request is not
+/// handled within the duration given as state, e.g.
+/// `from_fn_with_state(Duration::from_secs(5), limits::timeout)`.
+///
+/// The timeout closest to the route wins: a route group may shorten, but also extend, the
+/// timeout of the whole application.
+pub async fn timeout(State(limit): State<Duration>, mut request: Request, next: Next) -> Response {
+    let deadline = Instant::now() + limit;
+    let shared = match request.extensions().get::<Deadline>() {
+        Some(shared) => {
+            *shared.0.lock().unwrap() = deadline;
+            shared.clone()
+        }
+        None => {
+            let shared = Deadline(Arc::new(Mutex::new(deadline)));
+            request.extensions_mut().insert(shared.clone());
+            shared
+        }
+    };
+
+    let response = next.run(request);
+    tokio::pin!(response);
+    loop {
+        let deadline = *shared.0.lock().unwrap();
+        tokio::select! {
+            response = &mut response => return response,
+            _ = tokio::time::sleep_until(deadline) => {
+                // an inner timeout may have moved the deadline while this one slept
+                if *shared.0.lock().unwrap() <= Instant::now() {
+                    tracing::warn!("request timed out after {:?}", limit);
+                    return ApiError::RequestTimeoutError.into_response();
+                }
+            }
+        }
+    }
+}
+
+/// Limits request bodies to `limit`, overriding the limit of enclosing routers.
+///
+/// Extractors reject larger bodies with `413 Payload Too Large`.
+pub fn body_limit(limit: ByteSize) -> DefaultBodyLimit {
+    DefaultBodyLimit::max(limit.as_u64() as usize)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use axum::body::Body;
+    use axum::middleware::from_fn_with_state;
+    use axum::routing::get;
+    use axum::Router;
+    use http::StatusCode;
+    use tower::ServiceExt;
+
+    async fn slow() -> &'static str {
+        tokio::time::sleep(Duration::from_millis(100)).await;
+        "done"
+    }
+
+    fn app() -> Router {
+        let uploads = Router::new()
+            .route("/upload", get(slow))
+            .layer(from_fn_with_state(Duration::from_millis(500), timeout));
+        let login = Router::new()
+            .route("/login", get(|| async { "done" }))
+            .layer(from_fn_with_state(Duration::from_millis(10), timeout));
+
+        Router::new()
+            .route("/report", get(slow))
+            .merge(uploads)
+            .merge(login)
+            .layer(from_fn_with_state(Duration::from_millis(50), timeout))
+    }
+
+    async fn call(path: &str) -> Response {
+        let request = Request::get(path).body(Body::empty()).unwrap();
+        app().oneshot(request).await.unwrap()
+    }
+
+    #[tokio::test]
+    async fn test_route_groups_override_the_timeout() {
+        let response = call("/report").await;
+        assert_eq!(response.status(), StatusCode::REQUEST_TIMEOUT);
+        let body = axum::body::to_bytes(response.into_body(), usize::MAX)
+            .await
+            .unwrap();
+        let body: serde_json::Value = serde_json::from_slice(&body).unwrap();
+        assert_eq!(body["code"], 0);
+
+        assert_eq!(call("/upload").await.status(), StatusCode::OK);
+        assert_eq!(call("/login").await.status(), StatusCode::OK);
+    }
+}
@@ -1,19 +1,134 @@
+use crate::config::logging::{LogFormat, LogOutput};
+use crate::config::AppConfig;
+use crate::redact::Scrubbing;
+use crate::telemetry;
+use anyhow::Context;
+use opentelemetry_sdk::trace::SdkTracerProvider;
+use tracing_appender::non_blocking::WorkerGuard;
+use tracing_appender::rolling::{RollingFileAppender, Rotation};
+use tracing_subscriber::fmt::writer::BoxMakeWriter;
 use tracing_subscriber::layer::SubscriberExt;
 use tracing_subscriber::util::SubscriberInitExt;
-use tracing_subscriber::EnvFilter;
+use tracing_subscriber::{reload, EnvFilter, Layer, Registry};
+
+/// Handle changing the log levels of the running server.
+///
+/// The default handle is detached from any subscriber and can not change anything.
+#[derive(Debug, Clone, Default)]
+pub struct LogFilter(Option<reload::Handle<EnvFilter, Registry>>);
+
+impl LogFilter {
+    /// Wraps `filter` into a layer whose directives the returned handle can replace.
+    pub fn new(filter: EnvFilter) -> (reload::Layer<EnvFilter, Registry>, Self) {
+        let (layer, handle) = reload::Layer::new(filter);
+        (layer, Self(Some(handle)))
+    }
+
+    /// Returns the directives in effect, e.g. `info,sea_orm=warn`.
+    pub fn current(&self) -> Option<String> {
+        self.0
+            .as_ref()?
+            .with_current(|filter| filter.to_string())
+            .ok()
+    }
+
+    /// Replaces the directives, effective for every following log line.
+    pub fn set(&self, filter: EnvFilter) -> anyhow::Result<()> {
+        self.0
+            .as_ref()
+            .context("The log filter is not installed")?
+            .reload(filter)
+            .context("Failed to change the log filter")
+    }
+}
+
+/// The installed logging pipeline, kept until the server stopped.
+pub struct Logger {
+    filter: LogFilter,
+    tracer_provider: Option<SdkTracerProvider>,
+    /// Writes the buffered lines to the log file when dropped.
+    _file_writer: Option<WorkerGuard>,
+}
+
+impl Logger {
+    /// Returns the handle changing the log levels.
+    pub fn filter(&self) -> &LogFilter {
+        &self.filter
+    }
+
+    /// Exports the spans and writes the log lines still buffered.
+    pub async fn shutdown(self) {
+        if let Some(provider) = self.tracer_provider {
+            telemetry::shutdown(provider).await;
+        }
+    }
+}
 
 /// Initialize the global tracing subscriber.
+/// - Writes pretty or JSON lines to stdout or to daily rotated files, see `LoggingConfig`.
 /// - Displays file name, line number, thread ID, and thread name in log output.
-pub fn init() {
+/// - Filters by the levels of `RUST_LOG` if set, by the configured levels otherwise; the
+///   returned `Logger` can change them at runtime.
+/// - Masks tokens, password hashes, emails and the `logging.redacted_headers` in every line.
+/// - Exports the spans over OTLP when telemetry is enabled.
+pub fn init() -> anyhow::Result<Logger> {
+    let config = AppConfig::get();
+    let logging = config.logging();
+
+    let tracer_provider = telemetry::tracer_provider(config.telemetry())?;
+    if let Some(provider) = &tracer_provider {
+        telemetry::install(provider);
+    }
+
+    let filter = match EnvFilter::try_from_default_env() {
+        Ok(filter) => filter,
+        Err(_) => EnvFilter::builder()
+            .parse(logging.filter())
+            .context("Invalid logging.level or logging.modules")?,
+    };
+    let (filter_layer, filter) = LogFilter::new(filter);
+
+    let (writer, file_writer) = match logging.output() {
+        LogOutput::Stdout => (BoxMakeWriter::new(std::io::stdout), None),
+        LogOutput::File => {
+            let appender = RollingFileAppender::builder()
+                .rotation(Rotation::DAILY)
+                .filename_prefix(logging.file_prefix())
+                .max_log_files(logging.max_files())
+                .build(logging.dir())
+                .with_context(|| format!("Failed to open the log file in {}", logging.dir()))?;
+            let (writer, guard) = tracing_appender::non_blocking(appender);
+            (BoxMakeWriter::new(writer), Some(guard))
+        }
+    };
+
+    let fmt = tracing_subscriber::fmt::layer()
+        .with_writer(Scrubbing::new(writer))
+        .with_ansi(logging.output() == LogOutput::Stdout)
+        .with_file(true)
+        .with_line_number(true)
+        .with_thread_ids(true)
+        .with_thread_names(true)
+        .with_target(false);
+    let fmt = match logging.format() {
+        LogFormat::Pretty => fmt.boxed(),
+        // the fields of the enclosing spans, e.g. the request id, on every line
+        LogFormat::Json => fmt
+            .json()
+            .with_current_span(true)
+            .with_span_list(true)
+            .boxed(),
+    };
+
     tracing_subscriber::registry()
-        .with(EnvFilter::try_from_default_env().unwrap_or_else(|_| EnvFilter::new("info")))
-        .with(
-            tracing_subscriber::fmt::layer()
-                .with_file(true)
-                .with_line_number(true)
-                .with_thread_ids(true)
-                .with_thread_names(true)
-                .with_target(false),
-        )
+        .with(filter_layer)
+        .with(fmt)
+        .with(tracer_provider.as_ref().map(telemetry::layer))
         .init();
+
+    Ok(Logger {
+        filter,
+        tracer_provider,
+        _file_writer: file_writer,
+    })
 }
@@ -0,0 +1,298 @@
+use crate::config::login_guard::LoginGuardConfig;
+use crate::config::AppConfig;
+use anyhow::{bail, Context};
+use http::{HeaderMap, HeaderName};
+use std::collections::HashMap;
+use std::net::{IpAddr, SocketAddr};
+use std::sync::{Arc, Mutex, MutexGuard};
+use std::time::{Duration, Instant};
+
+/// Tracks failed logins per account and per client IP and locks them out with
+/// exponential backoff.
+///
+/// The counters are process-local, like `MemoryRevocationStore`.
+#[derive(Debug)]
+pub struct LoginGuard {
+    max_failures_per_account: u32,
+    max_failures_per_ip: u32,
+    lockout: Duration,
+    max_lockout: Duration,
+    window: Duration,
+    client_ip_header: Option<HeaderName>,
+    attempts: Mutex<HashMap<AttemptKey, Attempts>>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+enum AttemptKey {
+    Account(String),
+    Ip(IpAddr),
+}
+
+#[derive(Debug, Clone, Copy)]
+struct Attempts {
+    failures: u32,
+    last_failure: Instant,
+    locked_until: Option<Instant>,
+}
+
+impl LoginGuard {
+    /// Builds the guard, a `client_ip_header` that is not a valid header name is ignored,
+    /// `check_config` rejects it at startup.
+    pub fn new(config: &LoginGuardConfig) -> Self {
+        Self {
+            max_failures_per_account: config.max_failures_per_account(),
+            max_failures_per_ip: config.max_failures_per_ip(),
+            lockout: Duration::from_secs(config.lockout()),
+            max_lockout: Duration::from_secs(config.max_lockout()),
+            window: Duration::from_secs(config.window()),
+            client_ip_header: config
+                .client_ip_header()
+                .and_then(|header| header.parse().ok()),
+            attempts: Mutex::default(),
+        }
+    }
+
+    /// Returns the IP the failures of a request are counted against.
+    ///
+    /// The peer address of the connection, unless a `client_ip_header` is configured
+    /// and holds a valid address.
+    pub fn client_ip(&self, headers: &HeaderMap, peer: SocketAddr) -> IpAddr {
+        self.client_ip_header
+            .as_ref()
+            .and_then(|header| headers.get(header))
+            .and_then(|value| value.to_str().ok())
+            .and_then(|value| value.rsplit(',').next())
+            .and_then(|ip| ip.trim().parse().ok())
+            .unwrap_or_else(|| peer.ip())
+    }
+
+    /// Returns the remaining lockout if the account or the IP is locked.
+    pub fn check(&self, account: &str, ip: IpAddr) -> Result<(), Duration> {
+        self.check_at(account, ip, Instant::now())
+    }
+
+    /// Counts a failed attempt against both the account and the IP.
+    pub fn record_failure(&self, account: &str, ip: IpAddr) {
+        self.record_failure_at(account, ip, Instant::now())
+    }
+
+    /// Clears the failures of the account; the IP keeps its count, so one valid
+    /// login does not reset a spray over many accounts.
+    pub fn record_success(&self, account: &str) {
+        self.lock().remove(&AttemptKey::account(account));
+    }
+
+    /// Forgets counters that are neither locked nor within the window.
+    pub fn purge_stale(&self) -> usize {
+        let now = Instant::now();
+        let mut attempts = self.lock();
+        let before = attempts.len();
+        attempts.retain(|_, entry| !self.is_stale(entry, now));
+        before - attempts.len()
+    }
+
+    fn check_at(&self, account: &str, ip: IpAddr, now: Instant) -> Result<(), Duration> {
+        let attempts = self.lock();
+        let remaining = [AttemptKey::account(account), AttemptKey::Ip(ip)]
+            .iter()
+            .filter_map(|key| attempts.get(key)?.locked_until)
+            .filter_map(|locked_until| locked_until.checked_duration_since(now))
+            .max();
+
+        match remaining {
+            Some(remaining) if !remaining.is_zero() => Err(remaining),
+            _ => Ok(()),
+        }
+    }
+
+    fn record_failure_at(&self, account: &str, ip: IpAddr, now: Instant) {
+        let mut attempts = self.lock();
+        for (key, threshold) in [
+            (AttemptKey::account(account), self.max_failures_per_account),
+            (AttemptKey::Ip(ip), self.max_failures_per_ip),
+        ] {
+            let entry = attempts.entry(key.clone()).or_insert(Attempts {
+                failures: 0,
+                last_failure: now,
+                locked_until: None,
+            });
+            if self.is_stale(entry, now) {
+                entry.failures = 0;
+                entry.locked_until = None;
+            }
+
+            entry.failures += 1;
+            entry.last_failure = now;
+            if entry.failures >= threshold {
+                let lockout = self.lockout_for(entry.failures - threshold);
+                entry.locked_until = Some(now + lockout);
+                tracing::warn!(
+                    "login locked for {:?} after {} failures: {:?}",
+                    lockout,
+                    entry.failures,
+                    key
+                );
+            }
+        }
+    }
+
+    /// `lockout * 2^excess`, capped at `max_lockout`.
+    fn lockout_for(&self, excess: u32) -> Duration {
+        self.lockout
+            .checked_mul(2u32.saturating_pow(excess.min(31)))
+            .unwrap_or(self.max_lockout)
+            .min(self.max_lockout)
+    }
+
+    fn is_stale(&self, entry: &Attempts, now: Instant) -> bool {
+        let locked = entry.locked_until.is_some_and(|until| until > now);
+        !locked && now.duration_since(entry.last_failure) >= self.window
+    }
+
+    fn lock(&self) -> MutexGuard<'_, HashMap<AttemptKey, Attempts>> {
+        // the counters stay consistent even if a holder panicked
+        self.attempts
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner())
+    }
+}
+
+impl AttemptKey {
+    fn account(account: &str) -> Self {
+        Self::Account(account.trim().to_lowercase())
+    }
+}
+
+/// Rejects a login guard configuration that can not be enforced.
+pub fn check_config(config: &AppConfig) -> anyhow::Result<()> {
+    check_guard(config.login_guard())
+}
+
+fn check_guard(config: &LoginGuardConfig) -> anyhow::Result<()> {
+    if config.window() == 0 {
+        bail!("login_guard.window must be greater than 0");
+    }
+    if let Some(header) = config.client_ip_header() {
+        header
+            .parse::<HeaderName>()
+            .with_context(|| format!("Invalid login_guard.client_ip_header {:?}", header))?;
+    }
+
+    Ok(())
+}
+
+/// Spawns a background task that periodically forgets stale login counters.
+pub fn spawn_cleanup(guard: Arc<LoginGuard>, interval: Duration) {
+    tokio::spawn(async move {
+        let mut ticker = tokio::time::interval(interval);
+        loop {
+            ticker.tick().await;
+            let purged = guard.purge_stale();
+            if purged > 0 {
+                tracing::debug!("purged {} stale login counters", purged);
+            }
+        }
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn guard() -> LoginGuard {
+        let config: LoginGuardConfig = serde_json::from_value(serde_json::json!({
+            "max_failures_per_account": 3,
+            "max_failures_per_ip": 5,
+            "lockout": 60,
+            "max_lockout": 200,
+            "window": 900,
+        }))
+        .unwrap();
+        LoginGuard::new(&config)
+    }
+
+    #[test]
+    fn test_account_lockout_backs_off_exponentially() {
+        let guard = guard();
+        let ip: IpAddr = "10.0.0.1".parse().unwrap();
+        let now = Instant::now();
+
+        guard.record_failure_at("bobby@none.co", ip, now);
+        guard.record_failure_at("bobby@none.co", ip, now);
+        assert!(guard.check_at("bobby@none.co", ip, now).is_ok());
+
+        guard.record_failure_at("Bobby@none.co", ip, now);
+        assert_eq!(
+            guard.check_at("bobby@none.co", ip, now),
+            Err(Duration::from_secs(60))
+        );
+        // other accounts from the same IP are not locked yet
+        assert!(guard.check_at("alice@none.co", ip, now).is_ok());
+
+        guard.record_failure_at("bobby@none.co", ip, now);
+        assert_eq!(
+            guard.check_at("bobby@none.co", ip, now),
+            Err(Duration::from_secs(120))
+        );
+        guard.record_failure_at("bobby@none.co", ip, now);
+        assert_eq!(
+            guard.check_at("bobby@none.co", ip, now),
+            Err(Duration::from_secs(200))
+        );
+
+        // the IP reached its threshold with the fifth failure
+        assert!(guard.check_at("alice@none.co", ip, now).is_err());
+        let later = now + Duration::from_secs(201);
+        assert!(guard.check_at("bobby@none.co", ip, later).is_ok());
+    }
+
+    #[test]
+    fn test_success_and_window_reset_the_account() {
+        let guard = guard();
+        let ip: IpAddr = "10.0.0.1".parse().unwrap();
+        let now = Instant::now();
+
+        guard.record_failure_at("bobby@none.co", ip, now);
+        guard.record_failure_at("bobby@none.co", ip, now);
+        guard.record_success("bobby@none.co");
+        guard.record_failure_at("bobby@none.co", ip, now);
+        assert!(guard.check_at("bobby@none.co", ip, now).is_ok());
+
+        let later = now + Duration::from_secs(900);
+        guard.record_failure_at("bobby@none.co", ip, later);
+        guard.record_failure_at("bobby@none.co", ip, later);
+        assert!(guard.check_at("bobby@none.co", ip, later).is_ok());
+    }
+
+    #[test]
+    fn test_client_ip_header_is_only_used_when_configured() {
+        let peer = SocketAddr::from(([10, 0, 0, 1], 40000));
+        let mut headers = HeaderMap::new();
+        headers.insert("x-forwarded-for", "1.2.3.4, 192.168.0.7".parse().unwrap());
+
+        assert_eq!(guard().client_ip(&headers, peer), peer.ip());
+
+        let config: LoginGuardConfig =
+            serde_json::from_value(serde_json::json!({ "client_ip_header": "X-Forwarded-For" }))
+                .unwrap();
+        let behind_proxy = LoginGuard::new(&config);
+        // the address appended by the proxy, not the one the client sent
+        assert_eq!(
+            behind_proxy.client_ip(&headers, peer),
+            "192.168.0.7".parse::<IpAddr>().unwrap()
+        );
+        assert_eq!(behind_proxy.client_ip(&HeaderMap::new(), peer), peer.ip());
+    }
+
+    #[test]
+    fn test_unenforceable_config_is_refused() {
+        let config = |value| -> LoginGuardConfig { serde_json::from_value(value).unwrap() };
+
+        assert!(check_guard(&LoginGuardConfig::default()).is_ok());
+        assert!(check_guard(&config(serde_json::json!({ "window": 0 }))).is_err());
+        assert!(check_guard(&config(
+            serde_json::json!({ "client_ip_header": "X Forwarded" })
+        ))
+        .is_err());
+    }
+}
@@ -0,0 +1,158 @@
+use crate::config::account::AccountConfig;
+use crate::config::mail::{MailConfig, MailTransport};
+use crate::config::AppConfig;
+use crate::redact::Email;
+use anyhow::{bail, Context};
+use async_trait::async_trait;
+use std::path::PathBuf;
+use std::sync::Arc;
+
+/// A plain text mail.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Mail {
+    pub to: String,
+    pub subject: String,
+    pub body: String,
+}
+
+/// Delivers outgoing mails, e.g. verification and password reset links.
+#[async_trait]
+pub trait Mailer: Send + Sync {
+    async fn send(&self, mail: Mail) -> anyhow::Result<()>;
+}
+
+/// Writes mails to the log, for local development.
+#[derive(Debug)]
+pub struct LogMailer {
+    from: String,
+}
+
+/// Writes every mail as an `.eml` file, so tests and developers can pick up the links.
+#[derive(Debug)]
+pub struct FileMailer {
+    from: String,
+    dir: PathBuf,
+}
+
+impl LogMailer {
+    pub fn new(from: impl Into<String>) -> Self {
+        Self { from: from.into() }
+    }
+}
+
+#[async_trait]
+impl Mailer for LogMailer {
+    async fn send(&self, mail: Mail) -> anyhow::Result<()> {
+        tracing::info!(
+            from = %self.from,
+            to = %Email(&mail.to),
+            subject = %mail.subject,
+            "mail sent:\n{}",
+            mail.body
+        );
+        Ok(())
+    }
+}
+
+impl FileMailer {
+    pub fn new(from: impl Into<String>, dir: impl Into<PathBuf>) -> Self {
+        Self {
+            from: from.into(),
+            dir: dir.into(),
+        }
+    }
+}
+
+#[async_trait]
+impl Mailer for FileMailer {
+    async fn send(&self, mail: Mail) -> anyhow::Result<()> {
+        tokio::fs::create_dir_all(&self.dir)
+            .await
+            .with_context(|| format!("Failed to create mail directory {:?}", self.dir))?;
+
+        let path = self.dir.join(format!("{}.eml", xid::new()));
+        let content = format!(
+            "From: {}\r\nTo: {}\r\nSubject: {}\r\n\r\n{}\r\n",
+            self.from, mail.to, mail.subject, mail.body
+        );
+        tokio::fs::write(&path, content)
+            .await
+            .with_context(|| format!("Failed to write mail {:?}", path))?;
+
+        tracing::info!("mail to {} written to {:?}", mail.to, path);
+        Ok(())
+    }
+}
+
+/// Creates the mailer selected in the configuration.
+pub fn build_mailer(config: &MailConfig) -> Arc<dyn Mailer> {
+    match config.transport() {
+        MailTransport::Log => Arc::new(LogMailer::new(config.from())),
+        MailTransport::File => Arc::new(FileMailer::new(config.from(), config.dir())),
+    }
+}
+
+/// Validates the mail delivery before the server starts.
+///
+/// In prod, signup and password reset mails have to reach the users: the `log`
+/// transport is refused, and so are links to a page that is not served over https.
+pub fn check_config(config: &AppConfig) -> anyhow::Result<()> {
+    check_delivery(config.mail(), config.account(), config.is_prod())
+}
+
+fn check_delivery(mail: &MailConfig, account: &AccountConfig, is_prod: bool) -> anyhow::Result<()> {
+    if !is_prod {
+        return Ok(());
+    }
+    if mail.transport() == MailTransport::Log {
+        bail!("Refusing to start in prod with the log mail transport, configure mail.transport");
+    }
+    if !account.link_base_url().starts_with("https://") {
+        bail!("Refusing to start in prod with links to a non-https account.link_base_url");
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_prod_refuses_undeliverable_mails() {
+        let log: MailConfig = serde_json::from_value(serde_json::json!({})).unwrap();
+        let file: MailConfig =
+            serde_json::from_value(serde_json::json!({ "transport": "file" })).unwrap();
+        let local: AccountConfig = serde_json::from_value(serde_json::json!({})).unwrap();
+        let public: AccountConfig = serde_json::from_value(
+            serde_json::json!({ "link_base_url": "https://axum-template.com" }),
+        )
+        .unwrap();
+
+        assert!(check_delivery(&log, &local, false).is_ok());
+        assert!(check_delivery(&log, &public, true).is_err());
+        assert!(check_delivery(&file, &local, true).is_err());
+        assert!(check_delivery(&file, &public, true).is_ok());
+    }
+
+    #[tokio::test]
+    async fn test_file_mailer_writes_eml_files() {
+        let dir = std::env::temp_dir().join(format!("mails-{}", xid::new()));
+        let mailer = FileMailer::new("no-reply@none.co", &dir);
+
+        mailer
+            .send(Mail {
+                to: "bobby@none.co".to_string(),
+                subject: "Verify your email".to_string(),
+                body: "token: abc".to_string(),
+            })
+            .await
+            .unwrap();
+
+        let mut entries = std::fs::read_dir(&dir).unwrap();
+        let content = std::fs::read_to_string(entries.next().unwrap().unwrap().path()).unwrap();
+        assert!(content.contains("To: bobby@none.co"));
+        assert!(content.ends_with("token: abc\r\n"));
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+}
@@ -0,0 +1,273 @@
+use crate::database;
+use axum::extract::{MatchedPath, Request, State};
+use axum::middleware::Next;
+use axum::response::Response;
+use http::Method;
+use prometheus_client::collector::Collector;
+use prometheus_client::encoding::{DescriptorEncoder, EncodeLabelSet, EncodeMetric};
+use prometheus_client::metrics::counter::Counter;
+use prometheus_client::metrics::family::Family;
+use prometheus_client::metrics::gauge::{ConstGauge, Gauge};
+use prometheus_client::metrics::histogram::{exponential_buckets, Histogram};
+use prometheus_client::registry::Registry;
+use sea_orm::metric::Info;
+use sea_orm::DatabaseConnection;
+use std::sync::Arc;
+use std::time::Instant;
+
+/// Content type of the Prometheus exposition.
+pub const CONTENT_TYPE: &str = "application/openmetrics-text; version=1.0.0; charset=utf-8";
+
+/// Route label of requests no route matched, so unknown paths do not add series.
+const UNMATCHED_ROUTE: &str = "unmatched";
+
+/// Labels of a handled request.
+#[derive(Debug, Clone, Hash, PartialEq, Eq, EncodeLabelSet)]
+struct RequestLabels {
+    method: &'static str,
+    route: String,
+    status: u16,
+}
+
+/// Labels of a request being handled, its status is not known yet.
+#[derive(Debug, Clone, Hash, PartialEq, Eq, EncodeLabelSet)]
+struct RouteLabels {
+    method: &'static str,
+    route: String,
+}
+
+/// Labels of a database query.
+#[derive(Debug, Clone, Hash, PartialEq, Eq, EncodeLabelSet)]
+struct QueryLabels {
+    operation: &'static str,
+    failed: bool,
+}
+
+/// The metrics of the server, exposed in the Prometheus text format by `/metrics`.
+///
+/// HTTP requests are labeled by the route template, e.g. `/api/users/{id}`, rather than
+/// the path, so ids do not add series.
+#[derive(Debug)]
+pub struct Metrics {
+    registry: Registry,
+    requests: Family<RequestLabels, Counter>,
+    in_flight: Family<RouteLabels, Gauge>,
+    request_duration: Family<RequestLabels, Histogram>,
+    query_duration: Family<QueryLabels, Histogram>,
+}
+
+impl Metrics {
+    /// Creates the metrics, reading the pool usage of `db` on every scrape.
+    ///
+    /// Query durations are recorded once `database::instrument` hooked them up.
+    pub fn new(db: &DatabaseConnection) -> Self {
+        let mut metrics = Self::default();
+        if let DatabaseConnection::SqlxPostgresPoolConnection(_) = db {
+            metrics
+                .registry
+                .register_collector(Box::new(PoolCollector(db.clone())));
+        }
+        metrics
+    }
+
+    /// Records the duration of a finished query.
+    pub fn observe_query(&self, info: &Info<'_>) {
+        let labels = QueryLabels {
+            operation: database::operation(&info.statement.sql),
+            failed: info.failed,
+        };
+        self.query_duration
+            .get_or_create(&labels)
+            .observe(info.elapsed.as_secs_f64());
+    }
+
+    /// Encodes every metric in the Prometheus text format.
+    pub fn render(&self) -> String {
+        let mut buffer = String::new();
+        prometheus_client::encoding::text::encode(&mut buffer, &self.registry)
+            .expect("writing to a String does not fail");
+        buffer
+    }
+}
+
+impl Default for Metrics {
+    /// The metrics without any database instrumentation.
+    fn default() -> Self {
+        let requests = Family::<RequestLabels, Counter>::default();
+        let in_flight = Family::<RouteLabels, Gauge>::default();
+        let request_duration = Family::<RequestLabels, Histogram>::new_with_constructor(|| {
+            // 5 ms to 10 s
+            Histogram::new(exponential_buckets(0.005, 2.0, 12))
+        });
+        let query_duration = Family::<QueryLabels, Histogram>::new_with_constructor(|| {
+            // 1 ms to 2 s
+            Histogram::new(exponential_buckets(0.001, 2.0, 12))
+        });
+
+        let mut registry = Registry::default();
+        registry.register(
+            "http_requests",
+            "Number of handled HTTP requests",
+            requests.clone(),
+        );
+        registry.register(
+            "http_requests_in_flight",
+            "Number of HTTP requests being handled",
+            in_flight.clone(),
+        );
+        registry.register(
+            "http_request_duration_seconds",
+            "Time to handle an HTTP request",
+            request_duration.clone(),
+        );
+        registry.register(
+            "db_query_duration_seconds",
+            "Time to execute a database query",
+            query_duration.clone(),
+        );
+
+        Self {
+            registry,
+            requests,
+            in_flight,
+            request_duration,
+            query_duration,
+        }
+    }
+}
+
+/// Counts the requests and measures their latency, e.g.
+/// `from_fn_with_state(metrics, metrics::track)`.
+///
+/// Must be added with `Router::layer`, which runs after routing, to label the requests
+/// with their route template.
+pub async fn track(State(metrics): State<Arc<Metrics>>, request: Request, next: Next) -> Response {
+    let method = method_label(request.method());
+    let route = request
+        .extensions()
+        .get::<MatchedPath>()
+        .map(|path| path.as_str().to_string())
+        .unwrap_or_else(|| UNMATCHED_ROUTE.to_string());
+
+    let in_flight = metrics
+        .in_flight
+        .get_or_create(&RouteLabels {
+            method,
+            route: route.clone(),
+        })
+        .clone();
+    // decremented on drop, also when the client goes away before the response
+    let _in_flight = InFlight::start(in_flight);
+
+    let started = Instant::now();
+    let response = next.run(request).await;
+    let labels = RequestLabels {
+        method,
+        route,
+        status: response.status().as_u16(),
+    };
+    metrics.requests.get_or_create(&labels).inc();
+    metrics
+        .request_duration
+        .get_or_create(&labels)
+        .observe(started.elapsed().as_secs_f64());
+
+    response
+}
+
+/// A request counted by the in-flight gauge.
+struct InFlight(Gauge);
+
+impl InFlight {
+    fn start(gauge: Gauge) -> Self {
+        gauge.inc();
+        Self(gauge)
+    }
+}
+
+impl Drop for InFlight {
+    fn drop(&mut self) {
+        self.0.dec();
+    }
+}
+
+/// Standard methods are kept as is, any other is reported as `OTHER`.
+fn method_label(method: &Method) -> &'static str {
+    match *method {
+        Method::GET => "GET",
+        Method::POST => "POST",
+        Method::PUT => "PUT",
+        Method::DELETE => "DELETE",
+        Method::PATCH => "PATCH",
+        Method::HEAD => "HEAD",
+        Method::OPTIONS => "OPTIONS",
+        _ => "OTHER",
+    }
+}
+
+/// Reads the usage of the connection pool on every scrape.
+#[derive(Debug)]
+struct PoolCollector(DatabaseConnection);
+
+impl Collector for PoolCollector {
+    fn encode(&self, mut encoder: DescriptorEncoder) -> Result<(), std::fmt::Error> {
+        let pool = self.0.get_postgres_connection_pool();
+        let size = pool.size() as i64;
+        let idle = pool.num_idle() as i64;
+        let gauges = [
+            (
+                "db_pool_connections",
+                "Number of open database connections",
+                size,
+            ),
+            (
+                "db_pool_idle_connections",
+                "Number of idle database connections",
+                idle,
+            ),
+            (
+                "db_pool_max_connections",
+                "Largest number of database connections",
+                pool.options().get_max_connections() as i64,
+            ),
+        ];
+
+        for (name, help, value) in gauges {
+            let gauge = ConstGauge::new(value);
+            let metric = encoder.encode_descriptor(name, help, None, gauge.metric_type())?;
+            gauge.encode(metric)?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use sea_orm::{DbBackend, Statement};
+    use std::time::Duration;
+
+    fn observe(metrics: &Metrics, sql: &str, failed: bool) {
+        metrics.observe_query(&Info {
+            elapsed: Duration::from_millis(3),
+            statement: &Statement::from_string(DbBackend::Postgres, sql),
+            failed,
+        });
+    }
+
+    #[test]
+    fn test_queries_are_labeled_by_operation() {
+        let metrics = Metrics::default();
+        observe(&metrics, r#"SELECT "user"."id" FROM "user""#, false);
+        observe(&metrics, "insert into audit values (1)", true);
+        observe(&metrics, "WITH t AS (SELECT 1) SELECT * FROM t", false);
+
+        let rendered = metrics.render();
+        assert!(rendered
+            .contains(r#"db_query_duration_seconds_count{operation="SELECT",failed="false"} 1"#));
+        assert!(rendered
+            .contains(r#"db_query_duration_seconds_count{operation="INSERT",failed="true"} 1"#));
+        assert!(rendered
+            .contains(r#"db_query_duration_seconds_count{operation="OTHER",failed="false"} 1"#));
+    }
+}
@@ -0,0 +1,128 @@
+use rand::rngs::OsRng;
+use rand::{Rng, RngCore};
+use sha2::{Digest, Sha256};
+use totp_rs::{Algorithm, Secret, TOTP};
+
+/// Seconds a TOTP code is valid for, the default of authenticator apps.
+const STEP: u64 = 30;
+
+/// Steps before and after the current one that are still accepted, for clock drift.
+const SKEW: u64 = 1;
+
+/// Characters of recovery codes, without the easily confused `0`, `1`, `i`, `l` and `o`.
+const RECOVERY_CODE_ALPHABET: &[u8] = b"23456789abcdefghjkmnpqrstuvwxyz";
+
+/// Generates a 160 bit TOTP secret, base32 encoded as expected by authenticator apps.
+pub fn generate_secret() -> String {
+    let mut bytes = [0u8; 20];
+    OsRng.fill_bytes(&mut bytes);
+    Secret::Raw(bytes.to_vec()).to_encoded().to_string()
+}
+
+/// Builds the SHA1, 6 digit, 30 second TOTP of a base32 secret.
+pub fn totp(secret: &str, issuer: &str, account: &str) -> anyhow::Result<TOTP> {
+    let secret = Secret::Encoded(secret.to_string())
+        .to_bytes()
+        .map_err(|e| anyhow::anyhow!("Invalid TOTP secret: {:?}", e))?;
+
+    TOTP::new(
+        Algorithm::SHA1,
+        6,
+        SKEW as u8,
+        STEP,
+        secret,
+        Some(issuer.to_string()),
+        account.to_string(),
+    )
+    .map_err(|e| anyhow::anyhow!("Invalid TOTP parameters: {:?}", e))
+}
+
+/// Checks a code against the steps around `now` and returns the matching step.
+///
+/// Steps up to `last_used_step` are rejected, so a code can not be replayed within
+/// its validity.
+pub fn verify_code(totp: &TOTP, code: &str, last_used_step: u64, now: u64) -> Option<u64> {
+    let code = code.trim();
+    let current = now / STEP;
+
+    (current.saturating_sub(SKEW)..=current + SKEW)
+        .filter(|step| *step > last_used_step)
+        .find(|step| constant_time_eq(totp.generate(step * STEP).as_bytes(), code.as_bytes()))
+}
+
+/// Generates recovery codes formatted as `xxxxx-xxxxx`.
+pub fn generate_recovery_codes(count: usize) -> Vec<String> {
+    let mut rng = OsRng;
+    (0..count)
+        .map(|_| {
+            let chars: String = (0..10)
+                .map(|_| {
+                    RECOVERY_CODE_ALPHABET[rng.gen_range(0..RECOVERY_CODE_ALPHABET.len())] as char
+                })
+                .collect();
+            format!("{}-{}", &chars[..5], &chars[5..])
+        })
+        .collect()
+}
+
+/// Hex encoded sha256 of a recovery code, ignoring case and separators.
+pub fn hash_recovery_code(code: &str) -> String {
+    let normalized: String = code
+        .chars()
+        .filter(char::is_ascii_alphanumeric)
+        .map(|c| c.to_ascii_lowercase())
+        .collect();
+    format!("{:x}", Sha256::digest(normalized.as_bytes()))
+}
+
+fn constant_time_eq(a: &[u8], b: &[u8]) -> bool {
+    a.len() == b.len() && a.iter().zip(b).fold(0, |acc, (x, y)| acc | (x ^ y)) == 0
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_codes_are_accepted_once_around_the_current_step() {
+        let totp = totp(&generate_secret(), "axum-template", "bobby@none.co").unwrap();
+        let now = 1_700_000_000;
+        let code = totp.generate(now);
+
+        let step = verify_code(&totp, &code, 0, now).unwrap();
+        assert_eq!(step, now / STEP);
+        // a replay of the same code is refused
+        assert_eq!(verify_code(&totp, &code, step, now), None);
+        // the previous code is still accepted for clock drift, older ones are not
+        let previous = totp.generate(now - STEP);
+        assert!(verify_code(&totp, &previous, 0, now).is_some());
+        assert!(verify_code(&totp, &totp.generate(now - 3 * STEP), 0, now).is_none());
+        assert!(verify_code(&totp, "12345", 0, now).is_none());
+    }
+
+    #[test]
+    fn test_provisioning_uri_carries_issuer_and_account() {
+        let secret = generate_secret();
+        let totp = totp(&secret, "axum-template", "bobby@none.co").unwrap();
+        let uri = totp.get_url();
+
+        assert!(uri.starts_with("otpauth://totp/axum-template:bobby%40none.co?"));
+        assert!(uri.contains(&format!("secret={}", secret)));
+    }
+
+    #[test]
+    fn test_recovery_codes_hash_ignoring_format() {
+        let codes = generate_recovery_codes(10);
+        assert_eq!(codes.len(), 10);
+        assert!(codes
+            .iter()
+            .all(|code| code.len() == 11 && code.as_bytes()[5] == b'-'));
+
+        let code = &codes[0];
+        assert_eq!(
+            hash_recovery_code(code),
+            hash_recovery_code(&code.replace('-', "").to_uppercase())
+        );
+        assert_ne!(hash_recovery_code(code), hash_recovery_code(&codes[1]));
+    }
+}
@@ -1,5 +1,8 @@
-use crate::auth::{get_jwt, Jwt};
+use crate::api_key;
+use crate::application::AppState;
+use crate::auth::{get_jwt, Jwt, Principal};
 use crate::error::ApiError;
+use crate::redact::{JwtError, Token};
 use axum::body::Body;
 use http::{header, Request, Response};
 use std::future::Future;
@@ -10,6 +13,12 @@ use tower_http::auth::{AsyncAuthorizeRequest, AsyncRequireAuthorizationLayer};
 static AUTH_LAYER: LazyLock<AsyncRequireAuthorizationLayer<JWTAuth>> =
     LazyLock::new(|| AsyncRequireAuthorizationLayer::new(JWTAuth::new(get_jwt())));
 
+/// Authenticates `Authorization: Bearer` credentials, either an access token or an
+/// API key, and installs the `Principal` extension.
+///
+/// In cookie session mode, requests without the header are authenticated by the access
+/// token cookie instead, and state-changing ones must carry the CSRF token. Access
+/// tokens also install their `Claims`.
 #[derive(Clone)]
 pub struct JWTAuth {
     jwt: &'static Jwt,
@@ -37,8 +46,17 @@ impl AsyncAuthorizeRequest<Body> for JWTAuth {
     >;
 
     fn authorize(&mut self, mut request: Request<Self::RequestBody>) -> Self::Future {
-        Box::pin(async {
-            let token = request
+        let jwt = self.jwt;
+        Box::pin(async move {
+            let state = request
+                .extensions()
+                .get::<AppState>()
+                .cloned()
+                .ok_or_else(|| {
+                    ApiError::InternalError(anyhow::anyhow!("AppState extension is not installed"))
+                })?;
+
+            let bearer = request
                 .headers()
                 .get(header::AUTHORIZATION)
                 .map(|value| -> Result<_, ApiError> {
@@ -58,18 +76,140 @@ impl AsyncAuthorizeRequest<Body> for JWTAuth {
 
                     Ok(token_str)
                 })
-                .transpose()?
-                .ok_or_else(|| {
-                    ApiError::UnAuthenticatedError("Authorization header is not found!".to_string())
-                })?;
+                .transpose()?;
 
-            let principal = self.jwt.decode(token).map_err(|e| {
-                tracing::error!("JWT decode error, Invalid token!: {:?}", e);
-                ApiError::UnAuthenticatedError("Invalid token!".to_string())
+            // browsers send the cookie on their own, so it only counts with the CSRF token
+            let token = match (bearer, state.cookie_session()) {
+                (Some(token), _) => token,
+                (None, Some(session)) => {
+                    let token = session.access_token(request.headers()).ok_or_else(|| {
+                        ApiError::UnAuthenticatedError("Session cookie is not found!".to_string())
+                    })?;
+                    session.check_csrf(request.method(), request.headers())?;
+                    token
+                }
+                (None, None) => {
+                    return Err(ApiError::UnAuthenticatedError(
+                        "Authorization header is not found!".to_string(),
+                    )
+                    .into())
+                }
+            };
+
+            // API keys carry no claims, handlers that need them must cope with their absence
+            if api_key::is_api_key(token) {
+                let principal = api_key::authenticate(state.db(), token).await?;
+                request.extensions_mut().insert(principal);
+                return Ok(request);
+            }
+
+            let claims = jwt.decode_access(token).map_err(|e| {
+                tracing::error!(
+                    "JWT decode error, Invalid token {}: {}",
+                    Token(token),
+                    JwtError(&e)
+                );
+                ApiError::JWTError(e)
+            })?;
+            let principal = jwt.principal(&claims).map_err(|e| {
+                tracing::error!("JWT claims error, Invalid principal!: {}", JwtError(&e));
+                ApiError::JWTError(e)
             })?;
+
+            if state
+                .revocation()
+                .is_revoked(&claims.jti)
+                .await
+                .map_err(ApiError::InternalError)?
+            {
+                tracing::warn!("revoked token is used, jti: {}", claims.jti);
+                return Err(
+                    ApiError::UnAuthenticatedError("Token has been revoked!".to_string()).into(),
+                );
+            }
+
             request.extensions_mut().insert(principal);
+            request.extensions_mut().insert(claims);
 
             Ok(request)
         })
     }
 }
+
+/// Rejects requests whose principal lacks the given permission with `403 Forbidden`.
+///
+/// Must run after `JWTAuth`, which installs the `Principal` extension.
+#[derive(Clone, Copy)]
+pub struct RequirePermission(pub &'static str);
+
+/// Builds a route layer requiring `permission`, e.g. `require_permission("user:delete")`.
+pub fn require_permission(
+    permission: &'static str,
+) -> AsyncRequireAuthorizationLayer<RequirePermission> {
+    AsyncRequireAuthorizationLayer::new(RequirePermission(permission))
+}
+
+impl AsyncAuthorizeRequest<Body> for RequirePermission {
+    type RequestBody = Body;
+    type ResponseBody = Body;
+    type Future = Pin<
+        Box<
+            dyn Future<Output = Result<Request<Self::RequestBody>, Response<Self::ResponseBody>>>
+                + Send
+                + 'static,
+        >,
+    >;
+
+    fn authorize(&mut self, request: Request<Self::RequestBody>) -> Self::Future {
+        let permission = self.0;
+        Box::pin(async move {
+            let principal = request.extensions().get::<Principal>().ok_or_else(|| {
+                ApiError::UnAuthenticatedError("Authorization header is not found!".to_string())
+            })?;
+            if !principal.has_permission(permission) {
+                tracing::warn!("{} lacks permission {}", principal, permission);
+                return Err(ApiError::ForbiddenError(format!(
+                    "permission {} is required",
+                    permission
+                ))
+                .into());
+            }
+
+            Ok(request)
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use axum::routing::get;
+    use axum::Router;
+    use http::StatusCode;
+    use tower::ServiceExt;
+
+    async fn call(permissions: &[&str]) -> StatusCode {
+        let principal = Principal {
+            id: 1,
+            name: "Bobby".to_string(),
+            email: "bobby@none.co".to_string(),
+            ws_id: Some(1),
+            roles: vec!["member".to_string()],
+            permissions: permissions.iter().map(|p| p.to_string()).collect(),
+        };
+        let app = Router::new().route(
+            "/",
+            get(|| async {}).route_layer(require_permission("user:delete")),
+        );
+        let mut request = Request::get("/").body(Body::empty()).unwrap();
+        request.extensions_mut().insert(principal);
+
+        app.oneshot(request).await.unwrap().status()
+    }
+
+    #[tokio::test]
+    async fn test_require_permission() {
+        assert_eq!(call(&["user:delete"]).await, StatusCode::OK);
+        assert_eq!(call(&["user:read"]).await, StatusCode::FORBIDDEN);
+    }
+}
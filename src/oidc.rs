@@ -0,0 +1,321 @@
+use crate::config::oidc::{OidcConfig, OidcProviderConfig};
+use crate::telemetry;
+use anyhow::{bail, Context};
+use base64::engine::general_purpose::URL_SAFE_NO_PAD;
+use base64::Engine;
+use jsonwebtoken::jwk::JwkSet;
+use jsonwebtoken::{decode, decode_header, Algorithm, DecodingKey, Validation};
+use rand::RngCore;
+use serde::Deserialize;
+use sha2::{Digest, Sha256};
+use std::collections::BTreeMap;
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::sync::{OnceCell, RwLock};
+use url::Url;
+
+/// Timeout of every request to a provider.
+const HTTP_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// The configured identity providers, by name.
+#[derive(Debug, Default)]
+pub struct OidcProviders {
+    providers: BTreeMap<String, Arc<OidcProvider>>,
+}
+
+/// An OpenID Connect identity provider, logging users in with the authorization code
+/// flow and PKCE.
+///
+/// The discovery document is fetched on first use, the signing keys whenever an ID
+/// token is signed with a key that is not known yet.
+#[derive(Debug)]
+pub struct OidcProvider {
+    name: String,
+    config: OidcProviderConfig,
+    redirect_uri: String,
+    http: reqwest::Client,
+    metadata: OnceCell<ProviderMetadata>,
+    jwks: RwLock<Option<JwkSet>>,
+}
+
+/// The parts of the discovery document used by the login.
+#[derive(Debug, Clone, Deserialize)]
+struct ProviderMetadata {
+    issuer: String,
+    authorization_endpoint: String,
+    token_endpoint: String,
+    jwks_uri: String,
+}
+
+#[derive(Debug, Deserialize)]
+struct TokenResponse {
+    id_token: String,
+}
+
+/// Where to send the user, and what to keep until the provider redirects back.
+#[derive(Debug, Clone)]
+pub struct AuthorizationRequest {
+    pub url: String,
+    pub state: String,
+    pub nonce: String,
+    pub code_verifier: String,
+}
+
+/// The verified claims of an ID token.
+#[derive(Debug, Clone, Deserialize)]
+pub struct IdTokenClaims {
+    /// Identifier of the user at the provider, stable and never reassigned.
+    pub sub: String,
+    pub email: Option<String>,
+    #[serde(default)]
+    pub email_verified: bool,
+    pub name: Option<String>,
+    pub nonce: Option<String>,
+}
+
+impl OidcProviders {
+    /// Builds the providers of the configuration, rejecting unusable URLs.
+    pub fn from_config(config: &OidcConfig) -> anyhow::Result<Self> {
+        config
+            .providers()
+            .iter()
+            .map(|(name, provider)| OidcProvider::new(name, provider, config.redirect_base_url()))
+            .collect::<anyhow::Result<Vec<_>>>()
+            .map(Self::new)
+    }
+
+    pub fn new(providers: Vec<OidcProvider>) -> Self {
+        Self {
+            providers: providers
+                .into_iter()
+                .map(|provider| (provider.name.clone(), Arc::new(provider)))
+                .collect(),
+        }
+    }
+
+    /// Returns the provider with the given name.
+    pub fn get(&self, name: &str) -> Option<&Arc<OidcProvider>> {
+        self.providers.get(name)
+    }
+}
+
+impl OidcProvider {
+    /// Creates the provider `name`, called back at `{redirect_base_url}/auth/oidc/{name}/callback`.
+    pub fn new(
+        name: &str,
+        config: &OidcProviderConfig,
+        redirect_base_url: &str,
+    ) -> anyhow::Result<Self> {
+        Url::parse(config.issuer())
+            .with_context(|| format!("Invalid issuer of OIDC provider {}", name))?;
+        let redirect_uri = format!("{}/auth/oidc/{}/callback", redirect_base_url, name);
+        Url::parse(&redirect_uri)
+            .with_context(|| format!("Invalid redirect URI of OIDC provider {}", name))?;
+
+        Ok(Self {
+            name: name.to_string(),
+            config: config.clone(),
+            redirect_uri,
+            http: reqwest::Client::builder().timeout(HTTP_TIMEOUT).build()?,
+            metadata: OnceCell::new(),
+            jwks: RwLock::new(None),
+        })
+    }
+
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    /// Builds the URL of the provider's authorization endpoint with a fresh `state`,
+    /// `nonce` and PKCE code verifier.
+    pub async fn authorization_request(&self) -> anyhow::Result<AuthorizationRequest> {
+        let metadata = self.metadata().await?;
+        let state = random_string();
+        let nonce = random_string();
+        let code_verifier = random_string();
+
+        let mut url = Url::parse(&metadata.authorization_endpoint)
+            .context("Invalid authorization endpoint")?;
+        url.query_pairs_mut()
+            .append_pair("response_type", "code")
+            .append_pair("client_id", self.config.client_id())
+            .append_pair("redirect_uri", &self.redirect_uri)
+            .append_pair("scope", &self.config.scopes())
+            .append_pair("state", &state)
+            .append_pair("nonce", &nonce)
+            .append_pair("code_challenge", &code_challenge(&code_verifier))
+            .append_pair("code_challenge_method", "S256");
+
+        Ok(AuthorizationRequest {
+            url: url.into(),
+            state,
+            nonce,
+            code_verifier,
+        })
+    }
+
+    /// Exchanges the authorization code for an ID token and verifies it.
+    pub async fn exchange_code(
+        &self,
+        code: &str,
+        code_verifier: &str,
+        nonce: &str,
+    ) -> anyhow::Result<IdTokenClaims> {
+        let metadata = self.metadata().await?;
+
+        let mut form = vec![
+            ("grant_type", "authorization_code"),
+            ("code", code),
+            ("redirect_uri", &self.redirect_uri),
+            ("client_id", self.config.client_id()),
+            ("code_verifier", code_verifier),
+        ];
+        if let Some(client_secret) = self.config.client_secret() {
+            form.push(("client_secret", client_secret));
+        }
+
+        let response = self
+            .http
+            .post(&metadata.token_endpoint)
+            .headers(telemetry::trace_headers())
+            .form(&form)
+            .send()
+            .await
+            .context("Token request failed")?;
+        if !response.status().is_success() {
+            let status = response.status();
+            let body = response.text().await.unwrap_or_default();
+            bail!("Token endpoint answered {}: {}", status, body);
+        }
+        let tokens: TokenResponse = response.json().await.context("Invalid token response")?;
+
+        let claims = self.verify_id_token(&tokens.id_token).await?;
+        if claims.nonce.as_deref() != Some(nonce) {
+            bail!("ID token nonce does not match");
+        }
+
+        Ok(claims)
+    }
+
+    /// Verifies signature, issuer, audience and expiration of an ID token.
+    async fn verify_id_token(&self, id_token: &str) -> anyhow::Result<IdTokenClaims> {
+        let metadata = self.metadata().await?;
+        let header = decode_header(id_token).context("Invalid ID token header")?;
+        if matches!(
+            header.alg,
+            Algorithm::HS256 | Algorithm::HS384 | Algorithm::HS512
+        ) {
+            bail!("ID token must be signed with an asymmetric key");
+        }
+
+        let key = self.decoding_key(header.kid.as_deref()).await?;
+        let mut validation = Validation::new(header.alg);
+        validation.set_issuer(&[&metadata.issuer]);
+        validation.set_audience(&[self.config.client_id()]);
+        validation.set_required_spec_claims(&["exp", "iss", "aud", "sub"]);
+
+        Ok(decode::<IdTokenClaims>(id_token, &key, &validation)
+            .context("Invalid ID token")?
+            .claims)
+    }
+
+    /// Finds the signing key, refreshing the cached keys once if it is unknown, e.g.
+    /// after the provider rotated its keys.
+    async fn decoding_key(&self, kid: Option<&str>) -> anyhow::Result<DecodingKey> {
+        if let Some(jwks) = self.jwks.read().await.as_ref() {
+            if let Some(key) = find_key(jwks, kid) {
+                return key;
+            }
+        }
+
+        let metadata = self.metadata().await?;
+        let jwks: JwkSet = self
+            .get_json(&metadata.jwks_uri)
+            .await
+            .context("Failed to fetch the provider's keys")?;
+        let key = find_key(&jwks, kid);
+        *self.jwks.write().await = Some(jwks);
+
+        key.unwrap_or_else(|| Err(anyhow::anyhow!("No signing key {:?} at the provider", kid)))
+    }
+
+    async fn metadata(&self) -> anyhow::Result<&ProviderMetadata> {
+        self.metadata
+            .get_or_try_init(|| async {
+                let url = format!("{}/.well-known/openid-configuration", self.config.issuer());
+                let metadata: ProviderMetadata = self
+                    .get_json(&url)
+                    .await
+                    .with_context(|| format!("Failed to discover OIDC provider {}", self.name))?;
+                if metadata.issuer.trim_end_matches('/') != self.config.issuer() {
+                    bail!(
+                        "OIDC provider {} announces issuer {}",
+                        self.name,
+                        metadata.issuer
+                    );
+                }
+
+                Ok(metadata)
+            })
+            .await
+    }
+
+    async fn get_json<T: serde::de::DeserializeOwned>(&self, url: &str) -> anyhow::Result<T> {
+        Ok(self
+            .http
+            .get(url)
+            .headers(telemetry::trace_headers())
+            .send()
+            .await?
+            .error_for_status()?
+            .json()
+            .await?)
+    }
+}
+
+fn find_key(jwks: &JwkSet, kid: Option<&str>) -> Option<anyhow::Result<DecodingKey>> {
+    let jwk = match kid {
+        Some(kid) => jwks.find(kid),
+        None => jwks.keys.first(),
+    }?;
+
+    Some(DecodingKey::from_jwk(jwk).context("Unsupported signing key"))
+}
+
+/// 32 random bytes, URL safe encoded.
+fn random_string() -> String {
+    let mut bytes = [0u8; 32];
+    rand::rngs::OsRng.fill_bytes(&mut bytes);
+    URL_SAFE_NO_PAD.encode(bytes)
+}
+
+/// The `S256` PKCE challenge of a code verifier.
+pub fn code_challenge(code_verifier: &str) -> String {
+    URL_SAFE_NO_PAD.encode(Sha256::digest(code_verifier.as_bytes()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_code_challenge_matches_rfc_7636() {
+        assert_eq!(
+            code_challenge("dBjftJeZ4CVP-mB92K27uhbUJU1p1r_wW1gFWFOEjXk"),
+            "E9Melhoa2OwvFrEMTJguCHaoeK1t8URWbuGJSstw-cM"
+        );
+    }
+
+    #[test]
+    fn test_providers_reject_invalid_urls() {
+        let config: OidcConfig = serde_json::from_value(serde_json::json!({
+            "providers": {
+                "acme": { "issuer": "not a url", "client_id": "app" },
+            },
+        }))
+        .unwrap();
+
+        assert!(OidcProviders::from_config(&config).is_err());
+        assert!(OidcProviders::from_config(&OidcConfig::default()).is_ok());
+    }
+}
@@ -0,0 +1,292 @@
+use crate::config::password::{PasswordAlgorithm, PasswordConfig};
+use crate::config::AppConfig;
+use anyhow::bail;
+use argon2::password_hash::rand_core::OsRng;
+use argon2::password_hash::{PasswordHash, PasswordVerifier, SaltString};
+use argon2::{Argon2, Params, Version};
+use std::ops::RangeInclusive;
+use std::sync::LazyLock;
+
+/// Cost factors supported by bcrypt.
+const BCRYPT_COSTS: RangeInclusive<u32> = 4..=31;
+
+static PASSWORD_HASHERS: LazyLock<PasswordHashers> = LazyLock::new(|| {
+    PasswordHashers::from_config(AppConfig::get().password())
+        .expect("Failed to initialize password hashing")
+});
+
+/// A password hashing algorithm.
+pub trait PasswordHasher: Send + Sync {
+    /// Returns `true` if `hash` was produced by this algorithm.
+    fn recognizes(&self, hash: &str) -> bool;
+
+    /// Hashes the password with a fresh salt.
+    fn hash(&self, password: &str) -> anyhow::Result<String>;
+
+    /// Verifies the password against a hash of this algorithm.
+    fn verify(&self, password: &str, hash: &str) -> anyhow::Result<bool>;
+
+    /// Returns `true` if `hash` was produced with other parameters than configured.
+    fn is_outdated(&self, hash: &str) -> bool;
+}
+
+/// Argon2id with configurable memory, iterations and parallelism.
+pub struct Argon2Hasher {
+    argon2: Argon2<'static>,
+}
+
+/// bcrypt, kept to verify hashes created before Argon2id became the default.
+pub struct BcryptHasher {
+    cost: u32,
+}
+
+/// Hashes new passwords with the configured algorithm and verifies hashes of every
+/// supported algorithm.
+pub struct PasswordHashers {
+    current: Box<dyn PasswordHasher>,
+    legacy: Vec<Box<dyn PasswordHasher>>,
+    /// Hash of the current algorithm verified when there is no real hash to check.
+    dummy_hash: String,
+}
+
+impl Argon2Hasher {
+    pub fn new(memory_kib: u32, iterations: u32, parallelism: u32) -> anyhow::Result<Self> {
+        let params = Params::new(memory_kib, iterations, parallelism, None)
+            .map_err(|e| anyhow::anyhow!("Invalid argon2 parameters: {}", e))?;
+
+        Ok(Self {
+            argon2: Argon2::new(argon2::Algorithm::Argon2id, Version::V0x13, params),
+        })
+    }
+}
+
+impl PasswordHasher for Argon2Hasher {
+    fn recognizes(&self, hash: &str) -> bool {
+        hash.starts_with("$argon2")
+    }
+
+    fn hash(&self, password: &str) -> anyhow::Result<String> {
+        use argon2::PasswordHasher as _;
+
+        let salt = SaltString::generate(&mut OsRng);
+        let hash = self
+            .argon2
+            .hash_password(password.as_bytes(), &salt)
+            .map_err(|e| anyhow::anyhow!("Failed to hash password: {}", e))?;
+
+        Ok(hash.to_string())
+    }
+
+    fn verify(&self, password: &str, hash: &str) -> anyhow::Result<bool> {
+        let parsed =
+            PasswordHash::new(hash).map_err(|e| anyhow::anyhow!("Invalid argon2 hash: {}", e))?;
+
+        // the parameters are read from the hash, so outdated hashes keep verifying
+        Ok(self
+            .argon2
+            .verify_password(password.as_bytes(), &parsed)
+            .is_ok())
+    }
+
+    fn is_outdated(&self, hash: &str) -> bool {
+        let Ok(parsed) = PasswordHash::new(hash) else {
+            return true;
+        };
+        let Ok(params) = Params::try_from(&parsed) else {
+            return true;
+        };
+        let current = self.argon2.params();
+
+        parsed.algorithm != argon2::Algorithm::Argon2id.ident()
+            || params.m_cost() != current.m_cost()
+            || params.t_cost() != current.t_cost()
+            || params.p_cost() != current.p_cost()
+    }
+}
+
+impl BcryptHasher {
+    pub fn new(cost: u32) -> anyhow::Result<Self> {
+        if !BCRYPT_COSTS.contains(&cost) {
+            bail!(
+                "Invalid bcrypt cost {}, must be between {} and {}",
+                cost,
+                BCRYPT_COSTS.start(),
+                BCRYPT_COSTS.end()
+            );
+        }
+
+        Ok(Self { cost })
+    }
+}
+
+impl PasswordHasher for BcryptHasher {
+    fn recognizes(&self, hash: &str) -> bool {
+        hash.starts_with("$2")
+    }
+
+    fn hash(&self, password: &str) -> anyhow::Result<String> {
+        Ok(bcrypt::hash(password, self.cost)?)
+    }
+
+    fn verify(&self, password: &str, hash: &str) -> anyhow::Result<bool> {
+        Ok(bcrypt::verify(password, hash)?)
+    }
+
+    fn is_outdated(&self, hash: &str) -> bool {
+        // `$2b$12$...`
+        hash.split('$').nth(2).and_then(|cost| cost.parse().ok()) != Some(self.cost)
+    }
+}
+
+impl PasswordHashers {
+    /// Builds the hashers, with the configured algorithm used for new hashes.
+    pub fn from_config(config: &PasswordConfig) -> anyhow::Result<Self> {
+        let argon2: Box<dyn PasswordHasher> = Box::new(Argon2Hasher::new(
+            config.argon2_memory_kib(),
+            config.argon2_iterations(),
+            config.argon2_parallelism(),
+        )?);
+        let bcrypt: Box<dyn PasswordHasher> = Box::new(BcryptHasher::new(config.bcrypt_cost())?);
+
+        match config.algorithm() {
+            PasswordAlgorithm::Argon2id => Self::new(argon2, vec![bcrypt]),
+            PasswordAlgorithm::Bcrypt => Self::new(bcrypt, vec![argon2]),
+        }
+    }
+
+    /// Fails if the current algorithm can not hash, e.g. with unusable parameters.
+    fn new(
+        current: Box<dyn PasswordHasher>,
+        legacy: Vec<Box<dyn PasswordHasher>>,
+    ) -> anyhow::Result<Self> {
+        let dummy_hash = current.hash(&xid::new().to_string())?;
+
+        Ok(Self {
+            current,
+            legacy,
+            dummy_hash,
+        })
+    }
+
+    /// Hashes the password with the current algorithm.
+    pub fn hash(&self, password: &str) -> anyhow::Result<String> {
+        self.current.hash(password)
+    }
+
+    /// Verifies the password with the algorithm the hash was produced by.
+    ///
+    /// Hashes of unknown algorithms, e.g. the empty hashes of seeded users, never match,
+    /// but still take as long as a real verification.
+    pub fn verify(&self, password: &str, hash: &str) -> anyhow::Result<bool> {
+        match self.hasher_of(hash) {
+            Some(hasher) => hasher.verify(password, hash),
+            None => {
+                self.verify_dummy(password);
+                Ok(false)
+            }
+        }
+    }
+
+    /// Spends the time of a real verification without any hash to check, so that
+    /// unknown accounts can not be told apart from wrong passwords by timing.
+    pub fn verify_dummy(&self, password: &str) {
+        if let Err(e) = self.current.verify(password, &self.dummy_hash) {
+            tracing::error!("error verifying the dummy password hash: {:?}", e);
+        }
+    }
+
+    /// Returns `true` if the hash should be replaced by one of the current algorithm
+    /// and parameters.
+    pub fn needs_rehash(&self, hash: &str) -> bool {
+        !self.current.recognizes(hash) || self.current.is_outdated(hash)
+    }
+
+    fn hasher_of(&self, hash: &str) -> Option<&dyn PasswordHasher> {
+        std::iter::once(&self.current)
+            .chain(self.legacy.iter())
+            .find(|hasher| hasher.recognizes(hash))
+            .map(|hasher| hasher.as_ref())
+    }
+}
+
+/// Rejects password hashing parameters that can not be used.
+pub fn check_config(config: &AppConfig) -> anyhow::Result<()> {
+    PasswordHashers::from_config(config.password()).map(|_| ())
+}
+
+pub fn get_password_hashers() -> &'static PasswordHashers {
+    &PASSWORD_HASHERS
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn hashers(
+        current: Box<dyn PasswordHasher>,
+        legacy: Box<dyn PasswordHasher>,
+    ) -> PasswordHashers {
+        PasswordHashers::new(current, vec![legacy]).unwrap()
+    }
+
+    #[test]
+    fn test_argon2id_hashes_verify() {
+        let hashers = hashers(
+            Box::new(Argon2Hasher::new(1024, 1, 1).unwrap()),
+            Box::new(BcryptHasher::new(4).unwrap()),
+        );
+        let hash = hashers.hash("a1234567").unwrap();
+
+        assert!(hash.starts_with("$argon2id$"));
+        assert!(hashers.verify("a1234567", &hash).unwrap());
+        assert!(!hashers.verify("a1234568", &hash).unwrap());
+        assert!(!hashers.needs_rehash(&hash));
+        assert!(!hashers.verify("a1234567", "").unwrap());
+    }
+
+    #[test]
+    fn test_bcrypt_hashes_verify_and_need_rehash() {
+        let hashers = hashers(
+            Box::new(Argon2Hasher::new(1024, 1, 1).unwrap()),
+            Box::new(BcryptHasher::new(4).unwrap()),
+        );
+        let hash = bcrypt::hash("a1234567", 4).unwrap();
+
+        assert!(hashers.verify("a1234567", &hash).unwrap());
+        assert!(hashers.needs_rehash(&hash));
+    }
+
+    #[test]
+    fn test_bcrypt_cost_must_be_supported() {
+        assert!(BcryptHasher::new(3).is_err());
+        assert!(BcryptHasher::new(4).is_ok());
+        assert!(BcryptHasher::new(31).is_ok());
+        assert!(BcryptHasher::new(32).is_err());
+    }
+
+    #[test]
+    fn test_hashers_that_can_not_hash_are_refused() {
+        // bcrypt only supports costs from 4 to 31
+        let result = PasswordHashers::new(Box::new(BcryptHasher { cost: 3 }), vec![]);
+
+        assert!(result.is_err());
+    }
+
+    #[test]
+    fn test_hashes_with_old_parameters_need_rehash() {
+        let old = Argon2Hasher::new(1024, 1, 1)
+            .unwrap()
+            .hash("a1234567")
+            .unwrap();
+        let hashers = hashers(
+            Box::new(Argon2Hasher::new(2048, 1, 1).unwrap()),
+            Box::new(BcryptHasher::new(4).unwrap()),
+        );
+
+        assert!(hashers.verify("a1234567", &old).unwrap());
+        assert!(hashers.needs_rehash(&old));
+        assert!(BcryptHasher::new(5)
+            .unwrap()
+            .is_outdated(&bcrypt::hash("a1234567", 4).unwrap()));
+    }
+}
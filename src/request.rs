@@ -142,3 +142,51 @@ pub fn is_email_valid(value: &str) -> Result<(), ValidationError> {
         })
     }
 }
+
+// ===== Password Validation Utilities =====
+
+/// Shortest accepted password, in characters.
+const PASSWORD_MIN_LENGTH: usize = 8;
+
+/// Longest accepted password, in characters.
+///
+/// Argon2id hashes the whole password, the bound only keeps the hashing cost of a
+/// single request in check.
+const PASSWORD_MAX_LENGTH: usize = 128;
+
+/// Validates password strength
+///
+/// Requires 8 to 128 characters with at least one letter and one digit.
+pub fn is_password_strong(value: &str) -> Result<(), ValidationError> {
+    let length = value.chars().count();
+    let has_letter = value.chars().any(char::is_alphabetic);
+    let has_digit = value.chars().any(|c| c.is_ascii_digit());
+
+    if (PASSWORD_MIN_LENGTH..=PASSWORD_MAX_LENGTH).contains(&length) && has_letter && has_digit {
+        Ok(())
+    } else {
+        Err(ValidationError {
+            code: Cow::from(""),
+            message: Some(Cow::from(
+                "password must be 8 to 128 characters with at least one letter and one digit.",
+            )),
+            params: HashMap::new(),
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_is_password_strong() {
+        assert!(is_password_strong("a1234567").is_ok());
+        assert!(is_password_strong("a123456").is_err());
+        assert!(is_password_strong("12345678").is_err());
+        assert!(is_password_strong("abcdefgh").is_err());
+        // longer than the 72 bytes bcrypt could hash
+        assert!(is_password_strong(&format!("a1{}", "é".repeat(60))).is_ok());
+        assert!(is_password_strong(&format!("a1{}", "b".repeat(127))).is_err());
+    }
+}
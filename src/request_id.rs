@@ -0,0 +1,177 @@
+use crate::error::ApiError;
+use axum::body::Body;
+use axum::extract::{FromRequestParts, Request};
+use axum::middleware::Next;
+use axum::response::Response;
+use http::request::Parts;
+use http::{header, HeaderMap, HeaderName, HeaderValue};
+use std::fmt::{Display, Formatter};
+
+/// Header carrying the correlation id of a request, from the client or gateway and back.
+pub const REQUEST_ID_HEADER: HeaderName = HeaderName::from_static("x-request-id");
+
+/// Longest incoming id that is kept, longer ones are replaced.
+const MAX_LENGTH: usize = 128;
+
+/// Marks an error response whose body lacks the request id, see `propagate`.
+#[derive(Debug, Clone, Copy)]
+pub(crate) struct MissingRequestId;
+
+tokio::task_local! {
+    static CURRENT: RequestId;
+}
+
+/// Correlation id of a request, logged with every line of the request and returned in the
+/// `X-Request-Id` header and error bodies.
+///
+/// Also an extractor for handlers, e.g. to hand it to a job processing the request later.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RequestId(String);
+
+impl RequestId {
+    /// Returns the id of the request being handled by the current task.
+    ///
+    /// `None` outside of a request, and in tasks spawned by handlers unless they pass the
+    /// id on.
+    pub fn current() -> Option<RequestId> {
+        CURRENT.try_with(RequestId::clone).ok()
+    }
+
+    pub fn as_str(&self) -> &str {
+        &self.0
+    }
+
+    /// The id of the incoming header, ignored if it is too long or contains other
+    /// characters than letters, digits, `-`, `_`, `.` and `:`, so it is safe to log.
+    fn from_headers(headers: &HeaderMap) -> Option<Self> {
+        let id = headers.get(&REQUEST_ID_HEADER)?.to_str().ok()?;
+        let valid = !id.is_empty()
+            && id.len() <= MAX_LENGTH
+            && id
+                .bytes()
+                .all(|b| b.is_ascii_alphanumeric() || matches!(b, b'-' | b'_' | b'.' | b':'));
+
+        valid.then(|| Self(id.to_string()))
+    }
+
+    fn generate() -> Self {
+        Self(xid::new().to_string())
+    }
+}
+
+impl Display for RequestId {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        f.write_str(&self.0)
+    }
+}
+
+impl<S: Send + Sync> FromRequestParts<S> for RequestId {
+    type Rejection = ApiError;
+
+    async fn from_request_parts(parts: &mut Parts, _state: &S) -> Result<Self, Self::Rejection> {
+        parts.extensions.get::<RequestId>().cloned().ok_or_else(|| {
+            ApiError::InternalError(anyhow::anyhow!("The request id layer is missing"))
+        })
+    }
+}
+
+/// Honors the incoming `X-Request-Id` header or generates an id, makes it available to
+/// the inner layers and handlers, and echoes it on the response.
+///
+/// Error bodies built where `RequestId::current` is not set, e.g. in tasks spawned by
+/// handlers, get the id added here.
+pub async fn propagate(mut request: Request, next: Next) -> Response {
+    let id = RequestId::from_headers(request.headers()).unwrap_or_else(RequestId::generate);
+    request.extensions_mut().insert(id.clone());
+
+    let mut response = CURRENT.scope(id.clone(), next.run(request)).await;
+    if response
+        .extensions_mut()
+        .remove::<MissingRequestId>()
+        .is_some()
+    {
+        response = quote_in_body(response, &id).await;
+    }
+    // the id only holds header-safe characters
+    let value = HeaderValue::from_str(id.as_str()).expect("request id is a valid header value");
+    response.headers_mut().insert(REQUEST_ID_HEADER, value);
+    response
+}
+
+/// Adds `id` as the `request_id` of a JSON error body.
+async fn quote_in_body(response: Response, id: &RequestId) -> Response {
+    let (mut parts, body) = response.into_parts();
+    let bytes = match axum::body::to_bytes(body, usize::MAX).await {
+        Ok(bytes) => bytes,
+        Err(e) => {
+            tracing::error!("error reading the error body: {:?}", e);
+            return Response::from_parts(parts, Body::empty());
+        }
+    };
+    let body = match serde_json::from_slice::<serde_json::Value>(&bytes) {
+        Ok(serde_json::Value::Object(mut body)) => {
+            body.insert("request_id".to_string(), id.as_str().into());
+            serde_json::to_vec(&body).map_or(bytes, Into::into)
+        }
+        _ => bytes,
+    };
+
+    parts.headers.remove(header::CONTENT_LENGTH);
+    Response::from_parts(parts, Body::from(body))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::response::ApiResponse;
+    use axum::response::IntoResponse;
+    use axum::routing::get;
+    use axum::Router;
+    use tower::ServiceExt;
+
+    fn incoming(value: &str) -> Option<RequestId> {
+        let mut headers = HeaderMap::new();
+        headers.insert(REQUEST_ID_HEADER, HeaderValue::from_str(value).unwrap());
+        RequestId::from_headers(&headers)
+    }
+
+    #[test]
+    fn test_unsafe_incoming_ids_are_replaced() {
+        assert_eq!(
+            incoming("gw-7f3a:01.2_b").unwrap().as_str(),
+            "gw-7f3a:01.2_b"
+        );
+        assert!(incoming("").is_none());
+        assert!(incoming("a b").is_none());
+        assert!(incoming("id\"}{").is_none());
+        assert!(incoming(&"a".repeat(MAX_LENGTH + 1)).is_none());
+    }
+
+    #[tokio::test]
+    async fn test_errors_built_outside_of_the_request_quote_its_id() {
+        let app = Router::new()
+            .route(
+                "/spawned",
+                get(|| async {
+                    tokio::spawn(async { ApiResponse::<()>::error("failed").into_response() })
+                        .await
+                        .unwrap()
+                }),
+            )
+            .layer(axum::middleware::from_fn(propagate));
+
+        let request = Request::get("/spawned")
+            .header(REQUEST_ID_HEADER, "gw-1")
+            .body(Body::empty())
+            .unwrap();
+        let response = app.oneshot(request).await.unwrap();
+        assert!(response.extensions().get::<MissingRequestId>().is_none());
+        let body = axum::body::to_bytes(response.into_body(), usize::MAX)
+            .await
+            .unwrap();
+        let body: serde_json::Value = serde_json::from_slice(&body).unwrap();
+
+        assert_eq!(body["request_id"], "gw-1");
+        assert_eq!(body["msg"], "failed");
+    }
+}
@@ -1,4 +1,5 @@
 use crate::error::ApiError;
+use crate::request_id::{MissingRequestId, RequestId};
 use axum::response::{IntoResponse, Response};
 use serde::{Deserialize, Serialize};
 
@@ -18,11 +19,20 @@ pub struct ApiResponse<T> {
 
     #[serde(skip_serializing_if = "Option::is_none")] // 忽略序列化，如果Option is none.
     pub data: Option<T>,
+
+    /// `X-Request-Id` of the failed request, to quote when reporting the error.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub request_id: Option<String>,
 }
 
 impl<T> ApiResponse<T> {
     pub fn new(code: i16, msg: String, data: Option<T>) -> Self {
-        ApiResponse { code, msg, data }
+        ApiResponse {
+            code,
+            msg,
+            data,
+            request_id: None,
+        }
     }
 
     /// Creates a successful API response
@@ -36,14 +46,23 @@ impl<T> ApiResponse<T> {
     /// Creates an error API response
     ///
     /// Uses code 0 for errors (can be customized based on your needs).
-    /// Error responses typically don't include data payload.
+    /// Error responses typically don't include data payload, but the id of the request.
     pub fn error<M: Into<String>>(message: M) -> Self {
-        ApiResponse::new(0, message.into(), None)
+        ApiResponse {
+            request_id: RequestId::current().map(|id| id.to_string()),
+            ..ApiResponse::new(0, message.into(), None)
+        }
     }
 }
 
 impl<T: Serialize> IntoResponse for ApiResponse<T> {
     fn into_response(self) -> Response {
-        axum::Json(self).into_response()
+        // built outside of the request, e.g. in a spawned task, `propagate` fills it in
+        let missing_id = self.code == 0 && self.request_id.is_none();
+        let mut response = axum::Json(self).into_response();
+        if missing_id {
+            response.extensions_mut().insert(MissingRequestId);
+        }
+        response
     }
 }
@@ -0,0 +1,186 @@
+use crate::config::revocation::{RevocationBackend, RevocationConfig};
+use crate::config::AppConfig;
+use crate::entity::prelude::RevokedToken;
+use crate::entity::revoked_token;
+use anyhow::bail;
+use async_trait::async_trait;
+use jsonwebtoken::get_current_timestamp;
+use sea_orm::sea_query::OnConflict;
+use sea_orm::{prelude::*, Set};
+use std::collections::HashMap;
+use std::sync::{Arc, RwLock};
+use std::time::Duration;
+
+/// Storage for revoked access tokens, keyed by the token's `jti`.
+///
+/// Entries only need to live until the token's own `exp`; after that the token
+/// is rejected by signature validation anyway and the entry can be purged.
+#[async_trait]
+pub trait RevocationStore: Send + Sync {
+    /// Adds the token id to the denylist until `expires_at` (unix timestamp in seconds).
+    async fn revoke(&self, jti: &str, expires_at: u64) -> anyhow::Result<()>;
+
+    /// Returns `true` if the token id has been revoked.
+    async fn is_revoked(&self, jti: &str) -> anyhow::Result<bool>;
+
+    /// Removes entries whose tokens have expired, returning the number removed.
+    async fn purge_expired(&self) -> anyhow::Result<u64>;
+}
+
+/// Process-local revocation store.
+#[derive(Debug, Default)]
+pub struct MemoryRevocationStore {
+    entries: RwLock<HashMap<String, u64>>,
+}
+
+/// Revocation store backed by the `revoked_token` table.
+pub struct PgRevocationStore {
+    db: DatabaseConnection,
+}
+
+impl MemoryRevocationStore {
+    pub fn new() -> Self {
+        Self::default()
+    }
+}
+
+#[async_trait]
+impl RevocationStore for MemoryRevocationStore {
+    async fn revoke(&self, jti: &str, expires_at: u64) -> anyhow::Result<()> {
+        self.entries
+            .write()
+            .map_err(|_| anyhow::anyhow!("revocation store lock is poisoned"))?
+            .insert(jti.to_string(), expires_at);
+        Ok(())
+    }
+
+    async fn is_revoked(&self, jti: &str) -> anyhow::Result<bool> {
+        Ok(self
+            .entries
+            .read()
+            .map_err(|_| anyhow::anyhow!("revocation store lock is poisoned"))?
+            .contains_key(jti))
+    }
+
+    async fn purge_expired(&self) -> anyhow::Result<u64> {
+        let now = get_current_timestamp();
+        let mut entries = self
+            .entries
+            .write()
+            .map_err(|_| anyhow::anyhow!("revocation store lock is poisoned"))?;
+        let before = entries.len();
+        entries.retain(|_, expires_at| *expires_at > now);
+        Ok((before - entries.len()) as u64)
+    }
+}
+
+impl PgRevocationStore {
+    pub fn new(db: DatabaseConnection) -> Self {
+        Self { db }
+    }
+}
+
+#[async_trait]
+impl RevocationStore for PgRevocationStore {
+    async fn revoke(&self, jti: &str, expires_at: u64) -> anyhow::Result<()> {
+        let expires_at = DateTimeUtc::from_timestamp(expires_at as i64, 0)
+            .ok_or_else(|| anyhow::anyhow!("invalid token expiration: {}", expires_at))?;
+
+        RevokedToken::insert(revoked_token::ActiveModel {
+            jti: Set(jti.to_string()),
+            expires_at: Set(expires_at.fixed_offset()),
+            ..Default::default()
+        })
+        .on_conflict(
+            OnConflict::column(revoked_token::Column::Jti)
+                .do_nothing()
+                .to_owned(),
+        )
+        .do_nothing()
+        .exec(&self.db)
+        .await?;
+
+        Ok(())
+    }
+
+    async fn is_revoked(&self, jti: &str) -> anyhow::Result<bool> {
+        Ok(RevokedToken::find_by_id(jti).one(&self.db).await?.is_some())
+    }
+
+    async fn purge_expired(&self) -> anyhow::Result<u64> {
+        let result = RevokedToken::delete_many()
+            .filter(Expr::col(revoked_token::Column::ExpiresAt).lt(Expr::current_timestamp()))
+            .exec(&self.db)
+            .await?;
+
+        Ok(result.rows_affected)
+    }
+}
+
+/// Rejects a revocation configuration the cleanup task can not run with.
+pub fn check_config(config: &AppConfig) -> anyhow::Result<()> {
+    check_cleanup_interval(config.revocation())
+}
+
+fn check_cleanup_interval(config: &RevocationConfig) -> anyhow::Result<()> {
+    if config.cleanup_interval() == 0 {
+        bail!("revocation.cleanup_interval must be greater than 0");
+    }
+
+    Ok(())
+}
+
+/// Creates the revocation store selected in the configuration.
+pub fn build_store(config: &RevocationConfig, db: &DatabaseConnection) -> Arc<dyn RevocationStore> {
+    match config.store() {
+        RevocationBackend::Memory => Arc::new(MemoryRevocationStore::new()),
+        RevocationBackend::Postgres => Arc::new(PgRevocationStore::new(db.clone())),
+    }
+}
+
+/// Spawns a background task that periodically purges expired denylist entries.
+pub fn spawn_cleanup(store: Arc<dyn RevocationStore>, interval: Duration) {
+    tokio::spawn(async move {
+        let mut ticker = tokio::time::interval(interval);
+        loop {
+            ticker.tick().await;
+            match store.purge_expired().await {
+                Ok(0) => {}
+                Ok(purged) => tracing::info!("purged {} expired revoked tokens", purged),
+                Err(e) => tracing::error!("error purging revoked tokens: {:?}", e),
+            }
+        }
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_cleanup_interval_must_be_positive() {
+        let config = |interval: u64| -> RevocationConfig {
+            serde_json::from_value(serde_json::json!({ "cleanup_interval": interval })).unwrap()
+        };
+
+        assert!(check_cleanup_interval(&config(0)).is_err());
+        assert!(check_cleanup_interval(&config(1)).is_ok());
+        assert!(check_cleanup_interval(&RevocationConfig::default()).is_ok());
+    }
+
+    #[tokio::test]
+    async fn test_memory_store_revoke_and_purge() {
+        let store = MemoryRevocationStore::new();
+        let now = get_current_timestamp();
+        store.revoke("expired", now - 10).await.unwrap();
+        store.revoke("active", now + 3600).await.unwrap();
+
+        assert!(store.is_revoked("expired").await.unwrap());
+        assert!(store.is_revoked("active").await.unwrap());
+        assert!(!store.is_revoked("unknown").await.unwrap());
+
+        assert_eq!(store.purge_expired().await.unwrap(), 1);
+        assert!(!store.is_revoked("expired").await.unwrap());
+        assert!(store.is_revoked("active").await.unwrap());
+    }
+}
@@ -0,0 +1,222 @@
+use crate::config::session::{SameSite, SessionConfig, SessionMode};
+use crate::error::ApiError;
+use anyhow::{bail, Context};
+use base64::engine::general_purpose::URL_SAFE_NO_PAD;
+use base64::Engine;
+use http::{header, HeaderMap, HeaderName, HeaderValue, Method};
+use rand::RngCore;
+
+/// Cookie carrying the access token, sent with every request.
+pub const ACCESS_COOKIE: &str = "access_token";
+/// Cookie carrying the refresh token, only sent to `/auth/refresh`.
+pub const REFRESH_COOKIE: &str = "refresh_token";
+/// Cookie carrying the CSRF token, readable by scripts so they can echo it in a header.
+pub const CSRF_COOKIE: &str = "csrf_token";
+
+const REFRESH_PATH: &str = "/auth/refresh";
+
+/// Browser sessions kept in cookies: the tokens in `HttpOnly` cookies that scripts can
+/// not read, protected against cross-site request forgery by a double-submit token.
+///
+/// Every state-changing request authenticated by the cookies must echo the value of the
+/// CSRF cookie in the CSRF header, which other sites can neither read nor set.
+#[derive(Debug, Clone)]
+pub struct CookieSession {
+    secure: bool,
+    same_site: SameSite,
+    domain: Option<String>,
+    csrf_header: HeaderName,
+    access_max_age: u64,
+    refresh_max_age: u64,
+}
+
+/// The `Set-Cookie` values of a new session and its CSRF token.
+#[derive(Debug, Clone)]
+pub struct SessionCookies {
+    pub cookies: Vec<HeaderValue>,
+    pub csrf_token: String,
+}
+
+impl CookieSession {
+    /// Builds the cookie session of the configuration, `None` in bearer mode.
+    ///
+    /// The cookies live as long as the tokens they carry.
+    pub fn from_config(
+        config: &SessionConfig,
+        access_max_age: u64,
+        refresh_max_age: u64,
+    ) -> anyhow::Result<Option<Self>> {
+        if config.mode() != SessionMode::Cookie {
+            return Ok(None);
+        }
+        if config.same_site() == SameSite::None && !config.cookie_secure() {
+            bail!("session.same_site none requires session.cookie_secure");
+        }
+
+        Ok(Some(Self {
+            secure: config.cookie_secure(),
+            same_site: config.same_site(),
+            domain: config.cookie_domain().map(str::to_string),
+            csrf_header: HeaderName::try_from(config.csrf_header())
+                .context("Invalid session.csrf_header")?,
+            access_max_age,
+            refresh_max_age,
+        }))
+    }
+
+    /// Returns the header that must echo the CSRF cookie.
+    pub fn csrf_header(&self) -> &HeaderName {
+        &self.csrf_header
+    }
+
+    /// Returns the access token of the request's cookies.
+    pub fn access_token<'a>(&self, headers: &'a HeaderMap) -> Option<&'a str> {
+        cookie(headers, ACCESS_COOKIE).filter(|token| !token.is_empty())
+    }
+
+    /// Returns the refresh token of the request's cookies.
+    pub fn refresh_token<'a>(&self, headers: &'a HeaderMap) -> Option<&'a str> {
+        cookie(headers, REFRESH_COOKIE).filter(|token| !token.is_empty())
+    }
+
+    /// Rejects state-changing requests whose CSRF header does not match the CSRF cookie.
+    pub fn check_csrf(&self, method: &Method, headers: &HeaderMap) -> Result<(), ApiError> {
+        if matches!(
+            *method,
+            Method::GET | Method::HEAD | Method::OPTIONS | Method::TRACE
+        ) {
+            return Ok(());
+        }
+
+        let expected = cookie(headers, CSRF_COOKIE).filter(|token| !token.is_empty());
+        let presented = headers
+            .get(&self.csrf_header)
+            .and_then(|value| value.to_str().ok());
+        match (expected, presented) {
+            (Some(expected), Some(presented)) if constant_time_eq(expected, presented) => Ok(()),
+            _ => {
+                tracing::warn!("CSRF token is missing or does not match: {}", method);
+                Err(ApiError::ForbiddenError(
+                    "CSRF token is missing or invalid!".to_string(),
+                ))
+            }
+        }
+    }
+
+    /// Cookies of a new session holding the token pair, with a fresh CSRF token.
+    pub fn login_cookies(&self, access_token: &str, refresh_token: &str) -> SessionCookies {
+        let mut bytes = [0u8; 32];
+        rand::rngs::OsRng.fill_bytes(&mut bytes);
+        let csrf_token = URL_SAFE_NO_PAD.encode(bytes);
+
+        SessionCookies {
+            cookies: vec![
+                self.cookie(ACCESS_COOKIE, access_token, "/", self.access_max_age, true),
+                self.cookie(
+                    REFRESH_COOKIE,
+                    refresh_token,
+                    REFRESH_PATH,
+                    self.refresh_max_age,
+                    true,
+                ),
+                self.cookie(CSRF_COOKIE, &csrf_token, "/", self.refresh_max_age, false),
+            ],
+            csrf_token,
+        }
+    }
+
+    /// Cookies removing the session from the browser.
+    pub fn logout_cookies(&self) -> Vec<HeaderValue> {
+        vec![
+            self.cookie(ACCESS_COOKIE, "", "/", 0, true),
+            self.cookie(REFRESH_COOKIE, "", REFRESH_PATH, 0, true),
+            self.cookie(CSRF_COOKIE, "", "/", 0, false),
+        ]
+    }
+
+    fn cookie(
+        &self,
+        name: &str,
+        value: &str,
+        path: &str,
+        max_age: u64,
+        http_only: bool,
+    ) -> HeaderValue {
+        let mut cookie = format!("{}={}; Path={}; Max-Age={}", name, value, path, max_age);
+        if let Some(domain) = &self.domain {
+            cookie.push_str(&format!("; Domain={}", domain));
+        }
+        if http_only {
+            cookie.push_str("; HttpOnly");
+        }
+        if self.secure {
+            cookie.push_str("; Secure");
+        }
+        cookie.push_str(match self.same_site {
+            SameSite::Strict => "; SameSite=Strict",
+            SameSite::Lax => "; SameSite=Lax",
+            SameSite::None => "; SameSite=None",
+        });
+
+        // tokens and the configured domain are plain ASCII
+        HeaderValue::from_str(&cookie).expect("cookie is a valid header value")
+    }
+}
+
+/// Returns the value of the request cookie `name`.
+pub fn cookie<'a>(headers: &'a HeaderMap, name: &str) -> Option<&'a str> {
+    headers
+        .get_all(header::COOKIE)
+        .iter()
+        .filter_map(|value| value.to_str().ok())
+        .flat_map(|value| value.split(';'))
+        .filter_map(|pair| pair.trim().split_once('='))
+        .find(|(key, _)| *key == name)
+        .map(|(_, value)| value)
+}
+
+/// Compares without returning early, so the timing does not reveal the matching prefix.
+fn constant_time_eq(a: &str, b: &str) -> bool {
+    a.len() == b.len()
+        && a.bytes()
+            .zip(b.bytes())
+            .fold(0u8, |diff, (x, y)| diff | (x ^ y))
+            == 0
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn session(config: serde_json::Value) -> anyhow::Result<Option<CookieSession>> {
+        let config: SessionConfig = serde_json::from_value(config).unwrap();
+        CookieSession::from_config(&config, 3600, 86400)
+    }
+
+    #[test]
+    fn test_csrf_header_must_match_the_cookie() {
+        let session = session(serde_json::json!({ "mode": "cookie" }))
+            .unwrap()
+            .unwrap();
+        let mut headers = HeaderMap::new();
+        headers.insert(header::COOKIE, HeaderValue::from_static("csrf_token=abc"));
+
+        assert!(session.check_csrf(&Method::GET, &headers).is_ok());
+        assert!(session.check_csrf(&Method::POST, &headers).is_err());
+
+        headers.insert("x-csrf-token", HeaderValue::from_static("abd"));
+        assert!(session.check_csrf(&Method::DELETE, &headers).is_err());
+
+        headers.insert("x-csrf-token", HeaderValue::from_static("abc"));
+        assert!(session.check_csrf(&Method::DELETE, &headers).is_ok());
+    }
+
+    #[test]
+    fn test_invalid_cookie_settings_are_refused() {
+        assert!(session(serde_json::json!({})).unwrap().is_none());
+        assert!(session(serde_json::json!({
+            "mode": "cookie", "same_site": "none", "cookie_secure": false,
+        }))
+        .is_err());
+    }
+}
@@ -0,0 +1,176 @@
+use axum::Router;
+use std::future::Future;
+use std::net::SocketAddr;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::net::TcpListener;
+use tokio::sync::watch;
+
+/// Whether the server accepts traffic: set once it listens, cleared when it starts draining.
+///
+/// Load balancers stop routing new requests to an instance that is not ready.
+#[derive(Debug, Clone, Default)]
+pub struct Readiness(Arc<AtomicBool>);
+
+impl Readiness {
+    pub fn is_ready(&self) -> bool {
+        self.0.load(Ordering::Relaxed)
+    }
+
+    pub fn set_ready(&self, ready: bool) {
+        self.0.store(ready, Ordering::Relaxed);
+    }
+}
+
+/// Completes on `SIGINT` (Ctrl+C) or, on Unix, `SIGTERM`.
+pub async fn signal() {
+    let ctrl_c = async {
+        tokio::signal::ctrl_c()
+            .await
+            .expect("Failed to install the Ctrl+C handler");
+    };
+
+    #[cfg(unix)]
+    let terminate = async {
+        tokio::signal::unix::signal(tokio::signal::unix::SignalKind::terminate())
+            .expect("Failed to install the SIGTERM handler")
+            .recv()
+            .await;
+    };
+    #[cfg(not(unix))]
+    let terminate = std::future::pending::<()>();
+
+    tokio::select! {
+        _ = ctrl_c => tracing::info!("SIGINT received"),
+        _ = terminate => tracing::info!("SIGTERM received"),
+    }
+}
+
+/// Serves `router` until `shutdown` completes, then drains: the server is marked not
+/// ready, stops accepting connections and waits for in-flight requests.
+///
+/// Returns once every connection finished, or after `drain_timeout` with `false`; the
+/// requests still running then are dropped when the process exits.
+pub async fn serve(
+    listener: TcpListener,
+    router: Router,
+    shutdown: impl Future<Output = ()> + Send + 'static,
+    readiness: Readiness,
+    drain_timeout: Duration,
+) -> anyhow::Result<bool> {
+    let (draining_tx, draining_rx) = watch::channel(false);
+    tokio::spawn(async move {
+        shutdown.await;
+        readiness.set_ready(false);
+        tracing::info!(
+            "shutting down, draining connections for up to {:?}",
+            drain_timeout
+        );
+        let _ = draining_tx.send(true);
+    });
+
+    let mut stop_accepting = draining_rx.clone();
+    let server = axum::serve(
+        listener,
+        router.into_make_service_with_connect_info::<SocketAddr>(),
+    )
+    .with_graceful_shutdown(async move {
+        let _ = stop_accepting.wait_for(|draining| *draining).await;
+    });
+
+    let mut deadline = draining_rx;
+    tokio::select! {
+        result = async move { server.await } => {
+            result?;
+            tracing::info!("all connections drained");
+            Ok(true)
+        }
+        _ = async move {
+            if deadline.wait_for(|draining| *draining).await.is_ok() {
+                tokio::time::sleep(drain_timeout).await;
+            } else {
+                // the shutdown task is gone without a signal, serve until the server stops
+                std::future::pending::<()>().await;
+            }
+        } => {
+            tracing::warn!("drain deadline exceeded, dropping the remaining connections");
+            Ok(false)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use axum::routing::get;
+    use tokio::sync::oneshot;
+
+    async fn slow(delay: Duration) -> &'static str {
+        tokio::time::sleep(delay).await;
+        "done"
+    }
+
+    /// Starts a server with a single route answering after `delay`, and a request to it.
+    async fn start(
+        delay: Duration,
+        drain_timeout: Duration,
+    ) -> (
+        Readiness,
+        oneshot::Sender<()>,
+        tokio::task::JoinHandle<anyhow::Result<bool>>,
+        tokio::task::JoinHandle<reqwest::Result<reqwest::Response>>,
+    ) {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let url = format!("http://{}/", listener.local_addr().unwrap());
+        let router = Router::new().route("/", get(move || slow(delay)));
+        let readiness = Readiness::default();
+        readiness.set_ready(true);
+
+        let (signal_tx, signal_rx) = oneshot::channel();
+        let server = tokio::spawn(serve(
+            listener,
+            router,
+            async move {
+                let _ = signal_rx.await;
+            },
+            readiness.clone(),
+            drain_timeout,
+        ));
+        let request = tokio::spawn(reqwest::get(url));
+        // let the request reach the handler before shutting down
+        tokio::time::sleep(Duration::from_millis(50)).await;
+
+        (readiness, signal_tx, server, request)
+    }
+
+    #[tokio::test]
+    async fn test_in_flight_requests_complete_while_draining() {
+        let (readiness, signal, server, request) =
+            start(Duration::from_millis(200), Duration::from_secs(5)).await;
+
+        signal.send(()).unwrap();
+        tokio::time::sleep(Duration::from_millis(20)).await;
+        assert!(!readiness.is_ready());
+
+        let response = request.await.unwrap().unwrap();
+        assert_eq!(response.text().await.unwrap(), "done");
+        assert!(server.await.unwrap().unwrap());
+    }
+
+    #[tokio::test]
+    async fn test_drain_deadline_stops_waiting_for_requests() {
+        let (_, signal, server, request) =
+            start(Duration::from_secs(30), Duration::from_millis(100)).await;
+
+        signal.send(()).unwrap();
+        let drained = tokio::time::timeout(Duration::from_secs(5), server)
+            .await
+            .expect("the drain deadline stops the server")
+            .unwrap()
+            .unwrap();
+
+        assert!(!drained);
+        request.abort();
+    }
+}
@@ -0,0 +1,127 @@
+use crate::config::telemetry::TelemetryConfig;
+use crate::database;
+use anyhow::Context as _;
+use http::HeaderMap;
+use opentelemetry::trace::{
+    Span as _, SpanKind, Status, TraceContextExt, Tracer as _, TracerProvider as _,
+};
+use opentelemetry::{global, KeyValue};
+use opentelemetry_http::{HeaderExtractor, HeaderInjector};
+use opentelemetry_otlp::WithExportConfig;
+use opentelemetry_sdk::propagation::TraceContextPropagator;
+use opentelemetry_sdk::trace::{Sampler, SdkTracer, SdkTracerProvider};
+use opentelemetry_sdk::Resource;
+use std::time::{Duration, SystemTime};
+use tracing::Subscriber;
+use tracing_opentelemetry::{OpenTelemetryLayer, OpenTelemetrySpanExt};
+use tracing_subscriber::registry::LookupSpan;
+
+/// Instrumentation scope of the spans created by the application.
+const TRACER_NAME: &str = "axum_template";
+
+/// Builds the OTLP/HTTP trace pipeline, `None` when telemetry is disabled.
+///
+/// Spans are exported in batches from a background thread. Traces continued from a
+/// caller keep its sampling decision, new ones are sampled at `sample_ratio`.
+pub fn tracer_provider(config: &TelemetryConfig) -> anyhow::Result<Option<SdkTracerProvider>> {
+    if !config.enabled() {
+        return Ok(None);
+    }
+
+    let exporter = opentelemetry_otlp::SpanExporter::builder()
+        .with_http()
+        .with_endpoint(config.endpoint())
+        .with_timeout(Duration::from_secs(config.export_timeout()))
+        .build()
+        .context("Failed to create the OTLP span exporter")?;
+    let sampler = Sampler::ParentBased(Box::new(Sampler::TraceIdRatioBased(config.sample_ratio())));
+    let resource = Resource::builder()
+        .with_service_name(config.service_name().to_string())
+        .build();
+
+    Ok(Some(
+        SdkTracerProvider::builder()
+            .with_batch_exporter(exporter)
+            .with_sampler(sampler)
+            .with_resource(resource)
+            .build(),
+    ))
+}
+
+/// Makes `provider` the global one and propagates the W3C `traceparent` header.
+pub fn install(provider: &SdkTracerProvider) {
+    global::set_text_map_propagator(TraceContextPropagator::new());
+    global::set_tracer_provider(provider.clone());
+}
+
+/// The layer turning `tracing` spans, e.g. of `#[tracing::instrument]`, into exported spans.
+pub fn layer<S>(provider: &SdkTracerProvider) -> OpenTelemetryLayer<S, SdkTracer>
+where
+    S: Subscriber + for<'span> LookupSpan<'span>,
+{
+    tracing_opentelemetry::layer().with_tracer(provider.tracer(TRACER_NAME))
+}
+
+/// Exports the spans still buffered, waiting at most for the export timeout.
+pub async fn shutdown(provider: SdkTracerProvider) {
+    match tokio::task::spawn_blocking(move || provider.shutdown()).await {
+        Ok(Ok(())) => tracing::info!("trace exporter flushed"),
+        Ok(Err(e)) => tracing::warn!("failed to flush the trace exporter: {}", e),
+        Err(e) => tracing::warn!("failed to flush the trace exporter: {}", e),
+    }
+}
+
+/// Makes `span` a child of the trace context of the incoming `headers`, if any.
+///
+/// Does nothing when telemetry is disabled.
+pub fn continue_trace(span: &tracing::Span, headers: &HeaderMap) {
+    let parent =
+        global::get_text_map_propagator(|propagator| propagator.extract(&HeaderExtractor(headers)));
+    if parent.span().span_context().is_valid() {
+        // fails only without the telemetry layer, the span is then not exported anyway
+        let _ = span.set_parent(parent);
+    }
+}
+
+/// The trace context of the current span, to send with outgoing requests.
+///
+/// Empty when telemetry is disabled.
+pub fn trace_headers() -> HeaderMap {
+    let context = tracing::Span::current().context();
+    let mut headers = HeaderMap::new();
+    global::get_text_map_propagator(|propagator| {
+        propagator.inject_context(&context, &mut HeaderInjector(&mut headers))
+    });
+    headers
+}
+
+/// Records a finished query as a child span of the current span.
+///
+/// Queries outside of a trace, e.g. of background tasks, are not recorded.
+pub fn record_query(info: &sea_orm::metric::Info<'_>) {
+    let parent = tracing::Span::current().context();
+    if !parent.span().span_context().is_valid() {
+        return;
+    }
+
+    // the query is reported once finished, backdate its start
+    let end = SystemTime::now();
+    let start = end.checked_sub(info.elapsed).unwrap_or(end);
+    let operation = database::operation(&info.statement.sql);
+
+    let tracer = global::tracer(TRACER_NAME);
+    let mut span = tracer
+        .span_builder(operation)
+        .with_kind(SpanKind::Client)
+        .with_start_time(start)
+        .with_attributes([
+            KeyValue::new("db.system.name", "postgresql"),
+            KeyValue::new("db.operation.name", operation),
+            KeyValue::new("db.query.text", info.statement.sql.clone()),
+        ])
+        .start_with_context(&tracer, &parent);
+    if info.failed {
+        span.set_status(Status::error("query failed"));
+    }
+    span.end_with_timestamp(end);
+}
@@ -0,0 +1,99 @@
+use crate::auth::Principal;
+use crate::entity::{api_key, users, workspace};
+use crate::error::ApiError;
+use axum::extract::FromRequestParts;
+use http::request::Parts;
+use sea_orm::{ColumnTrait, DeleteMany, EntityTrait, QueryFilter, Select, UpdateMany};
+
+/// Workspace of the authenticated caller, resolved from the `Principal`.
+///
+/// Handlers touching tenant-owned tables build their queries through `Tenant`
+/// so that rows of other workspaces are never read or written.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Tenant {
+    pub ws_id: i64,
+    pub user_id: i64,
+}
+
+/// An entity whose rows belong to exactly one workspace.
+pub trait TenantScoped: EntityTrait {
+    /// Column holding the id of the owning workspace.
+    fn tenant_column() -> Self::Column;
+}
+
+impl TenantScoped for api_key::Entity {
+    fn tenant_column() -> Self::Column {
+        api_key::Column::WsId
+    }
+}
+
+impl TenantScoped for users::Entity {
+    fn tenant_column() -> Self::Column {
+        users::Column::WsId
+    }
+}
+
+impl TenantScoped for workspace::Entity {
+    fn tenant_column() -> Self::Column {
+        workspace::Column::Id
+    }
+}
+
+impl Tenant {
+    /// Selects the rows of `E` that belong to the caller's workspace.
+    pub fn find<E: TenantScoped>(&self) -> Select<E> {
+        E::find().filter(E::tenant_column().eq(self.ws_id))
+    }
+
+    /// Updates only among the rows of `E` that belong to the caller's workspace.
+    pub fn update_many<E: TenantScoped>(&self) -> UpdateMany<E> {
+        E::update_many().filter(E::tenant_column().eq(self.ws_id))
+    }
+
+    /// Deletes only among the rows of `E` that belong to the caller's workspace.
+    pub fn delete_many<E: TenantScoped>(&self) -> DeleteMany<E> {
+        E::delete_many().filter(E::tenant_column().eq(self.ws_id))
+    }
+
+    /// Rejects writes that target a workspace other than the caller's.
+    pub fn check(&self, ws_id: i64) -> Result<(), ApiError> {
+        if ws_id != self.ws_id {
+            tracing::warn!(
+                "user {} of workspace {} tried to access workspace {}",
+                self.user_id,
+                self.ws_id,
+                ws_id
+            );
+            return Err(ApiError::ForbiddenError(format!(
+                "workspace {} is not accessible",
+                ws_id
+            )));
+        }
+
+        Ok(())
+    }
+}
+
+impl<S> FromRequestParts<S> for Tenant
+where
+    S: Send + Sync,
+{
+    type Rejection = ApiError;
+
+    async fn from_request_parts(parts: &mut Parts, _state: &S) -> Result<Self, Self::Rejection> {
+        let principal = parts.extensions.get::<Principal>().ok_or_else(|| {
+            ApiError::UnAuthenticatedError("Authorization header is not found!".to_string())
+        })?;
+        // legacy tokens carry no workspace, the caller has to login again to get one
+        let ws_id = principal.ws_id.ok_or_else(|| {
+            ApiError::UnAuthenticatedError(
+                "Token carries no workspace, please login again!".to_string(),
+            )
+        })?;
+
+        Ok(Self {
+            ws_id,
+            user_id: principal.id,
+        })
+    }
+}
@@ -0,0 +1,247 @@
+mod common;
+
+use async_trait::async_trait;
+use axum::body::Body;
+use axum::Router;
+use axum_template::common::hash_password;
+use axum_template::mailer::{Mail, Mailer};
+use axum_template::{api, application};
+use common::{read_json, row, user_row, TestDb};
+use http::{header, Request, StatusCode};
+use sea_orm::prelude::DateTimeWithTimeZone;
+use sea_orm::ProxyRow;
+use sha2::{Digest, Sha256};
+use std::sync::{Arc, Mutex};
+use std::time::Instant;
+use tower::ServiceExt;
+
+const INVALID_TOKEN: &str = "The token is invalid or has expired!";
+
+const SIGNUP_MAIL_SENT: &str = "Registration received, please check your email to continue.";
+
+/// Keeps the mails instead of sending them.
+#[derive(Default)]
+struct Outbox(Mutex<Vec<Mail>>);
+
+#[async_trait]
+impl Mailer for Outbox {
+    async fn send(&self, mail: Mail) -> anyhow::Result<()> {
+        self.0.lock().unwrap().push(mail);
+        Ok(())
+    }
+}
+
+impl Outbox {
+    fn mails(&self) -> Vec<Mail> {
+        self.0.lock().unwrap().clone()
+    }
+
+    /// The token of the link in the only mail sent.
+    fn token(&self) -> String {
+        let mails = self.mails();
+        assert_eq!(mails.len(), 1);
+        let (_, token) = mails[0].body.split_once("?token=").unwrap();
+        token.lines().next().unwrap().to_string()
+    }
+}
+
+async fn app(test_db: &TestDb) -> (Router, Arc<Outbox>) {
+    let outbox = Arc::new(Outbox::default());
+    let mut state = common::state(&test_db.db);
+    state.mailer = outbox.clone();
+    (
+        application::build_app(state, api::build_routes().await),
+        outbox,
+    )
+}
+
+async fn post(app: &Router, uri: &str, body: serde_json::Value) -> (StatusCode, serde_json::Value) {
+    let request = Request::post(uri)
+        .header(header::CONTENT_TYPE, "application/json")
+        .body(Body::from(body.to_string()))
+        .unwrap();
+    let (status, mut body) = read_json(app.clone().oneshot(request).await.unwrap()).await;
+    body.as_object_mut().unwrap().remove("request_id");
+    (status, body)
+}
+
+fn user_token_row(purpose: &str) -> ProxyRow {
+    row([
+        ("id", 1i64.into()),
+        ("user_id", 2i64.into()),
+        ("purpose", purpose.into()),
+        ("token_hash", "hash".into()),
+        ("expires_at", DateTimeWithTimeZone::default().into()),
+        ("used_at", Option::<DateTimeWithTimeZone>::None.into()),
+        ("create_at", Option::<DateTimeWithTimeZone>::None.into()),
+    ])
+}
+
+fn hash_token(token: &str) -> String {
+    format!("{:x}", Sha256::digest(token.as_bytes()))
+}
+
+fn signup() -> serde_json::Value {
+    serde_json::json!({ "fullname": "Alice", "email": "alice2@none.co", "password": "a1234567" })
+}
+
+/// A token is consumed by a single conditional `UPDATE`, for one purpose, while unused
+/// and unexpired.
+fn assert_consumed_once(sql: &str, token: &str, purpose: &str) {
+    assert!(
+        sql.starts_with("UPDATE \"public\".\"user_token\""),
+        "{}",
+        sql
+    );
+    assert!(sql.contains(&hash_token(token)), "{}", sql);
+    assert!(!sql.contains(token), "{}", sql);
+    assert!(sql.contains(purpose), "{}", sql);
+    assert!(sql.contains("\"used_at\" IS NULL"), "{}", sql);
+    assert!(
+        sql.contains("\"expires_at\" > CURRENT_TIMESTAMP"),
+        "{}",
+        sql
+    );
+}
+
+#[tokio::test]
+async fn test_signup_mails_a_token_that_is_only_stored_hashed() {
+    let mut unverified = user_row(2, 0, "");
+    unverified
+        .values
+        .insert("email_verified".to_string(), false.into());
+    let workspace = row([
+        ("id", 2i64.into()),
+        ("name", "ws-alice".into()),
+        ("owner_id", 2i64.into()),
+        ("create_at", Option::<DateTimeWithTimeZone>::None.into()),
+    ]);
+    let test_db = TestDb::new(vec![
+        // the email is not registered yet
+        vec![],
+        vec![unverified.clone()],
+        vec![workspace],
+        vec![unverified],
+        // no signup role
+        vec![],
+        vec![user_token_row("verify_email")],
+    ])
+    .await;
+    let (app, outbox) = app(&test_db).await;
+
+    let (status, body) = post(&app, "/auth/register", signup()).await;
+
+    assert_eq!(status, StatusCode::OK, "{}", body);
+    assert_eq!(body["msg"], SIGNUP_MAIL_SENT);
+    let token = outbox.token();
+    assert_eq!(outbox.mails()[0].to, "alice2@none.co");
+    let statements = test_db.statements();
+    let insert_user = statements
+        .iter()
+        .find(|sql| sql.starts_with("INSERT INTO \"public\".\"users\""))
+        .unwrap();
+    assert!(insert_user.contains("$argon2id$"), "{}", insert_user);
+    assert!(!insert_user.contains("a1234567"), "{}", insert_user);
+    let insert_token = statements.last().unwrap();
+    assert!(
+        insert_token.contains(&hash_token(&token)),
+        "{}",
+        insert_token
+    );
+    assert!(!insert_token.contains(&token), "{}", insert_token);
+}
+
+#[tokio::test]
+async fn test_duplicate_signup_looks_like_a_new_one() {
+    let started = Instant::now();
+    hash_password("a1234567").await.unwrap();
+    let hashing = started.elapsed();
+
+    let test_db = TestDb::new(vec![vec![user_row(2, 1, "")]]).await;
+    let (app, outbox) = app(&test_db).await;
+    let started = Instant::now();
+    let (status, duplicate) = post(&app, "/auth/register", signup()).await;
+
+    assert_eq!(status, StatusCode::OK, "{}", duplicate);
+    assert_eq!(duplicate["msg"], SIGNUP_MAIL_SENT);
+    // the password is hashed like for a new account
+    assert!(started.elapsed() >= hashing / 4);
+    let mails = outbox.mails();
+    assert_eq!(mails.len(), 1);
+    assert!(!mails[0].body.contains("token="));
+    assert_eq!(test_db.statements().len(), 1);
+}
+
+#[tokio::test]
+async fn test_verification_token_is_single_use() {
+    let test_db = TestDb::new(vec![vec![user_token_row("verify_email")]]).await;
+    let (app, _) = app(&test_db).await;
+    let body = serde_json::json!({ "token": "mailed-token" });
+
+    let (status, verified) = post(&app, "/auth/verify-email", body.clone()).await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(verified["msg"], "Email verified successfully!");
+    let statements = test_db.statements();
+    assert_consumed_once(&statements[0], "mailed-token", "verify_email");
+    assert!(statements[1].contains("\"email_verified\" = TRUE"));
+    assert!(statements[1].contains("\"id\" = 2"));
+
+    // used or expired, the conditional update matches no token
+    let (status, replayed) = post(&app, "/auth/verify-email", body).await;
+    assert_eq!(status, StatusCode::OK);
+    assert!(replayed["msg"].as_str().unwrap().ends_with(INVALID_TOKEN));
+    assert_eq!(test_db.statements().len(), statements.len() + 1);
+}
+
+#[tokio::test]
+async fn test_forgot_password_looks_the_same_for_unknown_emails() {
+    let unknown_db = TestDb::new(vec![]).await;
+    let (unknown_app, unknown_outbox) = app(&unknown_db).await;
+    let body = serde_json::json!({ "email": "alice2@none.co" });
+    let (_, unknown) = post(&unknown_app, "/auth/forgot-password", body.clone()).await;
+    assert!(unknown_outbox.mails().is_empty());
+
+    let test_db = TestDb::new(vec![
+        vec![user_row(2, 1, "")],
+        vec![user_token_row("reset_password")],
+    ])
+    .await;
+    let (app, outbox) = app(&test_db).await;
+    let (status, known) = post(&app, "/auth/forgot-password", body).await;
+
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(known, unknown);
+    let token = outbox.token();
+    let insert_token = test_db.statements().pop().unwrap();
+    assert!(insert_token.contains("reset_password"), "{}", insert_token);
+    assert!(
+        insert_token.contains(&hash_token(&token)),
+        "{}",
+        insert_token
+    );
+    assert!(!insert_token.contains(&token), "{}", insert_token);
+}
+
+#[tokio::test]
+async fn test_reset_token_is_single_use_and_signs_out() {
+    let test_db = TestDb::new(vec![vec![user_token_row("reset_password")]]).await;
+    let (app, _) = app(&test_db).await;
+    let body = serde_json::json!({ "token": "mailed-token", "password": "b1234567" });
+
+    let (status, reset) = post(&app, "/auth/reset-password", body.clone()).await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(reset["msg"], "Password reset successfully!");
+    let statements = test_db.statements();
+    assert_consumed_once(&statements[0], "mailed-token", "reset_password");
+    assert!(statements[1].contains("$argon2id$"), "{}", statements[1]);
+    assert!(!statements[1].contains("b1234567"), "{}", statements[1]);
+    assert!(statements[2].starts_with("UPDATE \"public\".\"user_token\""));
+    assert!(statements[3].starts_with("UPDATE \"public\".\"refresh_token\""));
+    assert!(statements[3].contains("\"revoked\" = TRUE"));
+
+    // used or expired, the password is left alone
+    let (status, replayed) = post(&app, "/auth/reset-password", body).await;
+    assert_eq!(status, StatusCode::OK);
+    assert!(replayed["msg"].as_str().unwrap().ends_with(INVALID_TOKEN));
+    assert_eq!(test_db.statements().len(), statements.len() + 1);
+}
@@ -0,0 +1,168 @@
+mod common;
+
+use axum::body::Body;
+use axum_template::api_key;
+use common::{app, read_json, row, user_row, TestDb};
+use http::{header, Request, StatusCode};
+use sea_orm::prelude::DateTimeWithTimeZone;
+use sea_orm::ProxyRow;
+use tower::ServiceExt;
+
+/// An active key of user `2` in workspace `1`.
+fn api_key_row(key: &str, scopes: &str) -> ProxyRow {
+    row([
+        ("id", 7i64.into()),
+        ("ws_id", 1i64.into()),
+        ("user_id", 2i64.into()),
+        ("name", "nightly-sync".into()),
+        ("prefix", key[..11].into()),
+        ("key_hash", api_key::hash_key(key).into()),
+        ("scopes", scopes.into()),
+        ("expires_at", Option::<DateTimeWithTimeZone>::None.into()),
+        ("last_used_at", Option::<DateTimeWithTimeZone>::None.into()),
+        ("revoked_at", Option::<DateTimeWithTimeZone>::None.into()),
+        ("create_at", Option::<DateTimeWithTimeZone>::None.into()),
+    ])
+}
+
+/// The `admin` role of user `2`, and the permissions it currently grants.
+fn authorities(permissions: &[&str]) -> Vec<Vec<ProxyRow>> {
+    let admin = row([
+        ("id", 1i64.into()),
+        ("name", "admin".into()),
+        ("create_at", Option::<DateTimeWithTimeZone>::None.into()),
+    ]);
+    let permissions = permissions
+        .iter()
+        .zip(1i64..)
+        .map(|(name, id)| {
+            row([
+                ("id", id.into()),
+                ("name", (*name).into()),
+                ("create_at", Option::<DateTimeWithTimeZone>::None.into()),
+            ])
+        })
+        .collect();
+    vec![vec![admin], permissions]
+}
+
+async fn send(
+    test_db: &TestDb,
+    request: http::request::Builder,
+    key: &str,
+) -> (StatusCode, serde_json::Value) {
+    let request = request
+        .header(header::AUTHORIZATION, format!("Bearer {}", key))
+        .header(header::CONTENT_TYPE, "application/json")
+        .body(Body::from("{}"))
+        .unwrap();
+    read_json(app(&test_db.db).await.oneshot(request).await.unwrap()).await
+}
+
+#[tokio::test]
+async fn test_api_key_acts_as_its_creator_with_its_scopes() {
+    let key = api_key::generate().key;
+    let mut results = vec![
+        vec![api_key_row(&key, "user:read user:update")],
+        vec![user_row(2, 1, "")],
+    ];
+    results.extend(authorities(&["user:delete", "user:read", "user:update"]));
+    let test_db = TestDb::new(results).await;
+
+    let (status, body) = send(&test_db, Request::get("/auth/get_user_info"), &key).await;
+
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(body["data"]["id"], 2);
+    assert_eq!(body["data"]["ws_id"], 1);
+    assert_eq!(
+        body["data"]["permissions"],
+        serde_json::json!(["user:read", "user:update"])
+    );
+
+    let statements = test_db.statements();
+    assert!(statements[0].starts_with("UPDATE \"public\".\"api_key\" SET \"last_used_at\""));
+    assert!(statements[0].contains(&api_key::hash_key(&key)));
+    assert!(statements[0].contains("\"revoked_at\" IS NULL"));
+}
+
+#[tokio::test]
+async fn test_api_key_loses_the_permissions_its_creator_lost() {
+    let key = api_key::generate().key;
+    let mut results = vec![
+        vec![api_key_row(&key, "user:read user:update")],
+        vec![user_row(2, 1, "")],
+    ];
+    results.extend(authorities(&["user:read"]));
+    let test_db = TestDb::new(results).await;
+
+    let (status, body) = send(&test_db, Request::get("/auth/get_user_info"), &key).await;
+
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(
+        body["data"]["permissions"],
+        serde_json::json!(["user:read"])
+    );
+}
+
+#[tokio::test]
+async fn test_api_key_of_a_creator_who_left_the_workspace_is_rejected() {
+    let key = api_key::generate().key;
+    let test_db = TestDb::new(vec![
+        vec![api_key_row(&key, "user:read")],
+        vec![user_row(2, 3, "")],
+    ])
+    .await;
+
+    let (status, _) = send(&test_db, Request::get("/auth/get_user_info"), &key).await;
+
+    assert_eq!(status, StatusCode::UNAUTHORIZED);
+}
+
+#[tokio::test]
+async fn test_unknown_or_revoked_api_key_is_rejected() {
+    let test_db = TestDb::new(vec![]).await;
+
+    let (status, _) = send(
+        &test_db,
+        Request::get("/auth/get_user_info"),
+        &api_key::generate().key,
+    )
+    .await;
+
+    assert_eq!(status, StatusCode::UNAUTHORIZED);
+}
+
+#[tokio::test]
+async fn test_api_key_is_limited_to_its_scopes() {
+    let key = api_key::generate().key;
+    let test_db = TestDb::new(vec![
+        vec![api_key_row(&key, "user:read")],
+        vec![user_row(2, 1, "")],
+    ])
+    .await;
+
+    let (status, _) = send(&test_db, Request::delete("/api/delete_user_by_id/3"), &key).await;
+
+    assert_eq!(status, StatusCode::FORBIDDEN);
+}
+
+#[tokio::test]
+async fn test_api_key_can_not_create_api_keys() {
+    let key = api_key::generate().key;
+    let mut results = vec![
+        vec![api_key_row(&key, "api_key:manage")],
+        vec![user_row(2, 1, "")],
+    ];
+    results.extend(authorities(&["api_key:manage"]));
+    let test_db = TestDb::new(results).await;
+
+    let (status, _) = send(&test_db, Request::post("/api/create_api_key"), &key).await;
+
+    assert_eq!(status, StatusCode::FORBIDDEN);
+    // nothing is written besides recording the key's use
+    assert!(test_db
+        .statements()
+        .iter()
+        .skip(1)
+        .all(|sql| sql.starts_with("SELECT")));
+}
@@ -0,0 +1,214 @@
+//! Helpers shared by the integration tests.
+#![allow(dead_code)]
+
+use axum::body::Body;
+use axum::Router;
+use axum_template::auth::{get_jwt, Principal};
+use axum_template::logger::LogFilter;
+use axum_template::login_guard::LoginGuard;
+use axum_template::mailer::LogMailer;
+use axum_template::metrics::Metrics;
+use axum_template::oidc::OidcProviders;
+use axum_template::revocation::MemoryRevocationStore;
+use axum_template::{api, application, database};
+use http::{header, Response, StatusCode};
+use http_body_util::BodyExt;
+use sea_orm::prelude::DateTimeWithTimeZone;
+use sea_orm::{
+    Database, DatabaseBackend, DatabaseConnection, DbErr, ProxyDatabaseTrait, ProxyExecResult,
+    ProxyRow, Statement, Value,
+};
+use std::collections::{BTreeMap, VecDeque};
+use std::io;
+use std::sync::{Arc, Mutex};
+use tower::ServiceExt;
+
+/// Database stand-in that records every statement and answers queries with canned rows.
+#[derive(Debug, Default)]
+struct Recorder {
+    statements: Mutex<Vec<String>>,
+    results: Mutex<VecDeque<Vec<ProxyRow>>>,
+    rows_affected: Mutex<VecDeque<u64>>,
+}
+
+/// Shared handle given to the connection, the test keeps another one to inspect the statements.
+#[derive(Debug)]
+struct RecorderHandle(Arc<Recorder>);
+
+pub struct TestDb {
+    pub db: DatabaseConnection,
+    recorder: Arc<Recorder>,
+}
+
+impl TestDb {
+    /// Every query pops the next entry of `results`, or gets no rows once they run out.
+    pub async fn new(results: Vec<Vec<ProxyRow>>) -> Self {
+        let recorder = Arc::new(Recorder {
+            results: Mutex::new(results.into()),
+            ..Default::default()
+        });
+        let proxy: Box<dyn ProxyDatabaseTrait> = Box::new(RecorderHandle(recorder.clone()));
+        let db = Database::connect_proxy(DatabaseBackend::Postgres, Arc::new(proxy))
+            .await
+            .unwrap();
+
+        Self { db, recorder }
+    }
+
+    pub fn statements(&self) -> Vec<String> {
+        self.recorder.statements.lock().unwrap().clone()
+    }
+
+    /// Every executed statement pops the next entry of `rows_affected`, or affects no
+    /// rows once they run out.
+    pub fn set_rows_affected(&self, rows_affected: Vec<u64>) {
+        *self.recorder.rows_affected.lock().unwrap() = rows_affected.into();
+    }
+}
+
+#[async_trait::async_trait]
+impl ProxyDatabaseTrait for RecorderHandle {
+    async fn query(&self, statement: Statement) -> Result<Vec<ProxyRow>, DbErr> {
+        self.0
+            .statements
+            .lock()
+            .unwrap()
+            .push(statement.to_string());
+        Ok(self
+            .0
+            .results
+            .lock()
+            .unwrap()
+            .pop_front()
+            .unwrap_or_default())
+    }
+
+    async fn execute(&self, statement: Statement) -> Result<ProxyExecResult, DbErr> {
+        self.0
+            .statements
+            .lock()
+            .unwrap()
+            .push(statement.to_string());
+        let rows_affected = self
+            .0
+            .rows_affected
+            .lock()
+            .unwrap()
+            .pop_front()
+            .unwrap_or_default();
+        Ok(ProxyExecResult::new(0, rows_affected))
+    }
+}
+
+/// A `users` row.
+pub fn user_row(id: i64, ws_id: i64, password_hash: &str) -> ProxyRow {
+    row([
+        ("id", id.into()),
+        ("fullname", "Alice".into()),
+        ("gender", Option::<String>::None.into()),
+        ("email", format!("alice{}@none.co", id).into()),
+        ("password_hash", password_hash.into()),
+        ("create_at", Option::<DateTimeWithTimeZone>::None.into()),
+        ("ws_id", ws_id.into()),
+        ("email_verified", true.into()),
+    ])
+}
+
+pub fn row<const N: usize>(values: [(&str, Value); N]) -> ProxyRow {
+    let values: BTreeMap<String, Value> = values
+        .into_iter()
+        .map(|(column, value)| (column.to_string(), value))
+        .collect();
+
+    ProxyRow::new(values)
+}
+
+/// Application state backed by `db`, with in-memory stores, no OIDC providers and
+/// bearer token sessions.
+pub fn state(db: &DatabaseConnection) -> application::AppState {
+    let mut db = db.clone();
+    let metrics = Arc::new(Metrics::new(&db));
+    database::instrument(&mut db, metrics.clone());
+
+    application::AppState::new(
+        db,
+        Arc::new(MemoryRevocationStore::new()),
+        Arc::new(LogMailer::new("no-reply@none.co")),
+        Arc::new(LoginGuard::new(&Default::default())),
+        Arc::new(OidcProviders::default()),
+        None,
+        metrics,
+        LogFilter::default(),
+    )
+}
+
+/// The application with all middlewares, backed by `db`.
+pub async fn app(db: &DatabaseConnection) -> Router {
+    application::build_app(state(db), api::build_routes().await)
+}
+
+/// The caller of `token`: Bobby, `id = 1` in workspace `1`, granted `permissions`.
+pub fn principal(permissions: &[&str]) -> Principal {
+    Principal {
+        id: 1,
+        name: "Bobby".to_string(),
+        email: "bobby@none.co".to_string(),
+        ws_id: Some(1),
+        roles: vec!["admin".to_string()],
+        permissions: permissions.iter().map(|p| p.to_string()).collect(),
+    }
+}
+
+/// An access token of `principal(permissions)`.
+pub fn token(permissions: &[&str]) -> String {
+    get_jwt()
+        .encode(&principal(permissions), "session")
+        .unwrap()
+}
+
+/// Sends a JSON request with the access token of `principal(permissions)`.
+pub async fn send(
+    app: &Router,
+    request: http::request::Builder,
+    permissions: &[&str],
+    body: Option<serde_json::Value>,
+) -> (StatusCode, serde_json::Value) {
+    let request = request
+        .header(
+            header::AUTHORIZATION,
+            format!("Bearer {}", token(permissions)),
+        )
+        .header(header::CONTENT_TYPE, "application/json")
+        .body(body.map_or_else(Body::empty, |body| Body::from(body.to_string())))
+        .unwrap();
+    read_json(app.clone().oneshot(request).await.unwrap()).await
+}
+
+/// Splits a response into its status and JSON body.
+pub async fn read_json(response: Response<Body>) -> (StatusCode, serde_json::Value) {
+    let status = response.status();
+    let bytes = response.into_body().collect().await.unwrap().to_bytes();
+
+    (status, serde_json::from_slice(&bytes).unwrap())
+}
+
+/// Log output written to memory, e.g. `fmt().with_writer(move || captured.clone())`.
+#[derive(Clone, Default)]
+pub struct CapturedLogs(Arc<Mutex<Vec<u8>>>);
+
+impl CapturedLogs {
+    pub fn output(&self) -> String {
+        String::from_utf8(self.0.lock().unwrap().clone()).unwrap()
+    }
+}
+
+impl io::Write for CapturedLogs {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.0.lock().unwrap().extend_from_slice(buf);
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
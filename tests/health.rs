@@ -0,0 +1,82 @@
+mod common;
+
+use async_trait::async_trait;
+use axum::body::Body;
+use axum::Router;
+use axum_template::health::HealthCheck;
+use axum_template::{api, application};
+use common::{read_json, TestDb};
+use http::{Request, StatusCode};
+use std::sync::Arc;
+use tower::ServiceExt;
+
+/// A dependency that is down.
+struct Unreachable;
+
+#[async_trait]
+impl HealthCheck for Unreachable {
+    fn name(&self) -> &str {
+        "search"
+    }
+
+    async fn check(&self) -> anyhow::Result<Option<serde_json::Value>> {
+        anyhow::bail!("connection refused")
+    }
+}
+
+async fn app(test_db: &TestDb, ready: bool, checks: Vec<Arc<dyn HealthCheck>>) -> Router {
+    let mut state = common::state(&test_db.db);
+    state.readiness().set_ready(ready);
+    for check in checks {
+        state.register_health_check(check);
+    }
+    application::build_app(state, api::build_routes().await)
+}
+
+async fn get(app: Router, path: &str) -> (StatusCode, serde_json::Value) {
+    let request = Request::get(path).body(Body::empty()).unwrap();
+    read_json(app.oneshot(request).await.unwrap()).await
+}
+
+#[tokio::test]
+async fn test_live_does_not_touch_dependencies() {
+    let test_db = TestDb::new(vec![]).await;
+
+    let (status, body) = get(app(&test_db, false, vec![]).await, "/health/live").await;
+
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(body["status"], "up");
+    assert!(test_db.statements().is_empty());
+}
+
+#[tokio::test]
+async fn test_ready_reports_every_check_with_its_latency() {
+    let test_db = TestDb::new(vec![]).await;
+
+    let (status, body) = get(app(&test_db, true, vec![]).await, "/health/ready").await;
+    assert_eq!(status, StatusCode::OK, "{}", body);
+    assert_eq!(body["status"], "up");
+    assert_eq!(body["checks"]["database"]["status"], "up");
+    assert!(body["checks"]["database"]["latency_ms"].is_number());
+
+    let (status, body) = get(
+        app(&test_db, true, vec![Arc::new(Unreachable)]).await,
+        "/health/ready",
+    )
+    .await;
+    assert_eq!(status, StatusCode::SERVICE_UNAVAILABLE);
+    assert_eq!(body["status"], "down");
+    assert_eq!(body["checks"]["database"]["status"], "up");
+    assert_eq!(body["checks"]["search"]["status"], "down");
+    assert_eq!(body["checks"]["search"]["error"], "connection refused");
+}
+
+#[tokio::test]
+async fn test_not_ready_while_draining() {
+    let test_db = TestDb::new(vec![]).await;
+
+    let (status, body) = get(app(&test_db, false, vec![]).await, "/health/ready").await;
+
+    assert_eq!(status, StatusCode::SERVICE_UNAVAILABLE);
+    assert_eq!(body["checks"]["server"]["status"], "down");
+}
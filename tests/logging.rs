@@ -0,0 +1,104 @@
+mod common;
+
+use axum_template::logger::LogFilter;
+use axum_template::{api, application};
+use common::{send, CapturedLogs, TestDb};
+use http::{Request, StatusCode};
+use tracing_subscriber::layer::SubscriberExt;
+use tracing_subscriber::{EnvFilter, Registry};
+
+#[tokio::test]
+async fn test_log_filter_is_changed_at_runtime() {
+    let test_db = TestDb::new(vec![]).await;
+    let (_layer, log_filter) = LogFilter::new(EnvFilter::new("info"));
+    let mut state = common::state(&test_db.db);
+    state.log_filter = log_filter.clone();
+    let app = application::build_app(state, api::build_routes().await);
+    let admin = ["logging:manage"];
+
+    let (status, body) = send(&app, Request::get("/api/get_log_filter"), &admin, None).await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(body["data"]["filter"], "info");
+
+    let (status, body) = send(
+        &app,
+        Request::put("/api/update_log_filter"),
+        &admin,
+        Some(serde_json::json!({ "filter": "warn,axum_template::auth=debug" })),
+    )
+    .await;
+    assert_eq!(status, StatusCode::OK, "{}", body);
+    assert_eq!(body["data"]["filter"], "axum_template::auth=debug,warn");
+    assert_eq!(
+        log_filter.current().as_deref(),
+        Some("axum_template::auth=debug,warn")
+    );
+
+    let (status, _) = send(
+        &app,
+        Request::put("/api/update_log_filter"),
+        &admin,
+        Some(serde_json::json!({ "filter": "axum_template=loud" })),
+    )
+    .await;
+    assert_eq!(status, StatusCode::BAD_REQUEST);
+    assert_eq!(
+        log_filter.current().as_deref(),
+        Some("axum_template::auth=debug,warn")
+    );
+}
+
+#[tokio::test]
+async fn test_changing_the_log_filter_requires_the_permission() {
+    let test_db = TestDb::new(vec![]).await;
+    let (_layer, log_filter) = LogFilter::new(EnvFilter::new("info"));
+    let mut state = common::state(&test_db.db);
+    state.log_filter = log_filter.clone();
+    let app = application::build_app(state, api::build_routes().await);
+
+    let (status, _) = send(
+        &app,
+        Request::put("/api/update_log_filter"),
+        &["user:read"],
+        Some(serde_json::json!({ "filter": "trace" })),
+    )
+    .await;
+
+    assert_eq!(status, StatusCode::FORBIDDEN);
+    assert_eq!(log_filter.current().as_deref(), Some("info"));
+}
+
+#[tokio::test]
+async fn test_silencing_the_logs_is_still_recorded() {
+    let test_db = TestDb::new(vec![]).await;
+    let (filter_layer, log_filter) = LogFilter::new(EnvFilter::new("info"));
+    let captured = CapturedLogs::default();
+    let writer = captured.clone();
+    let subscriber = Registry::default().with(filter_layer).with(
+        tracing_subscriber::fmt::layer()
+            .with_writer(move || writer.clone())
+            .with_ansi(false),
+    );
+    let _guard = tracing::subscriber::set_default(subscriber);
+
+    let mut state = common::state(&test_db.db);
+    state.log_filter = log_filter.clone();
+    let app = application::build_app(state, api::build_routes().await);
+
+    let (status, body) = send(
+        &app,
+        Request::put("/api/update_log_filter"),
+        &["logging:manage"],
+        Some(serde_json::json!({ "filter": "off" })),
+    )
+    .await;
+    assert_eq!(status, StatusCode::OK, "{}", body);
+    assert_eq!(log_filter.current().as_deref(), Some("off"));
+
+    let output = captured.output();
+    assert!(
+        output.contains("changes the log filter from info to off"),
+        "{}",
+        output
+    );
+}
@@ -0,0 +1,102 @@
+mod common;
+
+use axum::body::Body;
+use axum::extract::ConnectInfo;
+use axum::Router;
+use axum_template::common::hash_password;
+use axum_template::error::INVALID_CREDENTIALS;
+use common::{app, read_json, user_row, TestDb};
+use http::{header, Request, StatusCode};
+use std::net::SocketAddr;
+use tower::ServiceExt;
+
+fn login_request(account: &str, password: &str) -> Request<Body> {
+    let body = serde_json::json!({ "account": account, "password": password });
+    let mut request = Request::post("/auth/login")
+        .header(header::CONTENT_TYPE, "application/json")
+        .body(Body::from(body.to_string()))
+        .unwrap();
+    request
+        .extensions_mut()
+        .insert(ConnectInfo(SocketAddr::from(([10, 0, 0, 1], 40000))));
+    request
+}
+
+async fn login(app: &Router, account: &str, password: &str) -> (StatusCode, serde_json::Value) {
+    let response = app
+        .clone()
+        .oneshot(login_request(account, password))
+        .await
+        .unwrap();
+    read_json(response).await
+}
+
+#[tokio::test]
+async fn test_unknown_account_and_wrong_password_look_the_same() {
+    let unknown_db = TestDb::new(vec![]).await;
+    let mut unknown = login(&app(&unknown_db.db).await, "nobody@none.co", "a1234567").await;
+
+    let password_hash = hash_password("a1234567").await.unwrap();
+    let known_db = TestDb::new(vec![vec![user_row(2, 1, &password_hash)]]).await;
+    let mut wrong_password = login(&app(&known_db.db).await, "alice2@none.co", "b1234567").await;
+
+    // only the ids of the requests differ
+    for (_, body) in [&mut unknown, &mut wrong_password] {
+        assert!(body["request_id"].is_string());
+        body.as_object_mut().unwrap().remove("request_id");
+    }
+    assert_eq!(unknown.0, StatusCode::UNAUTHORIZED);
+    assert_eq!(unknown, wrong_password);
+    assert!(unknown.1["msg"]
+        .as_str()
+        .unwrap()
+        .ends_with(INVALID_CREDENTIALS));
+}
+
+#[tokio::test]
+async fn test_unverified_email_can_not_sign_in() {
+    let password_hash = hash_password("a1234567").await.unwrap();
+    let mut unverified = user_row(2, 1, &password_hash);
+    unverified
+        .values
+        .insert("email_verified".to_string(), false.into());
+    let test_db = TestDb::new(vec![vec![unverified]]).await;
+
+    let (status, body) = login(&app(&test_db.db).await, "alice2@none.co", "a1234567").await;
+
+    assert_eq!(status, StatusCode::UNAUTHORIZED);
+    assert!(body["msg"].as_str().unwrap().ends_with(INVALID_CREDENTIALS));
+    // no session is created
+    assert_eq!(test_db.statements().len(), 1);
+}
+
+#[tokio::test]
+async fn test_repeated_failures_lock_the_account() {
+    let test_db = TestDb::new(vec![]).await;
+    let app = app(&test_db.db).await;
+
+    for _ in 0..5 {
+        let (status, _) = login(&app, "nobody@none.co", "a1234567").await;
+        assert_eq!(status, StatusCode::UNAUTHORIZED);
+    }
+
+    let response = app
+        .clone()
+        .oneshot(login_request("nobody@none.co", "a1234567"))
+        .await
+        .unwrap();
+    assert_eq!(response.status(), StatusCode::TOO_MANY_REQUESTS);
+    assert_eq!(response.headers()[header::RETRY_AFTER], "60");
+}
+
+#[tokio::test]
+async fn test_oversized_login_body_is_rejected() {
+    let test_db = TestDb::new(vec![]).await;
+    let password = "a".repeat(32 * 1024);
+
+    let (status, body) = login(&app(&test_db.db).await, "nobody@none.co", &password).await;
+
+    assert_eq!(status, StatusCode::PAYLOAD_TOO_LARGE);
+    assert_eq!(body["code"], 0);
+    assert!(test_db.statements().is_empty());
+}
@@ -0,0 +1,60 @@
+mod common;
+
+use axum::body::Body;
+use axum::Router;
+use common::TestDb;
+use http::{Request, StatusCode};
+use http_body_util::BodyExt;
+use tower::ServiceExt;
+
+async fn call(app: &Router, method: &str, path: &str) -> (StatusCode, String) {
+    let request = Request::builder()
+        .method(method)
+        .uri(path)
+        .body(Body::empty())
+        .unwrap();
+    let response = app.clone().oneshot(request).await.unwrap();
+    let status = response.status();
+    let bytes = response.into_body().collect().await.unwrap().to_bytes();
+
+    (status, String::from_utf8(bytes.to_vec()).unwrap())
+}
+
+#[tokio::test]
+async fn test_requests_are_counted_by_route_template() {
+    let test_db = TestDb::new(vec![]).await;
+    let app = common::app(&test_db.db).await;
+
+    call(&app, "GET", "/health/live").await;
+    call(&app, "GET", "/health/live").await;
+    assert_eq!(
+        call(&app, "DELETE", "/api/delete_user_by_id/7").await.0,
+        StatusCode::UNAUTHORIZED
+    );
+    assert_eq!(
+        call(&app, "DELETE", "/api/delete_user_by_id/8").await.0,
+        StatusCode::UNAUTHORIZED
+    );
+    assert_eq!(
+        call(&app, "GET", "/no/such/path").await.0,
+        StatusCode::NOT_FOUND
+    );
+
+    let (status, body) = call(&app, "GET", "/metrics").await;
+    assert_eq!(status, StatusCode::OK);
+    assert!(
+        body.contains(r#"http_requests_total{method="GET",route="/health/live",status="200"} 2"#),
+        "{}",
+        body
+    );
+    assert!(body.contains(
+        r#"http_requests_total{method="DELETE",route="/api/delete_user_by_id/{id}",status="401"} 2"#
+    ));
+    assert!(body.contains(r#"http_requests_total{method="GET",route="unmatched",status="404"} 1"#));
+    assert!(body.contains(
+        r#"http_request_duration_seconds_count{method="GET",route="/health/live",status="200"} 2"#
+    ));
+    // the scrape itself is still being handled
+    assert!(body.contains(r#"http_requests_in_flight{method="GET",route="/metrics"} 1"#));
+    assert!(body.contains(r#"http_requests_in_flight{method="GET",route="/health/live"} 0"#));
+}
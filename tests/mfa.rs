@@ -0,0 +1,207 @@
+mod common;
+
+use axum::body::Body;
+use axum::extract::ConnectInfo;
+use axum::Router;
+use axum_template::auth::get_jwt;
+use axum_template::common::hash_password;
+use axum_template::config::AppConfig;
+use axum_template::login_guard::LoginGuard;
+use axum_template::{api, application, mfa};
+use common::{app, read_json, row, user_row, TestDb};
+use http::{header, Request, StatusCode};
+use sea_orm::prelude::DateTimeWithTimeZone;
+use sea_orm::ProxyRow;
+use std::net::{IpAddr, SocketAddr};
+use std::sync::Arc;
+use tower::ServiceExt;
+
+const SECRET: &str = "JBSWY3DPEHPK3PXPJBSWY3DPEHPK3PXP";
+
+/// Enabled second factor of user `2`, whose codes up to `last_used_step` are used.
+fn user_mfa_row(last_used_step: i64) -> ProxyRow {
+    row([
+        ("user_id", 2i64.into()),
+        ("secret", SECRET.into()),
+        ("enabled", true.into()),
+        ("last_used_step", last_used_step.into()),
+        ("create_at", Option::<DateTimeWithTimeZone>::None.into()),
+    ])
+}
+
+fn refresh_token_row() -> ProxyRow {
+    row([
+        ("id", 1i64.into()),
+        ("jti", "jti".into()),
+        ("family_id", "family".into()),
+        ("user_id", 2i64.into()),
+        ("expires_at", DateTimeWithTimeZone::default().into()),
+        ("revoked", false.into()),
+        ("replaced_by", Option::<String>::None.into()),
+        ("create_at", Option::<DateTimeWithTimeZone>::None.into()),
+    ])
+}
+
+fn current_code() -> String {
+    mfa::totp(SECRET, AppConfig::get().mfa().issuer(), "alice2@none.co")
+        .unwrap()
+        .generate_current()
+        .unwrap()
+}
+
+async fn post(app: &Router, uri: &str, body: serde_json::Value) -> (StatusCode, serde_json::Value) {
+    let mut request = Request::post(uri)
+        .header(header::CONTENT_TYPE, "application/json")
+        .body(Body::from(body.to_string()))
+        .unwrap();
+    request
+        .extensions_mut()
+        .insert(ConnectInfo(SocketAddr::from(([10, 0, 0, 1], 40000))));
+    read_json(app.clone().oneshot(request).await.unwrap()).await
+}
+
+async fn verify(app: &Router, mfa_token: &str, code: &str) -> (StatusCode, serde_json::Value) {
+    let body = serde_json::json!({ "mfa_token": mfa_token, "code": code });
+    post(app, "/auth/mfa/verify", body).await
+}
+
+#[tokio::test]
+async fn test_password_login_with_second_factor_returns_pending_token() {
+    let password_hash = hash_password("a1234567").await.unwrap();
+    let test_db = TestDb::new(vec![
+        vec![user_row(2, 1, &password_hash)],
+        vec![user_mfa_row(0)],
+    ])
+    .await;
+    let app = app(&test_db.db).await;
+
+    let body = serde_json::json!({ "account": "alice2@none.co", "password": "a1234567" });
+    let (status, body) = post(&app, "/auth/login", body).await;
+
+    assert_eq!(status, StatusCode::OK, "{}", body);
+    assert_eq!(body["msg"], "second factor required");
+    assert_eq!(body["data"]["mfa_required"], true);
+    assert!(body["data"].get("access_token").is_none());
+    let mfa_token = body["data"]["mfa_token"].as_str().unwrap();
+    let claims = get_jwt().decode_mfa_pending(mfa_token).unwrap();
+    assert_eq!(claims.sub, "2");
+}
+
+#[tokio::test]
+async fn test_password_alone_does_not_reset_failed_attempts() {
+    let password_hash = hash_password("a1234567").await.unwrap();
+    let test_db = TestDb::new(vec![
+        vec![user_row(2, 1, &password_hash)],
+        vec![user_mfa_row(0)],
+    ])
+    .await;
+    let config = serde_json::from_value(serde_json::json!({ "max_failures_per_account": 2 }));
+    let login_guard = Arc::new(LoginGuard::new(&config.unwrap()));
+    let mut state = common::state(&test_db.db);
+    state.login_guard = login_guard.clone();
+    let app = application::build_app(state, api::build_routes().await);
+    let other_ip = IpAddr::from([10, 0, 0, 2]);
+    login_guard.record_failure("alice2@none.co", other_ip);
+
+    let body = serde_json::json!({ "account": "alice2@none.co", "password": "a1234567" });
+    let (status, body) = post(&app, "/auth/login", body).await;
+    assert_eq!(status, StatusCode::OK, "{}", body);
+    assert_eq!(body["data"]["mfa_required"], true);
+
+    // the failure before the password still counts until the second factor passes
+    login_guard.record_failure("alice2@none.co", other_ip);
+    assert!(login_guard.check("alice2@none.co", other_ip).is_err());
+}
+
+#[tokio::test]
+async fn test_wrong_code_looks_like_a_wrong_token() {
+    let test_db = TestDb::new(vec![vec![user_row(2, 1, "")], vec![user_mfa_row(0)]]).await;
+    let app = app(&test_db.db).await;
+    let mfa_token = get_jwt().encode_mfa_pending(2, "family").unwrap();
+
+    let (status, wrong_code) = verify(&app, &mfa_token, "not-a-code").await;
+    assert_eq!(status, StatusCode::UNAUTHORIZED);
+    // the recovery code lookup is the last statement, no token is issued
+    assert!(test_db.statements().last().unwrap().starts_with("UPDATE"));
+
+    let (status, wrong_token) = verify(&app, "not-a-token", "123456").await;
+    assert_eq!(status, StatusCode::UNAUTHORIZED);
+    assert_eq!(wrong_code["msg"], wrong_token["msg"]);
+}
+
+#[tokio::test]
+async fn test_replayed_code_is_rejected() {
+    let code = current_code();
+    // every step up to the next one is used already
+    let last_used_step = (jsonwebtoken::get_current_timestamp() / 30 + 1) as i64;
+    let test_db = TestDb::new(vec![
+        vec![user_row(2, 1, "")],
+        vec![user_mfa_row(last_used_step)],
+    ])
+    .await;
+    let app = app(&test_db.db).await;
+    let mfa_token = get_jwt().encode_mfa_pending(2, "family").unwrap();
+
+    let (status, body) = verify(&app, &mfa_token, &code).await;
+
+    assert_eq!(status, StatusCode::UNAUTHORIZED, "{}", body);
+    let statements = test_db.statements();
+    assert!(statements
+        .iter()
+        .all(|sql| !sql.contains("\"last_used_step\" =")));
+}
+
+#[tokio::test]
+async fn test_totp_code_signs_in() {
+    let test_db = TestDb::new(vec![
+        vec![user_row(2, 1, "")],
+        vec![user_mfa_row(0)],
+        // no roles
+        vec![],
+        vec![refresh_token_row()],
+    ])
+    .await;
+    // the step of the code is recorded
+    test_db.set_rows_affected(vec![1]);
+    let app = app(&test_db.db).await;
+    let mfa_token = get_jwt().encode_mfa_pending(2, "family").unwrap();
+
+    let (status, body) = verify(&app, &mfa_token, &current_code()).await;
+
+    assert_eq!(status, StatusCode::OK, "{}", body);
+    assert!(body["data"]["access_token"].is_string());
+    assert!(test_db
+        .statements()
+        .iter()
+        .any(|sql| sql.contains("\"last_used_step\" <")));
+}
+
+#[tokio::test]
+async fn test_recovery_code_signs_in_once_per_pending_token() {
+    let test_db = TestDb::new(vec![
+        vec![user_row(2, 1, "")],
+        vec![user_mfa_row(0)],
+        // no roles
+        vec![],
+        vec![refresh_token_row()],
+    ])
+    .await;
+    // the recovery code is marked as used
+    test_db.set_rows_affected(vec![1]);
+    let app = app(&test_db.db).await;
+    let mfa_token = get_jwt().encode_mfa_pending(2, "family").unwrap();
+
+    let (status, body) = verify(&app, &mfa_token, "abcde-12345").await;
+    assert_eq!(status, StatusCode::OK, "{}", body);
+    assert!(body["data"]["access_token"].is_string());
+    let recovery_code = mfa::hash_recovery_code("abcde-12345");
+    assert!(test_db
+        .statements()
+        .iter()
+        .any(|sql| sql.contains(&recovery_code)));
+
+    let statements = test_db.statements().len();
+    let (status, _) = verify(&app, &mfa_token, "fghij-67890").await;
+    assert_eq!(status, StatusCode::UNAUTHORIZED);
+    assert_eq!(test_db.statements().len(), statements);
+}
@@ -0,0 +1,303 @@
+mod common;
+
+use axum::body::Body;
+use axum::extract::State;
+use axum::routing::{get, post};
+use axum::{Form, Json, Router};
+use axum_template::auth::get_jwt;
+use axum_template::oidc::{code_challenge, OidcProvider, OidcProviders};
+use axum_template::{api, application};
+use base64::engine::general_purpose::URL_SAFE_NO_PAD;
+use base64::Engine;
+use common::{read_json, row, user_row, TestDb};
+use http::{header, Request, StatusCode};
+use jsonwebtoken::{encode, get_current_timestamp, Algorithm, EncodingKey, Header};
+use p256::elliptic_curve::sec1::ToEncodedPoint;
+use p256::pkcs8::DecodePublicKey;
+use sea_orm::prelude::DateTimeWithTimeZone;
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+use tower::ServiceExt;
+use url::Url;
+
+const CLIENT_ID: &str = "axum-app";
+const CLIENT_SECRET: &str = "s3cret";
+const SIGNING_KID: &str = "idp-1";
+const EC_PRIVATE: &[u8] = include_bytes!("fixtures/jwt/ec.pem");
+const EC_PUBLIC: &str = include_str!("fixtures/jwt/ec.pub.pem");
+
+/// A minimal OpenID Connect provider: discovery, keys and the token endpoint.
+#[derive(Default)]
+struct MockIdp {
+    issuer: String,
+    /// `code_challenge` and `nonce` of the authorization request the code was issued for.
+    authorization: Mutex<Option<(String, String)>>,
+    /// Signs ID tokens with another nonce than requested, like a replayed token.
+    wrong_nonce: bool,
+}
+
+async fn start_idp(wrong_nonce: bool) -> Arc<MockIdp> {
+    let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let idp = Arc::new(MockIdp {
+        issuer: format!("http://{}", listener.local_addr().unwrap()),
+        wrong_nonce,
+        ..Default::default()
+    });
+
+    let router = Router::new()
+        .route("/.well-known/openid-configuration", get(discovery))
+        .route("/jwks", get(jwks))
+        .route("/token", post(token))
+        .with_state(idp.clone());
+    tokio::spawn(async move { axum::serve(listener, router).await.unwrap() });
+
+    idp
+}
+
+async fn discovery(State(idp): State<Arc<MockIdp>>) -> Json<serde_json::Value> {
+    Json(serde_json::json!({
+        "issuer": idp.issuer,
+        "authorization_endpoint": format!("{}/authorize", idp.issuer),
+        "token_endpoint": format!("{}/token", idp.issuer),
+        "jwks_uri": format!("{}/jwks", idp.issuer),
+    }))
+}
+
+async fn jwks() -> Json<serde_json::Value> {
+    let key = p256::PublicKey::from_public_key_pem(EC_PUBLIC).unwrap();
+    let point = key.to_encoded_point(false);
+
+    Json(serde_json::json!({
+        "keys": [{
+            "kty": "EC",
+            "crv": "P-256",
+            "kid": SIGNING_KID,
+            "alg": "ES256",
+            "use": "sig",
+            "x": URL_SAFE_NO_PAD.encode(point.x().unwrap()),
+            "y": URL_SAFE_NO_PAD.encode(point.y().unwrap()),
+        }],
+    }))
+}
+
+async fn token(
+    State(idp): State<Arc<MockIdp>>,
+    Form(form): Form<HashMap<String, String>>,
+) -> Result<Json<serde_json::Value>, StatusCode> {
+    let (challenge, nonce) = idp.authorization.lock().unwrap().clone().unwrap();
+    let valid = form["grant_type"] == "authorization_code"
+        && form["code"] == "good-code"
+        && form["client_id"] == CLIENT_ID
+        && form["client_secret"] == CLIENT_SECRET
+        && code_challenge(&form["code_verifier"]) == challenge;
+    if !valid {
+        return Err(StatusCode::BAD_REQUEST);
+    }
+
+    let now = get_current_timestamp();
+    let claims = serde_json::json!({
+        "iss": idp.issuer,
+        "aud": CLIENT_ID,
+        "sub": "idp-user-42",
+        "email": "alice2@none.co",
+        "email_verified": true,
+        "name": "Alice",
+        "nonce": if idp.wrong_nonce { "another-nonce".to_string() } else { nonce },
+        "iat": now,
+        "exp": now + 300,
+    });
+    let mut header = Header::new(Algorithm::ES256);
+    header.kid = Some(SIGNING_KID.to_string());
+    let id_token = encode(
+        &header,
+        &claims,
+        &EncodingKey::from_ec_pem(EC_PRIVATE).unwrap(),
+    )
+    .unwrap();
+
+    Ok(Json(serde_json::json!({
+        "access_token": "opaque",
+        "token_type": "Bearer",
+        "id_token": id_token,
+    })))
+}
+
+async fn app(test_db: &TestDb, idp: &MockIdp) -> Router {
+    let config = serde_json::from_value(serde_json::json!({
+        "issuer": idp.issuer,
+        "client_id": CLIENT_ID,
+        "client_secret": CLIENT_SECRET,
+    }))
+    .unwrap();
+    let provider = OidcProvider::new("mock", &config, "http://127.0.0.1:3005").unwrap();
+
+    let mut state = common::state(&test_db.db);
+    state.oidc = Arc::new(OidcProviders::new(vec![provider]));
+    application::build_app(state, api::build_routes().await)
+}
+
+/// Starts the login and returns the `state` sent to the provider and the state cookie.
+async fn start_login(app: &Router, idp: &MockIdp) -> (String, String) {
+    let response = app
+        .clone()
+        .oneshot(
+            Request::get("/auth/oidc/mock/login")
+                .body(Body::empty())
+                .unwrap(),
+        )
+        .await
+        .unwrap();
+    assert_eq!(response.status(), StatusCode::SEE_OTHER);
+
+    let location = Url::parse(response.headers()[header::LOCATION].to_str().unwrap()).unwrap();
+    assert!(location
+        .as_str()
+        .starts_with(&format!("{}/authorize?", idp.issuer)));
+    let query: HashMap<_, _> = location.query_pairs().into_owned().collect();
+    assert_eq!(query["client_id"], CLIENT_ID);
+    assert_eq!(query["code_challenge_method"], "S256");
+    assert_eq!(
+        query["redirect_uri"],
+        "http://127.0.0.1:3005/auth/oidc/mock/callback"
+    );
+    *idp.authorization.lock().unwrap() =
+        Some((query["code_challenge"].clone(), query["nonce"].clone()));
+
+    let cookie = response.headers()[header::SET_COOKIE].to_str().unwrap();
+    assert!(cookie.contains("HttpOnly"));
+    let cookie = cookie.split(';').next().unwrap().to_string();
+
+    (query["state"].clone(), cookie)
+}
+
+async fn callback(app: &Router, state: &str, cookie: &str) -> (StatusCode, serde_json::Value) {
+    let request = Request::get(format!(
+        "/auth/oidc/mock/callback?code=good-code&state={}",
+        state
+    ))
+    .header(header::COOKIE, cookie)
+    .body(Body::empty())
+    .unwrap();
+
+    read_json(app.clone().oneshot(request).await.unwrap()).await
+}
+
+fn refresh_token_row() -> sea_orm::ProxyRow {
+    row([
+        ("id", 1i64.into()),
+        ("jti", "jti".into()),
+        ("family_id", "family".into()),
+        ("user_id", 2i64.into()),
+        ("expires_at", DateTimeWithTimeZone::default().into()),
+        ("revoked", false.into()),
+        ("replaced_by", Option::<String>::None.into()),
+        ("create_at", Option::<DateTimeWithTimeZone>::None.into()),
+    ])
+}
+
+#[tokio::test]
+async fn test_first_login_links_the_user_with_the_verified_email() {
+    let idp = start_idp(false).await;
+    let identity = row([
+        ("id", 1i64.into()),
+        ("user_id", 2i64.into()),
+        ("provider", "mock".into()),
+        ("subject", "idp-user-42".into()),
+        ("email", Some("alice2@none.co".to_string()).into()),
+        ("create_at", Option::<DateTimeWithTimeZone>::None.into()),
+    ]);
+    let test_db = TestDb::new(vec![
+        // no linked identity yet, then the user with the same email
+        vec![],
+        vec![user_row(2, 1, "")],
+        vec![identity],
+        // no second factor, no roles
+        vec![],
+        vec![],
+        vec![refresh_token_row()],
+    ])
+    .await;
+    let app = app(&test_db, &idp).await;
+
+    let (state, cookie) = start_login(&app, &idp).await;
+    let (status, body) = callback(&app, &state, &cookie).await;
+
+    assert_eq!(status, StatusCode::OK, "{}", body);
+    let access_token = body["data"]["access_token"].as_str().unwrap();
+    assert_eq!(get_jwt().decode(access_token).unwrap().id, 2);
+    assert!(test_db.statements().iter().any(|sql| {
+        sql.starts_with("INSERT INTO \"public\".\"user_identity\"") && sql.contains("'idp-user-42'")
+    }));
+}
+
+#[tokio::test]
+async fn test_callback_with_another_state_is_rejected() {
+    let idp = start_idp(false).await;
+    let test_db = TestDb::new(vec![]).await;
+    let app = app(&test_db, &idp).await;
+
+    let (_, cookie) = start_login(&app, &idp).await;
+    let (status, _) = callback(&app, "forged-state", &cookie).await;
+
+    assert_eq!(status, StatusCode::UNAUTHORIZED);
+    assert!(test_db.statements().is_empty());
+}
+
+#[tokio::test]
+async fn test_id_token_with_another_nonce_is_rejected() {
+    let idp = start_idp(true).await;
+    let test_db = TestDb::new(vec![]).await;
+    let app = app(&test_db, &idp).await;
+
+    let (state, cookie) = start_login(&app, &idp).await;
+    let (status, _) = callback(&app, &state, &cookie).await;
+
+    assert_eq!(status, StatusCode::UNAUTHORIZED);
+    assert!(test_db.statements().is_empty());
+}
+
+#[tokio::test]
+async fn test_unverified_local_account_loses_its_password_when_linked() {
+    let idp = start_idp(false).await;
+    // signed up with the email of the provider account, but never verified it
+    let mut squatter = user_row(2, 1, "$argon2id$v=19$m=19456,t=2,p=1$c2FsdA$aGFzaA");
+    squatter
+        .values
+        .insert("email_verified".to_string(), false.into());
+    let identity = row([
+        ("id", 1i64.into()),
+        ("user_id", 2i64.into()),
+        ("provider", "mock".into()),
+        ("subject", "idp-user-42".into()),
+        ("email", Some("alice2@none.co".to_string()).into()),
+        ("create_at", Option::<DateTimeWithTimeZone>::None.into()),
+    ]);
+    let test_db = TestDb::new(vec![
+        vec![],
+        vec![squatter],
+        vec![identity],
+        vec![],
+        vec![],
+        vec![refresh_token_row()],
+    ])
+    .await;
+    let app = app(&test_db, &idp).await;
+
+    let (state, cookie) = start_login(&app, &idp).await;
+    let (status, body) = callback(&app, &state, &cookie).await;
+
+    assert_eq!(status, StatusCode::OK, "{}", body);
+    let statements = test_db.statements();
+    assert!(
+        statements.iter().any(|sql| {
+            sql.starts_with("UPDATE \"public\".\"users\"")
+                && sql.contains("\"password_hash\" = ''")
+                && sql.contains("\"email_verified\" = TRUE")
+        }),
+        "{:#?}",
+        statements
+    );
+    assert!(statements
+        .iter()
+        .any(|sql| sql.starts_with("UPDATE \"public\".\"refresh_token\" SET \"revoked\" = TRUE")));
+}
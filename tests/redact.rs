@@ -0,0 +1,155 @@
+mod common;
+
+use axum::body::Body;
+use axum::extract::ConnectInfo;
+use axum_template::common::hash_password;
+use common::{app, read_json, row, send, user_row, CapturedLogs, TestDb};
+use http::{header, Request, StatusCode};
+use sea_orm::prelude::DateTimeWithTimeZone;
+use std::net::SocketAddr;
+use tower::ServiceExt;
+use tracing::subscriber::DefaultGuard;
+use tracing_subscriber::EnvFilter;
+
+/// The log output of the current thread, as written by the handlers, before any scrubbing.
+struct Captured(CapturedLogs);
+
+impl Captured {
+    /// Captures at the debug level, sea_orm as configured.
+    fn start() -> (Self, DefaultGuard) {
+        let captured = CapturedLogs::default();
+        let writer = captured.clone();
+        let subscriber = tracing_subscriber::fmt()
+            .with_env_filter(EnvFilter::new("debug,sea_orm=warn"))
+            .with_writer(move || writer.clone())
+            .with_ansi(false)
+            .finish();
+        (Self(captured), tracing::subscriber::set_default(subscriber))
+    }
+
+    fn output(&self) -> String {
+        self.0.output()
+    }
+
+    fn assert_hides(&self, secrets: &[&str]) {
+        let output = self.output();
+        assert!(!output.is_empty());
+        for secret in secrets {
+            assert!(
+                !output.contains(secret),
+                "{} is logged:\n{}",
+                secret,
+                output
+            );
+        }
+    }
+}
+
+#[tokio::test]
+async fn test_login_logs_neither_tokens_nor_credentials() {
+    let refresh_token = row([
+        ("id", 1i64.into()),
+        ("jti", "jti".into()),
+        ("family_id", "family".into()),
+        ("user_id", 2i64.into()),
+        ("expires_at", DateTimeWithTimeZone::default().into()),
+        ("revoked", false.into()),
+        ("replaced_by", Option::<String>::None.into()),
+        ("create_at", Option::<DateTimeWithTimeZone>::None.into()),
+    ]);
+    let password_hash = hash_password("a1234567").await.unwrap();
+    let test_db = TestDb::new(vec![
+        vec![user_row(2, 1, &password_hash)],
+        // no second factor, no roles
+        vec![],
+        vec![],
+        vec![refresh_token],
+    ])
+    .await;
+    let app = app(&test_db.db).await;
+
+    let (captured, _guard) = Captured::start();
+    let body = serde_json::json!({ "account": "alice2@none.co", "password": "a1234567" });
+    let mut request = Request::post("/auth/login")
+        .header(header::CONTENT_TYPE, "application/json")
+        .body(Body::from(body.to_string()))
+        .unwrap();
+    request
+        .extensions_mut()
+        .insert(ConnectInfo(SocketAddr::from(([10, 0, 0, 1], 40000))));
+    let (status, body) = read_json(app.oneshot(request).await.unwrap()).await;
+    assert_eq!(status, StatusCode::OK, "{}", body);
+
+    captured.assert_hides(&[
+        body["data"]["access_token"].as_str().unwrap(),
+        body["data"]["refresh_token"].as_str().unwrap(),
+        "alice2@none.co",
+        "a1234567",
+        &password_hash,
+    ]);
+    assert!(captured.output().contains("a***@none.co"));
+}
+
+#[tokio::test]
+async fn test_user_query_logs_no_personal_data() {
+    let password_hash = hash_password("a1234567").await.unwrap();
+    let test_db = TestDb::new(vec![vec![user_row(2, 1, &password_hash)]]).await;
+    let app = app(&test_db.db).await;
+    let token = common::token(&["user:read"]);
+
+    let (captured, _guard) = Captured::start();
+    let request = Request::get("/api/get_user?id=2")
+        .header(header::AUTHORIZATION, format!("Bearer {}", token))
+        .body(Body::empty())
+        .unwrap();
+    let (status, body) = read_json(app.oneshot(request).await.unwrap()).await;
+    assert_eq!(status, StatusCode::OK, "{}", body);
+    assert_eq!(body["data"][0]["id"], 2);
+
+    captured.assert_hides(&[&token, "alice2@none.co", "bobby@none.co", &password_hash]);
+    assert!(captured.output().contains(r#""authorization": "***""#));
+}
+
+#[tokio::test]
+async fn test_rejected_token_is_logged_as_a_fingerprint() {
+    let test_db = TestDb::new(vec![]).await;
+    let app = app(&test_db.db).await;
+    // a valid token whose signature no longer matches
+    let token = format!("{}x", common::token(&["user:read"]));
+
+    let (captured, _guard) = Captured::start();
+    let request = Request::get("/api/get_user?id=2")
+        .header(header::AUTHORIZATION, format!("Bearer {}", token))
+        .body(Body::empty())
+        .unwrap();
+    let response = app.oneshot(request).await.unwrap();
+    assert_eq!(response.status(), StatusCode::UNAUTHORIZED);
+
+    captured.assert_hides(&[&token]);
+    assert!(captured.output().contains("Invalid token [token "));
+}
+
+#[tokio::test]
+async fn test_rejected_password_is_not_logged() {
+    let test_db = TestDb::new(vec![]).await;
+    let app = app(&test_db.db).await;
+    let body = serde_json::json!({
+        "fullname": "Mallory",
+        "email": "mallory@none.co",
+        "password": "hunter2",
+        "ws_id": 1,
+    });
+
+    let (captured, _guard) = Captured::start();
+    let (status, body) = send(
+        &app,
+        Request::post("/api/create_user"),
+        &["user:create"],
+        Some(body),
+    )
+    .await;
+    assert_eq!(status, StatusCode::BAD_REQUEST, "{}", body);
+
+    captured.assert_hides(&["hunter2", "mallory@none.co"]);
+    assert!(test_db.statements().is_empty());
+}
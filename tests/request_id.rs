@@ -0,0 +1,95 @@
+mod common;
+
+use axum::body::Body;
+use axum::extract::ConnectInfo;
+use axum::routing::get;
+use axum::Router;
+use axum_template::application;
+use axum_template::request_id::RequestId;
+use common::{read_json, TestDb};
+use http::{header, Request, StatusCode};
+use http_body_util::BodyExt;
+use std::net::SocketAddr;
+use tower::ServiceExt;
+
+fn request(path: &str, request_id: Option<&str>) -> Request<Body> {
+    let mut request = Request::get(path);
+    if let Some(id) = request_id {
+        request = request.header("x-request-id", id);
+    }
+    request.body(Body::empty()).unwrap()
+}
+
+#[tokio::test]
+async fn test_incoming_id_is_echoed_and_quoted_in_errors() {
+    let test_db = TestDb::new(vec![]).await;
+    let app = common::app(&test_db.db).await;
+
+    let response = app
+        .clone()
+        .oneshot(request("/health/live", Some("gw-5f1c2a")))
+        .await
+        .unwrap();
+    assert_eq!(response.headers()["x-request-id"], "gw-5f1c2a");
+
+    let response = app
+        .oneshot(request("/no/such/path", Some("gw-5f1c2b")))
+        .await
+        .unwrap();
+    assert_eq!(response.headers()["x-request-id"], "gw-5f1c2b");
+    let (status, body) = read_json(response).await;
+    assert_eq!(status, StatusCode::NOT_FOUND);
+    assert_eq!(body["request_id"], "gw-5f1c2b");
+}
+
+#[tokio::test]
+async fn test_id_is_generated_and_given_to_handlers() {
+    let test_db = TestDb::new(vec![]).await;
+    let routes = Router::new().route(
+        "/whoami",
+        get(|id: RequestId| async move { id.to_string() }),
+    );
+    let app = application::build_app(common::state(&test_db.db), routes);
+
+    // an id that is unsafe to log is replaced as well
+    for incoming in [None, Some("<script>")] {
+        let response = app
+            .clone()
+            .oneshot(request("/whoami", incoming))
+            .await
+            .unwrap();
+        let header = response.headers()["x-request-id"]
+            .to_str()
+            .unwrap()
+            .to_string();
+        let body = response.into_body().collect().await.unwrap().to_bytes();
+
+        assert_eq!(header.len(), 20);
+        assert_eq!(body, header.as_bytes());
+    }
+}
+
+#[tokio::test]
+async fn test_rejections_of_the_limits_quote_the_id() {
+    let test_db = TestDb::new(vec![]).await;
+    let app = common::app(&test_db.db).await;
+
+    let body =
+        serde_json::json!({ "account": "nobody@none.co", "password": "a".repeat(32 * 1024) });
+    let mut request = Request::post("/auth/login")
+        .header(header::CONTENT_TYPE, "application/json")
+        .body(Body::from(body.to_string()))
+        .unwrap();
+    request
+        .extensions_mut()
+        .insert(ConnectInfo(SocketAddr::from(([10, 0, 0, 1], 40000))));
+    let response = app.oneshot(request).await.unwrap();
+
+    let id = response.headers()["x-request-id"]
+        .to_str()
+        .unwrap()
+        .to_string();
+    let (status, body) = read_json(response).await;
+    assert_eq!(status, StatusCode::PAYLOAD_TOO_LARGE);
+    assert_eq!(body["request_id"], id);
+}
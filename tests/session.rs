@@ -0,0 +1,127 @@
+mod common;
+
+use axum::body::Body;
+use axum::extract::ConnectInfo;
+use axum::Router;
+use axum_template::common::hash_password;
+use axum_template::session::CookieSession;
+use axum_template::{api, application};
+use common::{read_json, row, user_row, TestDb};
+use http::{header, Request, StatusCode};
+use sea_orm::prelude::DateTimeWithTimeZone;
+use std::net::SocketAddr;
+use std::sync::Arc;
+use tower::ServiceExt;
+
+async fn app(test_db: &TestDb) -> Router {
+    let config = serde_json::from_value(serde_json::json!({ "mode": "cookie" })).unwrap();
+    let session = CookieSession::from_config(&config, 3600, 86400)
+        .unwrap()
+        .unwrap();
+
+    let mut state = common::state(&test_db.db);
+    state.cookie_session = Some(Arc::new(session));
+    application::build_app(state, api::build_routes().await)
+}
+
+fn set_cookies(response: &http::Response<Body>) -> Vec<String> {
+    response
+        .headers()
+        .get_all(header::SET_COOKIE)
+        .iter()
+        .map(|value| value.to_str().unwrap().to_string())
+        .collect()
+}
+
+#[tokio::test]
+async fn test_login_sets_http_only_cookies_instead_of_returning_tokens() {
+    let refresh_token = row([
+        ("id", 1i64.into()),
+        ("jti", "jti".into()),
+        ("family_id", "family".into()),
+        ("user_id", 2i64.into()),
+        ("expires_at", DateTimeWithTimeZone::default().into()),
+        ("revoked", false.into()),
+        ("replaced_by", Option::<String>::None.into()),
+        ("create_at", Option::<DateTimeWithTimeZone>::None.into()),
+    ]);
+    let password_hash = hash_password("a1234567").await.unwrap();
+    let test_db = TestDb::new(vec![
+        vec![user_row(2, 1, &password_hash)],
+        // no second factor, no roles
+        vec![],
+        vec![],
+        vec![refresh_token],
+    ])
+    .await;
+
+    let body = serde_json::json!({ "account": "alice2@none.co", "password": "a1234567" });
+    let mut request = Request::post("/auth/login")
+        .header(header::CONTENT_TYPE, "application/json")
+        .body(Body::from(body.to_string()))
+        .unwrap();
+    request
+        .extensions_mut()
+        .insert(ConnectInfo(SocketAddr::from(([10, 0, 0, 1], 40000))));
+    let response = app(&test_db).await.oneshot(request).await.unwrap();
+
+    let cookies = set_cookies(&response);
+    let (status, body) = read_json(response).await;
+    assert_eq!(status, StatusCode::OK, "{}", body);
+    assert!(body["data"].get("access_token").is_none());
+    let csrf_token = body["data"]["csrf_token"].as_str().unwrap();
+
+    assert_eq!(cookies.len(), 3);
+    assert!(cookies[0].starts_with("access_token=ey"));
+    assert!(cookies[0].contains("; Path=/;"));
+    assert!(cookies[1].starts_with("refresh_token=ey"));
+    assert!(cookies[1].contains("; Path=/auth/refresh;"));
+    for cookie in &cookies[..2] {
+        assert!(cookie.contains("; HttpOnly"));
+        assert!(cookie.contains("; Secure"));
+        assert!(cookie.ends_with("; SameSite=Strict"));
+    }
+    // scripts read the CSRF token from its cookie after a reload
+    assert!(cookies[2].starts_with(&format!("csrf_token={};", csrf_token)));
+    assert!(!cookies[2].contains("HttpOnly"));
+}
+
+#[tokio::test]
+async fn test_cookie_session_requires_the_csrf_token_to_change_state() {
+    let test_db = TestDb::new(vec![]).await;
+    let app = app(&test_db).await;
+    let cookie = format!("access_token={}; csrf_token=csrf-123", common::token(&[]));
+
+    let read = Request::get("/auth/get_user_info")
+        .header(header::COOKIE, &cookie)
+        .body(Body::empty())
+        .unwrap();
+    let (status, body) = read_json(app.clone().oneshot(read).await.unwrap()).await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(body["data"]["id"], 1);
+
+    for csrf_token in [None, Some("csrf-456")] {
+        let mut logout = Request::post("/auth/logout").header(header::COOKIE, &cookie);
+        if let Some(csrf_token) = csrf_token {
+            logout = logout.header("X-CSRF-Token", csrf_token);
+        }
+        let response = app
+            .clone()
+            .oneshot(logout.body(Body::empty()).unwrap())
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::FORBIDDEN);
+    }
+    assert!(test_db.statements().is_empty());
+
+    let logout = Request::post("/auth/logout")
+        .header(header::COOKIE, &cookie)
+        .header("X-CSRF-Token", "csrf-123")
+        .body(Body::empty())
+        .unwrap();
+    let response = app.clone().oneshot(logout).await.unwrap();
+    assert_eq!(response.status(), StatusCode::OK);
+    let cookies = set_cookies(&response);
+    assert_eq!(cookies.len(), 3);
+    assert!(cookies.iter().all(|cookie| cookie.contains("Max-Age=0")));
+}
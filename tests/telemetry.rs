@@ -0,0 +1,168 @@
+mod common;
+
+use axum::body::Body;
+use axum::extract::State;
+use axum::routing::get;
+use axum::{Json, Router};
+use axum_template::oidc::{OidcProvider, OidcProviders};
+use axum_template::{api, application, telemetry};
+use common::TestDb;
+use http::{HeaderMap, Request, StatusCode};
+use opentelemetry::trace::{SpanKind, TraceContextExt, TraceId};
+use opentelemetry::Value;
+use opentelemetry_sdk::trace::{InMemorySpanExporter, SdkTracerProvider, SpanData};
+use sea_orm::metric::Info;
+use sea_orm::{DbBackend, Statement};
+use std::sync::{Arc, LazyLock, Mutex};
+use std::time::Duration;
+use tower::ServiceExt;
+use tracing_opentelemetry::OpenTelemetrySpanExt;
+use tracing_subscriber::layer::SubscriberExt;
+use tracing_subscriber::util::SubscriberInitExt;
+
+const TRACE_ID: &str = "4bf92f3577b34da6a3ce929d0e0e4736";
+const CALLER_SPAN_ID: &str = "00f067aa0ba902b7";
+
+/// Collector stand-in: the pipeline of `logger::init`, exporting to memory.
+static EXPORTER: LazyLock<InMemorySpanExporter> = LazyLock::new(|| {
+    let exporter = InMemorySpanExporter::default();
+    let provider = SdkTracerProvider::builder()
+        .with_simple_exporter(exporter.clone())
+        .build();
+    telemetry::install(&provider);
+    tracing_subscriber::registry()
+        .with(telemetry::layer(&provider))
+        .init();
+    exporter
+});
+
+/// The exported spans of the trace.
+fn spans(trace_id: TraceId) -> Vec<SpanData> {
+    EXPORTER
+        .get_finished_spans()
+        .unwrap()
+        .into_iter()
+        .filter(|span| span.span_context.trace_id() == trace_id)
+        .collect()
+}
+
+fn find<'a>(spans: &'a [SpanData], name: &str) -> &'a SpanData {
+    spans
+        .iter()
+        .find(|span| span.name == name)
+        .unwrap_or_else(|| panic!("no span {} in {:?}", name, spans))
+}
+
+fn attribute(span: &SpanData, key: &str) -> Option<Value> {
+    span.attributes
+        .iter()
+        .find(|attribute| attribute.key.as_str() == key)
+        .map(|attribute| attribute.value.clone())
+}
+
+/// An OpenID Connect provider only serving its discovery document, recording the
+/// `traceparent` header it received.
+async fn start_idp() -> (String, Arc<Mutex<Option<String>>>) {
+    let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let issuer = format!("http://{}", listener.local_addr().unwrap());
+    let traceparent = Arc::new(Mutex::new(None));
+
+    let router = Router::new()
+        .route(
+            "/.well-known/openid-configuration",
+            get(
+                |State((issuer, traceparent)): State<(String, Arc<Mutex<Option<String>>>)>,
+                 headers: HeaderMap| async move {
+                    *traceparent.lock().unwrap() = headers
+                        .get("traceparent")
+                        .map(|value| value.to_str().unwrap().to_string());
+                    Json(serde_json::json!({
+                        "issuer": issuer,
+                        "authorization_endpoint": format!("{}/authorize", issuer),
+                        "token_endpoint": format!("{}/token", issuer),
+                        "jwks_uri": format!("{}/jwks", issuer),
+                    }))
+                },
+            ),
+        )
+        .with_state((issuer.clone(), traceparent.clone()));
+    tokio::spawn(async move { axum::serve(listener, router).await.unwrap() });
+
+    (issuer, traceparent)
+}
+
+#[tokio::test]
+async fn test_incoming_trace_is_continued_and_propagated() {
+    LazyLock::force(&EXPORTER);
+    let (issuer, received) = start_idp().await;
+    let config = serde_json::from_value(serde_json::json!({
+        "issuer": issuer,
+        "client_id": "axum-app",
+    }))
+    .unwrap();
+    let provider = OidcProvider::new("mock", &config, "http://127.0.0.1:3005").unwrap();
+    let test_db = TestDb::new(vec![]).await;
+    let mut state = common::state(&test_db.db);
+    state.oidc = Arc::new(OidcProviders::new(vec![provider]));
+    let app = application::build_app(state, api::build_routes().await);
+
+    let request = Request::get("/auth/oidc/mock/login")
+        .header(
+            "traceparent",
+            format!("00-{}-{}-01", TRACE_ID, CALLER_SPAN_ID),
+        )
+        .body(Body::empty())
+        .unwrap();
+    let response = app.oneshot(request).await.unwrap();
+    assert_eq!(response.status(), StatusCode::SEE_OTHER);
+    drop(response);
+
+    let spans = spans(TraceId::from_hex(TRACE_ID).unwrap());
+    let server = find(&spans, "GET /auth/oidc/{provider}/login");
+    assert_eq!(server.span_kind, SpanKind::Server);
+    assert_eq!(server.parent_span_id.to_string(), CALLER_SPAN_ID);
+    assert_eq!(
+        attribute(server, "http.response.status_code"),
+        Some(Value::I64(303))
+    );
+
+    let handler = find(&spans, "oidc_login");
+    assert_eq!(handler.parent_span_id, server.span_context.span_id());
+
+    // the provider was called on behalf of the handler
+    assert_eq!(
+        received.lock().unwrap().clone(),
+        Some(format!(
+            "00-{}-{}-01",
+            TRACE_ID,
+            handler.span_context.span_id()
+        ))
+    );
+}
+
+#[tokio::test]
+async fn test_queries_are_child_spans_of_the_current_span() {
+    LazyLock::force(&EXPORTER);
+    let statement = Statement::from_string(DbBackend::Postgres, r#"SELECT "id" FROM "users""#);
+    let info = Info {
+        elapsed: Duration::from_millis(12),
+        statement: &statement,
+        failed: false,
+    };
+
+    let handler = tracing::info_span!("handler");
+    handler.in_scope(|| telemetry::record_query(&info));
+    let context = handler.context();
+    let parent = context.span().span_context().clone();
+
+    let spans = spans(parent.trace_id());
+    let query = find(&spans, "SELECT");
+    assert_eq!(query.span_kind, SpanKind::Client);
+    assert_eq!(query.parent_span_id, parent.span_id());
+    assert_eq!(
+        attribute(query, "db.query.text"),
+        Some(Value::from(r#"SELECT "id" FROM "users""#))
+    );
+    let elapsed = query.end_time.duration_since(query.start_time).unwrap();
+    assert_eq!(elapsed, Duration::from_millis(12));
+}
@@ -0,0 +1,200 @@
+mod common;
+
+use axum::body::Body;
+use axum_template::auth::{get_jwt, Principal};
+use common::{app, row, send, user_row, TestDb};
+use http::{header, Request, StatusCode};
+use sea_orm::prelude::DateTimeWithTimeZone;
+use tower::ServiceExt;
+
+const ALL_PERMISSIONS: [&str; 6] = [
+    "user:create",
+    "user:read",
+    "user:update",
+    "user:transfer",
+    "user:delete",
+    "workspace:create",
+];
+
+/// Every statement sent to the database must be restricted to workspace `1`.
+fn assert_scoped(test_db: &TestDb) {
+    let statements = test_db.statements();
+    assert!(!statements.is_empty());
+    for sql in statements {
+        assert!(sql.contains("\"ws_id\" = 1"), "unscoped statement: {}", sql);
+    }
+}
+
+#[tokio::test]
+async fn test_reads_only_see_the_callers_workspace() {
+    let test_db = TestDb::new(vec![vec![user_row(2, 1, "")]]).await;
+
+    let (status, body) = send(
+        &app(&test_db.db).await,
+        Request::get("/api/get_user?name=Alice"),
+        &ALL_PERMISSIONS,
+        None,
+    )
+    .await;
+
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(body["data"][0]["wsId"], 1);
+    assert!(body["data"][0].get("passwordHash").is_none());
+    assert_scoped(&test_db);
+}
+
+#[tokio::test]
+async fn test_keyword_search_is_scoped() {
+    let test_db = TestDb::new(vec![]).await;
+
+    let (status, _) = send(
+        &app(&test_db.db).await,
+        Request::get("/api/query_by_keyword?keyword=bob"),
+        &ALL_PERMISSIONS,
+        None,
+    )
+    .await;
+
+    assert_eq!(status, StatusCode::OK);
+    assert_scoped(&test_db);
+}
+
+#[tokio::test]
+async fn test_users_of_other_workspaces_cannot_be_deleted() {
+    // the scoped delete matches nothing when user 9 lives in another workspace
+    let test_db = TestDb::new(vec![]).await;
+
+    let (status, body) = send(
+        &app(&test_db.db).await,
+        Request::delete("/api/delete_user_by_id/9"),
+        &ALL_PERMISSIONS,
+        None,
+    )
+    .await;
+
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(body["code"], 0);
+    assert_scoped(&test_db);
+}
+
+#[tokio::test]
+async fn test_users_cannot_be_created_in_other_workspaces() {
+    let test_db = TestDb::new(vec![]).await;
+    let body = serde_json::json!({
+        "fullname": "Mallory",
+        "email": "mallory@none.co",
+        "password": "a1234567",
+        "ws_id": 2,
+    });
+
+    let (status, _) = send(
+        &app(&test_db.db).await,
+        Request::post("/api/create_user"),
+        &ALL_PERMISSIONS,
+        Some(body),
+    )
+    .await;
+
+    assert_eq!(status, StatusCode::FORBIDDEN);
+    assert!(test_db.statements().is_empty());
+}
+
+#[tokio::test]
+async fn test_users_cannot_be_moved_into_foreign_workspaces() {
+    // workspace 2 is not owned by the caller, the lookup finds nothing
+    let test_db = TestDb::new(vec![]).await;
+
+    let (status, _) = send(
+        &app(&test_db.db).await,
+        Request::patch("/api/update_user_ws_by_id/2/2"),
+        &ALL_PERMISSIONS,
+        None,
+    )
+    .await;
+
+    assert_eq!(status, StatusCode::FORBIDDEN);
+    assert!(test_db
+        .statements()
+        .iter()
+        .all(|sql| !sql.starts_with("UPDATE")));
+}
+
+#[tokio::test]
+async fn test_moving_users_out_of_the_workspace_requires_the_transfer_permission() {
+    let test_db = TestDb::new(vec![]).await;
+    let (status, _) = send(
+        &app(&test_db.db).await,
+        Request::patch("/api/update_user_ws_by_id/2/2"),
+        &["user:update"],
+        None,
+    )
+    .await;
+
+    assert_eq!(status, StatusCode::FORBIDDEN);
+    assert!(test_db.statements().is_empty());
+}
+
+#[tokio::test]
+async fn test_users_can_be_moved_into_owned_workspaces() {
+    let owned_workspace = row([
+        ("id", 2i64.into()),
+        ("name", "ws-bobby".into()),
+        ("owner_id", 1i64.into()),
+        ("create_at", Option::<DateTimeWithTimeZone>::None.into()),
+    ]);
+    let test_db = TestDb::new(vec![
+        vec![owned_workspace],
+        vec![user_row(2, 1, "")],
+        vec![user_row(2, 2, "")],
+    ])
+    .await;
+
+    let (status, body) = send(
+        &app(&test_db.db).await,
+        Request::patch("/api/update_user_ws_by_id/2/2"),
+        &ALL_PERMISSIONS,
+        None,
+    )
+    .await;
+
+    assert_eq!(status, StatusCode::OK, "{}", body);
+    assert_eq!(body["data"]["wsId"], 2);
+    let statements = test_db.statements();
+    assert!(statements[1].contains("\"ws_id\" = 1"), "{}", statements[1]);
+    assert!(statements[2].starts_with("UPDATE"), "{}", statements[2]);
+}
+
+#[tokio::test]
+async fn test_workspaces_cannot_be_created_for_other_users() {
+    let test_db = TestDb::new(vec![]).await;
+    let body = serde_json::json!({ "name": "ws-mallory", "owner_id": 2 });
+
+    let (status, _) = send(
+        &app(&test_db.db).await,
+        Request::post("/api/create_workspace"),
+        &ALL_PERMISSIONS,
+        Some(body),
+    )
+    .await;
+
+    assert_eq!(status, StatusCode::FORBIDDEN);
+    assert!(test_db.statements().is_empty());
+}
+
+#[tokio::test]
+async fn test_tokens_without_workspace_must_login_again() {
+    let test_db = TestDb::new(vec![]).await;
+    let principal = Principal {
+        ws_id: None,
+        ..common::principal(&ALL_PERMISSIONS)
+    };
+    let token = get_jwt().encode(&principal, "session").unwrap();
+    let request = Request::get("/api/get_user")
+        .header(header::AUTHORIZATION, format!("Bearer {}", token))
+        .body(Body::empty())
+        .unwrap();
+
+    let response = app(&test_db.db).await.oneshot(request).await.unwrap();
+
+    assert_eq!(response.status(), StatusCode::UNAUTHORIZED);
+}